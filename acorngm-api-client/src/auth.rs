@@ -0,0 +1,165 @@
+//! Logging in, accounts and their login provider identities.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AuthProvider {
+    /// used in urls
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuthProviderList {
+    pub providers: Vec<AuthProvider>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderAuthResponse {
+    /// whether the identity has no account yet
+    pub register: bool,
+    pub provider: String,
+    pub provider_user_id: String,
+    /// only for existing accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// only for new identities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_access_token: Option<String>,
+    /// only for new identities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_username: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordAuthResponse {
+    pub register: bool,
+    pub discord_user_id: String,
+    /// only for existing accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// only for new identities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discord_access_token: Option<String>,
+    /// only for new identities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discord_username: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterRequest {
+    pub username: String,
+    /// older clients only know discord
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    #[serde(alias = "discord_user_id")]
+    pub provider_user_id: String,
+    #[serde(alias = "discord_access_token")]
+    pub provider_access_token: String,
+}
+
+fn default_provider() -> String {
+    "discord".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TempLoginRequest {
+    pub temp_login_token: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AccessTokenResponse {
+    pub access_token: String,
+    /// the account the access token belongs to
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AccountRequest {
+    pub username: String,
+    pub access_token: String,
+}
+
+/// What anyone may see about an account; display name and avatar come from the last login.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An identity at an external login provider that can be used to log into an account.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedIdentity {
+    pub provider: String,
+    pub provider_user_id: String,
+    pub provider_username: Option<String>,
+    pub linked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkedIdentityList {
+    pub identities: Vec<LinkedIdentity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkIdentityRequest {
+    pub username: String,
+    pub access_token: String,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub provider_access_token: String,
+}
+
+/// Also used to start moving the account away from the identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnlinkIdentityRequest {
+    pub username: String,
+    pub access_token: String,
+    pub provider: String,
+    pub provider_user_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityMoveStarted {
+    pub move_code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConfirmIdentityMoveRequest {
+    pub move_code: String,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub provider_access_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IdentityMoved {
+    pub username: String,
+}
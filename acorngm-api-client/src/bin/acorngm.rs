@@ -0,0 +1,360 @@
+//! `acorngm`: use an AcornGM server from the command line.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use uuid::Uuid;
+use acorngm_api_client::mods::{ModRatings, ModSearch, ModSearchResults, ModSuggestions, ModSummary, SearchMode, SearchSort};
+use acorngm_api_client::screenshots::ScreenshotInfo;
+use acorngm_api_client::tags::ModCategory;
+use acorngm_api_client::{Client, Credentials, ModChanges, NewMod};
+
+
+#[derive(Parser)]
+#[command(version, about = "Use an AcornGM server from the command line")]
+struct Cli {
+    /// where the server's frontend is
+    #[arg(long, env = "ACORNGM_SERVER", default_value = "https://acorngm.biotomatede.hackclub.app")]
+    server: String,
+    /// `login` saves the account's access token here; commands that need an account read it from here
+    #[arg(long, env = "ACORNGM_CREDENTIALS", default_value = "acorngm_credentials.json")]
+    credentials: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the login providers of the server
+    Providers,
+    /// Log in (or register) in the browser and save the credentials
+    Login {
+        #[arg(long, default_value = "discord")]
+        provider: String,
+        /// seconds to wait for the login to be finished
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+    },
+    /// List approved mods, newest first
+    List {
+        #[arg(long, default_value_t = 0)]
+        page: u32,
+    },
+    /// Search approved mods
+    Search {
+        /// may be empty to only filter
+        query: String,
+        #[arg(long, value_enum, default_value_t = SearchMode::Basic)]
+        mode: SearchMode,
+        #[arg(long, value_enum, default_value_t = SearchSort::Relevance)]
+        sort: SearchSort,
+        /// `Undertale` or `Deltarune`
+        #[arg(long)]
+        game: Option<String>,
+        /// `major.minor`
+        #[arg(long)]
+        game_version: Option<String>,
+        /// only mods with all of these tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        author: Option<String>,
+        /// RFC 3339, e.g. `2025-01-31T00:00:00Z`
+        #[arg(long)]
+        updated_since: Option<DateTime<Utc>>,
+        /// ISO 639-1 code of the language the query is written in; `en` if not given
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// List canonical and popular tags
+    Tags,
+    /// List approved mods with a tag, newest first
+    Tagged {
+        tag: String,
+        #[arg(long, default_value_t = 0)]
+        page: u32,
+    },
+    /// List approved mods in a category, newest first
+    Category {
+        #[arg(value_enum)]
+        category: ModCategory,
+        #[arg(long, default_value_t = 0)]
+        page: u32,
+    },
+    /// Complete a title or author name
+    Suggest {
+        prefix: String,
+    },
+    /// Show a mod
+    Info {
+        mod_id: Uuid,
+    },
+    /// Download the file of a mod
+    Download {
+        mod_id: Uuid,
+        /// defaults to `<mod id>.bin` in the current directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List your own mods with their review state
+    MyMods,
+    /// Upload a new mod
+    Upload {
+        file: PathBuf,
+        #[arg(long)]
+        title: String,
+        /// Markdown
+        #[arg(long, default_value = "")]
+        description: String,
+        /// `Undertale` or `Deltarune`
+        #[arg(long)]
+        game: String,
+        /// `major.minor`
+        #[arg(long)]
+        game_version: String,
+        /// ISO 639-1 code of the title and description, e.g. `es`; `en` if not given
+        #[arg(long)]
+        language: Option<String>,
+        /// at most 10
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long = "category", value_enum)]
+        categories: Vec<ModCategory>,
+    },
+    /// Replace the file, description, language, tags and/or categories of one of your mods
+    Update {
+        mod_id: Uuid,
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Markdown
+        #[arg(long)]
+        description: Option<String>,
+        /// ISO 639-1 code of the title and description
+        #[arg(long)]
+        language: Option<String>,
+        /// replaces all tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// replaces all categories
+        #[arg(long = "category", value_enum)]
+        categories: Vec<ModCategory>,
+    },
+    /// Delete one of your mods
+    Delete {
+        mod_id: Uuid,
+    },
+    /// List the screenshots of a mod
+    Screenshots {
+        mod_id: Uuid,
+    },
+    /// Add a PNG, JPEG or WebP screenshot to one of your mods
+    AddScreenshot {
+        mod_id: Uuid,
+        image: PathBuf,
+        /// show it in listings
+        #[arg(long)]
+        cover: bool,
+    },
+    /// Reorder the screenshots of one of your mods and/or pick the cover
+    ArrangeScreenshots {
+        mod_id: Uuid,
+        /// every screenshot of the mod, in the new order
+        #[arg(long, num_args = 1..)]
+        order: Option<Vec<Uuid>>,
+        #[arg(long)]
+        cover: Option<Uuid>,
+    },
+    /// Remove a screenshot from one of your mods
+    RemoveScreenshot {
+        mod_id: Uuid,
+        screenshot_id: Uuid,
+    },
+    /// Rate a mod of someone else
+    Rate {
+        mod_id: Uuid,
+        /// 1 to 5
+        #[arg(value_parser = clap::value_parser!(i16).range(1..=5))]
+        stars: i16,
+    },
+}
+
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let client = Client::new(&cli.server).map_err(|e| e.to_string())?;
+
+    match cli.command {
+        Command::Providers => {
+            for provider in client.auth_providers().await.map_err(|e| e.to_string())? {
+                println!("{}\t{}", provider.name, provider.display_name);
+            }
+        }
+        Command::Login { provider, timeout } => {
+            let login = client.start_login(&provider).map_err(|e| e.to_string())?;
+            println!("Open this url in your browser to log in:\n{}", login.url);
+            let credentials: Credentials = client.finish_login(&login, Duration::from_secs(timeout)).await.map_err(|e| e.to_string())?;
+            save_credentials(&cli.credentials, &credentials).await?;
+            println!("Logged in as {}; saved credentials to {}", credentials.username, cli.credentials.display());
+        }
+        Command::List { page } => {
+            client.list_mods(page).await.map_err(|e| e.to_string())?.iter().for_each(print_mod);
+        }
+        Command::Search { query, mode, sort, game, game_version, tags, author, updated_since, language } => {
+            let search = ModSearch { query, mode: Some(mode), sort: Some(sort), game, game_version, tags, author, updated_since, language };
+            let results: ModSearchResults = client.search_mods(&search).await.map_err(|e| e.to_string())?;
+            results.mods.iter().for_each(print_mod);
+            for (name, counts) in [
+                ("games", &results.facets.games),
+                ("game versions", &results.facets.game_versions),
+                ("authors", &results.facets.authors),
+                ("tags", &results.facets.tags),
+            ] {
+                if !counts.is_empty() {
+                    let counts: Vec<String> = counts.iter().map(|facet| format!("{} ({})", facet.value, facet.count)).collect();
+                    println!("{name}: {}", counts.join(", "));
+                }
+            }
+        }
+        Command::Tags => {
+            for tag in client.tags().await.map_err(|e| e.to_string())? {
+                let aliases: String = if tag.aliases.is_empty() { String::new() } else { format!(" (also {})", tag.aliases.join(", ")) };
+                println!("{}\t{} mods{aliases}", tag.tag, tag.mod_count);
+            }
+        }
+        Command::Tagged { tag, page } => {
+            client.mods_with_tag(&tag, page).await.map_err(|e| e.to_string())?.iter().for_each(print_mod);
+        }
+        Command::Category { category, page } => {
+            client.mods_in_category(category, page).await.map_err(|e| e.to_string())?.iter().for_each(print_mod);
+        }
+        Command::Suggest { prefix } => {
+            let suggestions: ModSuggestions = client.suggest_mods(&prefix).await.map_err(|e| e.to_string())?;
+            for title in &suggestions.titles {
+                println!("{}\t{}", title.id, title.title);
+            }
+            for author in &suggestions.authors {
+                println!("by {} ({} mods)", author.username, author.mod_count);
+            }
+        }
+        Command::Info { mod_id } => {
+            let summary: ModSummary = client.get_mod(mod_id).await.map_err(|e| e.to_string())?;
+            print_mod(&summary);
+            println!("uploaded {}, language {}", summary.created_at, summary.language);
+            println!("categories: {}; tags: {}\n\n{}", summary.categories.join(", "), summary.tags.join(", "), summary.description);
+        }
+        Command::Download { mod_id, output } => {
+            let file_data: Vec<u8> = client.download_mod(mod_id).await.map_err(|e| e.to_string())?;
+            let output: PathBuf = output.unwrap_or_else(|| PathBuf::from(format!("{mod_id}.bin")));
+            tokio::fs::write(&output, &file_data).await
+                .map_err(|e| format!("Could not write {}: {e}", output.display()))?;
+            println!("Saved {} bytes to {}", file_data.len(), output.display());
+        }
+        Command::MyMods => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            for status in client.own_mods(&credentials).await.map_err(|e| e.to_string())? {
+                let reason: String = status.review_reason.map(|reason| format!(": {reason}")).unwrap_or_default();
+                println!("{}\t{} (v{})\t{}{reason}", status.id, status.title, status.mod_version, status.review_state);
+            }
+        }
+        Command::Upload { file, title, description, game, game_version, language, tags, categories } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            let file_data: Vec<u8> = read_file(&file).await?;
+            let new_mod = NewMod { title, description, game_name: game, game_version, language, tags, categories, file_data };
+            let uploaded = client.upload_mod(&credentials, new_mod).await.map_err(|e| e.to_string())?;
+            println!("Uploaded mod {} ({})", uploaded.mod_id, uploaded.review_state);
+        }
+        Command::Update { mod_id, file, description, language, tags, categories } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            let file_data: Option<Vec<u8>> = match file {
+                Some(file) => Some(read_file(&file).await?),
+                None => None,
+            };
+            let changes = ModChanges {
+                file_data,
+                description,
+                language,
+                tags: (!tags.is_empty()).then_some(tags),
+                categories: (!categories.is_empty()).then_some(categories),
+            };
+            client.update_mod(&credentials, mod_id, changes).await.map_err(|e| e.to_string())?;
+            println!("Updated mod {mod_id}");
+        }
+        Command::Delete { mod_id } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            client.delete_mod(&credentials, mod_id).await.map_err(|e| e.to_string())?;
+            println!("Deleted mod {mod_id}");
+        }
+        Command::Screenshots { mod_id } => {
+            client.screenshots(mod_id).await.map_err(|e| e.to_string())?.iter().for_each(print_screenshot);
+        }
+        Command::AddScreenshot { mod_id, image, cover } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            let image: Vec<u8> = read_file(&image).await?;
+            let screenshot: ScreenshotInfo = client.upload_screenshot(&credentials, mod_id, image, cover).await.map_err(|e| e.to_string())?;
+            print_screenshot(&screenshot);
+        }
+        Command::ArrangeScreenshots { mod_id, order, cover } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            client.arrange_screenshots(&credentials, mod_id, order, cover).await.map_err(|e| e.to_string())?.iter().for_each(print_screenshot);
+        }
+        Command::RemoveScreenshot { mod_id, screenshot_id } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            client.delete_screenshot(&credentials, mod_id, screenshot_id).await.map_err(|e| e.to_string())?;
+            println!("Removed screenshot {screenshot_id}");
+        }
+        Command::Rate { mod_id, stars } => {
+            let credentials: Credentials = load_credentials(&cli.credentials).await?;
+            let ratings: ModRatings = client.rate_mod(&credentials, mod_id, stars).await.map_err(|e| e.to_string())?;
+            println!("Rated mod {mod_id}: {:.1} stars from {} ratings", ratings.rating_average.unwrap_or_default(), ratings.rating_count);
+        }
+    }
+    Ok(())
+}
+
+
+fn print_mod(summary: &ModSummary) {
+    println!("{}\t{} by {} ({} {}.{}, v{})", summary.id, summary.title, summary.author,
+        summary.game_name, summary.game_version_major, summary.game_version_minor, summary.mod_version);
+}
+
+fn print_screenshot(screenshot: &ScreenshotInfo) {
+    let cover: &str = if screenshot.cover { ", cover" } else { "" };
+    println!("{}\t{}. {}x{} {}{cover}", screenshot.id, screenshot.position + 1, screenshot.width, screenshot.height, screenshot.content_type);
+}
+
+async fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    tokio::fs::read(path).await.map_err(|e| format!("Could not read {}: {e}", path.display()))
+}
+
+async fn load_credentials(path: &Path) -> Result<Credentials, String> {
+    let json: String = tokio::fs::read_to_string(path).await
+        .map_err(|e| format!("Could not read credentials from {} (log in first): {e}", path.display()))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid credentials file {}: {e}", path.display()))
+}
+
+async fn save_credentials(path: &Path, credentials: &Credentials) -> Result<(), String> {
+    let json: String = serde_json::to_string_pretty(credentials).map_err(|e| format!("Could not serialize credentials: {e}"))?;
+    tokio::fs::write(path, json).await.map_err(|e| format!("Could not write credentials to {}: {e}", path.display()))?;
+
+    // the access token is as good as a password
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await
+            .map_err(|e| format!("Could not restrict permissions of {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
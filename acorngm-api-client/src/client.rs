@@ -0,0 +1,413 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use reqwest::multipart::{Form, Part};
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::auth::{AccessTokenResponse, AccountProfile, AuthProvider, AuthProviderList};
+use crate::error::ErrorResponse;
+use crate::review::ModeratorRequest;
+use crate::screenshots::{ArrangeScreenshotsRequest, DeleteScreenshotRequest, ScreenshotInfo, ScreenshotList, UploadScreenshotForm};
+use crate::tags::{CanonicalTagRequest, ModCategory, TagInfo, TagList};
+use crate::mods::{DeleteModForm, ModList, ModRatings, ModSearch, ModSearchResults, ModSuggestions, ModSummary, ModUploaded, OwnModList, OwnModStatus, OwnModsRequest, RateModRequest, UpdateModForm, UploadModForm};
+
+
+/// How often [`Client::finish_login`] asks the server whether the user is done.
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+
+/// Talks to one AcornGM server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    /// without trailing slash
+    server_url: String,
+}
+
+/// A logged in account. Access tokens only expire if the server is configured to, so these can be stored and reused;
+/// once the server answers `not_authenticated`, log in again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub access_token: String,
+}
+
+/// A login the user has to finish in their browser.
+#[derive(Debug, Clone)]
+pub struct PendingLogin {
+    /// open this in the user's browser
+    pub url: Url,
+    temp_login_token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewMod {
+    pub title: String,
+    /// Markdown
+    pub description: String,
+    /// `Undertale` or `Deltarune`
+    pub game_name: String,
+    /// `major.minor`
+    pub game_version: String,
+    /// ISO 639-1 code of the title and description; `en` if not given
+    pub language: Option<String>,
+    /// at most 10
+    pub tags: Vec<String>,
+    pub categories: Vec<ModCategory>,
+    pub file_data: Vec<u8>,
+}
+
+/// At least one field has to be set.
+#[derive(Debug, Clone, Default)]
+pub struct ModChanges {
+    pub file_data: Option<Vec<u8>>,
+    /// Markdown
+    pub description: Option<String>,
+    pub language: Option<String>,
+    /// replaces all tags; empty to remove them
+    pub tags: Option<Vec<String>>,
+    /// replaces all categories; empty to remove them
+    pub categories: Option<Vec<ModCategory>>,
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// the server responded with an error; match on `code`, all codes are listed in the server's `docs/error_codes.md`
+    Api { status: StatusCode, code: String, message: String },
+    /// the server responded with something that is not an AcornGM api response
+    UnexpectedResponse { status: StatusCode, body: String },
+    /// the server could not be reached, or the response could not be read
+    Http(reqwest::Error),
+    InvalidUrl(String),
+    /// the user did not finish logging in in time
+    LoginTimedOut,
+}
+
+impl ClientError {
+    /// the stable error code, if the server sent one
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Api { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Api { status, code, message } => write!(f, "{message} ({status}, {code})"),
+            ClientError::UnexpectedResponse { status, body } => write!(f, "Unexpected response from server ({status}): {body}"),
+            ClientError::Http(e) => write!(f, "Could not talk to server: {e}"),
+            ClientError::InvalidUrl(e) => write!(f, "Invalid url: {e}"),
+            ClientError::LoginTimedOut => write!(f, "Login was not finished in time"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> Self {
+        ClientError::Http(error)
+    }
+}
+
+
+impl Client {
+    /// `server_url` is where the server's frontend is, e.g. `https://acorngm.biotomatede.hackclub.app`.
+    pub fn new(server_url: &str) -> Result<Client, ClientError> {
+        Url::parse(server_url).map_err(|e| ClientError::InvalidUrl(format!("{server_url}: {e}")))?;
+        let http = reqwest::Client::builder()
+            .user_agent(concat!("acorngm-api-client/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Client { http, server_url: server_url.trim_end_matches('/').to_string() })
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("{}/api/v1/{path}", self.server_url)
+    }
+
+    /// like [`Client::api_url`], for paths with user given segments that need escaping
+    fn path_url(&self, segments: &[&str]) -> Result<Url, ClientError> {
+        let mut url: Url = Url::parse(&self.api_url("")).map_err(|e| ClientError::InvalidUrl(e.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| ClientError::InvalidUrl(format!("{} can not have a path", self.server_url)))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, ClientError> {
+        let response = request.send().await?;
+        let status: StatusCode = response.status();
+        let body: String = response.text().await?;
+        if !status.is_success() {
+            return Err(error_response(status, body))
+        }
+        serde_json::from_str::<T>(&body).map_err(|_| ClientError::UnexpectedResponse { status, body })
+    }
+
+    async fn send_for_bytes(request: RequestBuilder) -> Result<Vec<u8>, ClientError> {
+        let response = request.send().await?;
+        let status: StatusCode = response.status();
+        if status.is_success() {
+            return Ok(response.bytes().await?.to_vec())
+        }
+        Err(error_response(status, response.text().await?))
+    }
+
+
+    pub async fn auth_providers(&self) -> Result<Vec<AuthProvider>, ClientError> {
+        let list: AuthProviderList = Self::send(self.http.get(self.api_url("auth/providers"))).await?;
+        Ok(list.providers)
+    }
+
+    /// Starts logging in with a login provider (see [`Client::auth_providers`]).
+    /// Send the user to [`PendingLogin::url`], then wait for them with [`Client::finish_login`].
+    pub fn start_login(&self, provider: &str) -> Result<PendingLogin, ClientError> {
+        let temp_login_token: String = Uuid::new_v4().to_string();
+        let url: String = format!("{}/goto_auth/{provider}", self.server_url);
+        let url: Url = Url::parse_with_params(&url, [("temp_login_token", &temp_login_token)])
+            .map_err(|e| ClientError::InvalidUrl(format!("{url}: {e}")))?;
+        Ok(PendingLogin { url, temp_login_token })
+    }
+
+    /// Waits until the user has logged in (or registered and logged in) in their browser.
+    pub async fn finish_login(&self, login: &PendingLogin, timeout: Duration) -> Result<Credentials, ClientError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let request = self.http.post(self.api_url("access_token")).body(login.temp_login_token.clone());
+            match Self::send::<AccessTokenResponse>(request).await {
+                Ok(response) => return Ok(Credentials { username: response.username, access_token: response.access_token }),
+                // the user is not done yet
+                Err(e) if e.code() == Some("temp_login_token_not_found") => {},
+                Err(e) => return Err(e),
+            }
+            if tokio::time::Instant::now() + LOGIN_POLL_INTERVAL > deadline {
+                return Err(ClientError::LoginTimedOut)
+            }
+            tokio::time::sleep(LOGIN_POLL_INTERVAL).await;
+        }
+    }
+
+    pub async fn account(&self, username: &str) -> Result<AccountProfile, ClientError> {
+        Self::send(self.http.get(self.path_url(&["account", username])?)).await
+    }
+
+
+    /// Approved mods, newest first; pages start at 0.
+    pub async fn list_mods(&self, page: u32) -> Result<Vec<ModSummary>, ClientError> {
+        let list: ModList = Self::send(self.http.get(self.api_url("mods")).query(&[("page", page)])).await?;
+        Ok(list.mods)
+    }
+
+    /// The best matches and how many mods there are per game, game version, author and tag.
+    pub async fn search_mods(&self, search: &ModSearch) -> Result<ModSearchResults, ClientError> {
+        Self::send(self.http.get(self.api_url("mods/search")).query(search)).await
+    }
+
+    /// Titles and authors that start with `prefix`, for completing a search as it is typed.
+    pub async fn suggest_mods(&self, prefix: &str) -> Result<ModSuggestions, ClientError> {
+        Self::send(self.http.get(self.api_url("mods/suggest")).query(&[("q", prefix)])).await
+    }
+
+    pub async fn get_mod(&self, mod_id: Uuid) -> Result<ModSummary, ClientError> {
+        Self::send(self.http.get(self.api_url(&format!("mod/{mod_id}")))).await
+    }
+
+    pub async fn download_mod(&self, mod_id: Uuid) -> Result<Vec<u8>, ClientError> {
+        Self::send_for_bytes(self.http.get(self.api_url(&format!("mod/{mod_id}/download")))).await
+    }
+
+    /// 1 to 5 stars; rating a mod again replaces the earlier rating.
+    pub async fn rate_mod(&self, credentials: &Credentials, mod_id: Uuid, rating: i16) -> Result<ModRatings, ClientError> {
+        let request = RateModRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone(), rating };
+        Self::send(self.http.put(self.api_url(&format!("mod/{mod_id}/rating"))).json(&request)).await
+    }
+
+    /// All mods of the account, including the ones not approved yet.
+    pub async fn own_mods(&self, credentials: &Credentials) -> Result<Vec<OwnModStatus>, ClientError> {
+        let request = OwnModsRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone() };
+        let list: OwnModList = Self::send(self.http.post(self.api_url("my_mods")).json(&request)).await?;
+        Ok(list.mods)
+    }
+
+    pub async fn upload_mod(&self, credentials: &Credentials, new_mod: NewMod) -> Result<ModUploaded, ClientError> {
+        let form = UploadModForm {
+            username: credentials.username.clone(),
+            access_token: credentials.access_token.clone(),
+            file_data: new_mod.file_data,
+            title: new_mod.title,
+            description: new_mod.description,
+            game_name: new_mod.game_name,
+            game_version: new_mod.game_version,
+            language: new_mod.language,
+            tags: (!new_mod.tags.is_empty()).then(|| new_mod.tags.join(",")),
+            categories: (!new_mod.categories.is_empty()).then(|| join_categories(&new_mod.categories)),
+        };
+        Self::send(self.http.put(self.api_url("mod")).multipart(upload_form(form))).await
+    }
+
+    pub async fn update_mod(&self, credentials: &Credentials, mod_id: Uuid, changes: ModChanges) -> Result<(), ClientError> {
+        let form = UpdateModForm {
+            username: credentials.username.clone(),
+            access_token: credentials.access_token.clone(),
+            mod_id,
+            file_data: changes.file_data,
+            description: changes.description,
+            language: changes.language,
+            tags: changes.tags.map(|tags| tags.join(",")),
+            categories: changes.categories.as_deref().map(join_categories),
+        };
+        Self::send::<IgnoredAny>(self.http.patch(self.api_url("mod")).multipart(update_form(form))).await?;
+        Ok(())
+    }
+
+    pub async fn delete_mod(&self, credentials: &Credentials, mod_id: Uuid) -> Result<(), ClientError> {
+        let form = DeleteModForm {
+            username: credentials.username.clone(),
+            access_token: credentials.access_token.clone(),
+            mod_id,
+        };
+        Self::send::<IgnoredAny>(self.http.delete(self.api_url("mod")).multipart(delete_form(form))).await?;
+        Ok(())
+    }
+
+
+    /// Canonical tags and the most used others, most used first.
+    pub async fn tags(&self) -> Result<Vec<TagInfo>, ClientError> {
+        let list: TagList = Self::send(self.http.get(self.api_url("tags"))).await?;
+        Ok(list.tags)
+    }
+
+    /// Approved mods with the tag (or the canonical tag it is an alias of), newest first; pages start at 0.
+    pub async fn mods_with_tag(&self, tag: &str, page: u32) -> Result<Vec<ModSummary>, ClientError> {
+        let url: Url = self.path_url(&["tags", tag, "mods"])?;
+        let list: ModList = Self::send(self.http.get(url).query(&[("page", page)])).await?;
+        Ok(list.mods)
+    }
+
+    /// Approved mods in the category, newest first; pages start at 0.
+    pub async fn mods_in_category(&self, category: ModCategory, page: u32) -> Result<Vec<ModSummary>, ClientError> {
+        let url: String = self.api_url(&format!("categories/{}/mods", category.as_str()));
+        let list: ModList = Self::send(self.http.get(url).query(&[("page", page)])).await?;
+        Ok(list.mods)
+    }
+
+    /// Moderators only: makes `tag` canonical with exactly these aliases, and retags mods tagged with an alias.
+    pub async fn set_canonical_tag(&self, credentials: &Credentials, tag: &str, aliases: Vec<String>) -> Result<TagInfo, ClientError> {
+        let request = CanonicalTagRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone(), aliases };
+        Self::send(self.http.put(self.path_url(&["tags", tag])?).json(&request)).await
+    }
+
+    /// Moderators only: `tag` stops being canonical and its aliases are dropped; mods keep their tags.
+    pub async fn remove_canonical_tag(&self, credentials: &Credentials, tag: &str) -> Result<(), ClientError> {
+        let request = ModeratorRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone() };
+        Self::send::<IgnoredAny>(self.http.delete(self.path_url(&["tags", tag])?).json(&request)).await?;
+        Ok(())
+    }
+
+
+    /// The gallery of an approved mod, in order.
+    pub async fn screenshots(&self, mod_id: Uuid) -> Result<Vec<ScreenshotInfo>, ClientError> {
+        let list: ScreenshotList = Self::send(self.http.get(self.api_url(&format!("mod/{mod_id}/screenshots")))).await?;
+        Ok(list.screenshots)
+    }
+
+    /// The image itself, or its thumbnail; see [`ScreenshotInfo::content_type`] for the format.
+    pub async fn download_screenshot(&self, screenshot_id: Uuid, thumbnail: bool) -> Result<Vec<u8>, ClientError> {
+        let path: String = if thumbnail { format!("screenshots/{screenshot_id}/thumbnail") } else { format!("screenshots/{screenshot_id}") };
+        Self::send_for_bytes(self.http.get(self.api_url(&path))).await
+    }
+
+    /// Adds a PNG, JPEG or WebP image to the end of the gallery of one of your mods.
+    pub async fn upload_screenshot(&self, credentials: &Credentials, mod_id: Uuid, image: Vec<u8>, cover: bool) -> Result<ScreenshotInfo, ClientError> {
+        let form = UploadScreenshotForm {
+            username: credentials.username.clone(),
+            access_token: credentials.access_token.clone(),
+            image,
+            cover: Some(cover),
+        };
+        Self::send(self.http.put(self.api_url(&format!("mod/{mod_id}/screenshots"))).multipart(screenshot_form(form))).await
+    }
+
+    /// Reorders the gallery (`order` has to contain every screenshot) and/or picks the cover.
+    pub async fn arrange_screenshots(&self, credentials: &Credentials, mod_id: Uuid, order: Option<Vec<Uuid>>, cover: Option<Uuid>) -> Result<Vec<ScreenshotInfo>, ClientError> {
+        let request = ArrangeScreenshotsRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone(), order, cover };
+        let list: ScreenshotList = Self::send(self.http.patch(self.api_url(&format!("mod/{mod_id}/screenshots"))).json(&request)).await?;
+        Ok(list.screenshots)
+    }
+
+    pub async fn delete_screenshot(&self, credentials: &Credentials, mod_id: Uuid, screenshot_id: Uuid) -> Result<(), ClientError> {
+        let request = DeleteScreenshotRequest { username: credentials.username.clone(), access_token: credentials.access_token.clone() };
+        Self::send::<IgnoredAny>(self.http.delete(self.api_url(&format!("mod/{mod_id}/screenshots/{screenshot_id}"))).json(&request)).await?;
+        Ok(())
+    }
+}
+
+
+fn error_response(status: StatusCode, body: String) -> ClientError {
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ClientError::Api { status, code: error.code, message: error.error },
+        Err(_) => ClientError::UnexpectedResponse { status, body },
+    }
+}
+
+/// the server only takes files from parts that look like file uploads
+fn file_part(file_data: Vec<u8>) -> Part {
+    Part::bytes(file_data).file_name("mod.bin")
+}
+
+fn upload_form(form: UploadModForm) -> Form {
+    let multipart = Form::new()
+        .text("username", form.username)
+        .text("access_token", form.access_token)
+        .part("file_data", file_part(form.file_data))
+        .text("title", form.title)
+        .text("description", form.description)
+        .text("game_name", form.game_name)
+        .text("game_version", form.game_version);
+    optional_texts(multipart, [("language", form.language), ("tags", form.tags), ("categories", form.categories)])
+}
+
+fn update_form(form: UpdateModForm) -> Form {
+    let mut multipart = Form::new()
+        .text("username", form.username)
+        .text("access_token", form.access_token)
+        .text("mod_id", form.mod_id.to_string());
+    if let Some(file_data) = form.file_data {
+        multipart = multipart.part("file_data", file_part(file_data));
+    }
+    if let Some(description) = form.description {
+        multipart = multipart.text("description", description);
+    }
+    optional_texts(multipart, [("language", form.language), ("tags", form.tags), ("categories", form.categories)])
+}
+
+fn optional_texts<const N: usize>(multipart: Form, fields: [(&'static str, Option<String>); N]) -> Form {
+    fields.into_iter().fold(multipart, |multipart, (name, value)| match value {
+        Some(value) => multipart.text(name, value),
+        None => multipart,
+    })
+}
+
+fn join_categories(categories: &[ModCategory]) -> String {
+    categories.iter().map(ModCategory::as_str).collect::<Vec<_>>().join(",")
+}
+
+fn screenshot_form(form: UploadScreenshotForm) -> Form {
+    let multipart = Form::new()
+        .text("username", form.username)
+        .text("access_token", form.access_token)
+        .part("image", Part::bytes(form.image).file_name("screenshot"));
+    optional_texts(multipart, [("cover", form.cover.map(|cover| cover.to_string()))])
+}
+
+fn delete_form(form: DeleteModForm) -> Form {
+    Form::new()
+        .text("username", form.username)
+        .text("access_token", form.access_token)
+        .text("mod_id", form.mod_id.to_string())
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+
+/// The body of every error response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    /// english message for humans; may change at any time
+    #[cfg_attr(feature = "openapi", schema(example = "Mod does not exist"))]
+    pub error: String,
+    /// stable; see `docs/error_codes.md`
+    #[cfg_attr(feature = "openapi", schema(example = "mod_not_found"))]
+    pub code: String,
+}
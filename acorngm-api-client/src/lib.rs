@@ -0,0 +1,17 @@
+//! The AcornGM backend api, from the outside.
+//!
+//! The modules [`auth`], [`mods`], [`tags`], [`screenshots`], [`review`] and [`error`] hold the request and response
+//! bodies of `/api/v1`. The server uses the very same types, so they can not drift apart.
+//! [`Client`] (feature `client`, on by default) talks to a server with them.
+
+pub mod auth;
+pub mod error;
+pub mod mods;
+pub mod review;
+pub mod screenshots;
+pub mod tags;
+#[cfg(feature = "client")]
+mod client;
+
+#[cfg(feature = "client")]
+pub use client::{Client, ClientError, Credentials, ModChanges, NewMod, PendingLogin};
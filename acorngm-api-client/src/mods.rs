@@ -0,0 +1,390 @@
+//! Uploading, updating and finding mods.
+
+use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+
+/// Public view of a mod; only ever built from approved mods.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ModSummary {
+    pub id: Uuid,
+    pub author: String,
+    pub title: String,
+    /// Markdown, as written by the author
+    pub description: String,
+    /// the description rendered to sanitized HTML; safe to show as is
+    pub description_html: String,
+    pub game_name: String,
+    pub game_version_major: i32,
+    pub game_version_minor: i32,
+    /// starts at 1 and goes up whenever the file or description changes
+    pub mod_version: i32,
+    pub created_at: DateTime<Utc>,
+    /// when the file or description last changed
+    pub updated_at: DateTime<Utc>,
+    /// ISO 639-1 code of the title and description
+    pub language: String,
+    pub download_count: i64,
+    /// 1 to 5 stars; `None` until someone rated the mod
+    pub rating_average: Option<f64>,
+    pub rating_count: i64,
+    /// canonical where a moderator picked one, alphabetical
+    pub tags: Vec<String>,
+    /// see [`crate::tags::ModCategory`], alphabetical
+    pub categories: Vec<String>,
+    /// the screenshot to show in listings, see [`crate::screenshots::ScreenshotInfo`]
+    pub cover_screenshot_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModList {
+    pub mods: Vec<ModSummary>,
+}
+
+/// Response of `GET mods/search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModSearchResults {
+    /// the 50 most relevant matches
+    pub mods: Vec<ModSummary>,
+    pub facets: SearchFacets,
+}
+
+/// How many mods match per game, game version, author and tag, most common first.
+/// The counts of each facet ignore the filter on that facet itself, so they show what choosing another value would give.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFacets {
+    pub games: Vec<FacetCount>,
+    /// `major.minor`, like the `game_version` filter
+    pub game_versions: Vec<FacetCount>,
+    pub authors: Vec<FacetCount>,
+    pub tags: Vec<FacetCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Response of `GET mods/suggest`: completions of what has been typed so far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModSuggestions {
+    /// approved mods whose title starts with the text, most downloaded first (among the first 200 alphabetically)
+    pub titles: Vec<TitleSuggestion>,
+    /// authors of approved mods whose username starts with the text, most mods first (among the first 200 alphabetically)
+    pub authors: Vec<AuthorSuggestion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TitleSuggestion {
+    pub id: Uuid,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorSuggestion {
+    pub username: String,
+    pub mod_count: i64,
+}
+
+/// How `GET mods/search` matches the search terms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// whole words (and their stems), all of them
+    #[default]
+    Basic,
+    /// like `basic`, but also finds titles and descriptions that are only similar to the terms, e.g. with typos
+    Fuzzy,
+    /// every term may be the beginning of a word, for searching while typing
+    Prefix,
+}
+
+impl SearchMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchMode::Basic => "basic",
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Prefix => "prefix",
+        }
+    }
+}
+
+impl FromStr for SearchMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "basic" => Ok(SearchMode::Basic),
+            "fuzzy" => Ok(SearchMode::Fuzzy),
+            "prefix" => Ok(SearchMode::Prefix),
+            _ => Err(format!("Unknown search mode \"{mode}\"; use `basic`, `fuzzy` or `prefix`")),
+        }
+    }
+}
+
+/// The order of `GET mods/search` results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    /// text score blended with downloads, rating, recent updates and how new the mod is (weights are configured on the server)
+    #[default]
+    Relevance,
+    /// most downloaded first
+    Downloads,
+    /// best rated first; mods with few ratings count as average
+    Rating,
+    /// most recently uploaded first
+    Newest,
+    /// most recently updated first
+    Updated,
+}
+
+impl SearchSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchSort::Relevance => "relevance",
+            SearchSort::Downloads => "downloads",
+            SearchSort::Rating => "rating",
+            SearchSort::Newest => "newest",
+            SearchSort::Updated => "updated",
+        }
+    }
+}
+
+impl FromStr for SearchSort {
+    type Err = String;
+
+    fn from_str(sort: &str) -> Result<Self, Self::Err> {
+        match sort {
+            "relevance" => Ok(SearchSort::Relevance),
+            "downloads" => Ok(SearchSort::Downloads),
+            "rating" => Ok(SearchSort::Rating),
+            "newest" => Ok(SearchSort::Newest),
+            "updated" => Ok(SearchSort::Updated),
+            _ => Err(format!("Unknown sort order \"{sort}\"; use `relevance`, `downloads`, `rating`, `newest` or `updated`")),
+        }
+    }
+}
+
+/// Query parameters of `GET mods/search`. All filters are optional and combined with AND.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModSearch {
+    /// empty to only filter
+    pub query: String,
+    /// `basic` if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<SearchMode>,
+    /// `relevance` if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SearchSort>,
+    /// `Undertale` or `Deltarune`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    /// `major.minor`; mods made for this version of the game
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+    /// mods that have all of these tags (aliases count as their canonical tag); comma separated in the query string
+    #[serde(default, skip_serializing_if = "Vec::is_empty", serialize_with = "join_tags", deserialize_with = "split_tags")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// mods whose file or description changed at or after this time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    /// ISO 639-1 code of the language the terms are written in, for stemming them; `en` if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl ModSearch {
+    pub fn new(query: impl Into<String>) -> Self {
+        ModSearch { query: query.into(), ..ModSearch::default() }
+    }
+
+    pub fn mode(mut self, mode: SearchMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn sort(mut self, sort: SearchSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn game(mut self, game: impl Into<String>) -> Self {
+        self.game = Some(game.into());
+        self
+    }
+
+    pub fn game_version(mut self, game_version: impl Into<String>) -> Self {
+        self.game_version = Some(game_version.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn updated_since(mut self, updated_since: DateTime<Utc>) -> Self {
+        self.updated_since = Some(updated_since);
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+fn join_tags<S: Serializer>(tags: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&tags.join(","))
+}
+
+fn split_tags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let tags = String::deserialize(deserializer)?;
+    Ok(tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect())
+}
+
+/// What the uploader sees of their own mods, including why a mod was rejected or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OwnModStatus {
+    pub id: Uuid,
+    pub title: String,
+    pub mod_version: i32,
+    /// `pending_review`, `approved`, `rejected` or `removed` (taken down by the server operators)
+    pub review_state: String,
+    pub review_reason: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OwnModList {
+    pub mods: Vec<OwnModStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OwnModsRequest {
+    pub username: String,
+    pub access_token: String,
+}
+
+/// Body of `PUT mod/{mod_id}/rating`; rating again replaces the earlier rating.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RateModRequest {
+    pub username: String,
+    pub access_token: String,
+    /// 1 to 5 stars
+    pub rating: i16,
+}
+
+/// The ratings of a mod after rating it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ModRatings {
+    pub rating_average: Option<f64>,
+    pub rating_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ModUploaded {
+    pub mod_id: Uuid,
+    /// `approved` if the mod is public right away, otherwise `pending_review`
+    pub review_state: String,
+}
+
+/// The raw file of a mod.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(value_type = String, format = Binary))]
+pub struct ModFile(pub Vec<u8>);
+
+
+/// Fields of the `multipart/form-data` body of `PUT mod`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadModForm {
+    pub username: String,
+    pub access_token: String,
+    #[cfg_attr(feature = "openapi", schema(format = Binary, value_type = String))]
+    pub file_data: Vec<u8>,
+    /// 8-256 characters (grapheme clusters), one line; a word may not mix scripts, like Latin and Cyrillic letters
+    pub title: String,
+    /// Markdown, at most 10000 characters; raw HTML is limited to formatting and links, images are not shown
+    pub description: String,
+    /// `Undertale` or `Deltarune`
+    pub game_name: String,
+    /// `major.minor`
+    #[cfg_attr(feature = "openapi", schema(example = "1.6"))]
+    pub game_version: String,
+    /// ISO 639-1 code of the title and description; `en` if not given
+    #[cfg_attr(feature = "openapi", schema(example = "es"))]
+    pub language: Option<String>,
+    /// comma separated, at most 10; lowercase letters, digits and `-`, 2-32 chars each
+    #[cfg_attr(feature = "openapi", schema(example = "boss-fight,hard-mode"))]
+    pub tags: Option<String>,
+    /// comma separated `gameplay`, `cosmetic`, `translation`, `qol` and/or `content`
+    #[cfg_attr(feature = "openapi", schema(example = "gameplay,content"))]
+    pub categories: Option<String>,
+}
+
+/// Fields of the `multipart/form-data` body of `PATCH mod`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateModForm {
+    pub username: String,
+    pub access_token: String,
+    pub mod_id: Uuid,
+    /// at least one of `file_data`, `description`, `language`, `tags` and `categories` has to be given
+    #[cfg_attr(feature = "openapi", schema(format = Binary, value_type = Option<String>))]
+    pub file_data: Option<Vec<u8>>,
+    /// Markdown, at most 10000 characters
+    pub description: Option<String>,
+    /// ISO 639-1 code of the title and description
+    pub language: Option<String>,
+    /// comma separated; replaces all tags, empty to remove them
+    pub tags: Option<String>,
+    /// comma separated; replaces all categories, empty to remove them
+    pub categories: Option<String>,
+}
+
+/// Fields of the `multipart/form-data` body of `DELETE mod`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteModForm {
+    pub username: String,
+    pub access_token: String,
+    pub mod_id: Uuid,
+}
@@ -0,0 +1,43 @@
+//! Pre-publication review; moderators only.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PendingMod {
+    pub id: Uuid,
+    pub author: String,
+    pub title: String,
+    pub description: String,
+    /// see [`crate::mods::ModSummary::description_html`]
+    pub description_html: String,
+    pub game_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PendingModList {
+    pub mods: Vec<PendingMod>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModeratorRequest {
+    pub username: String,
+    pub access_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReviewDecisionRequest {
+    pub username: String,
+    pub access_token: String,
+    pub mod_id: String,
+    /// required for rejections, at most 1024 chars
+    pub reason: Option<String>,
+}
@@ -0,0 +1,72 @@
+//! The screenshot gallery of mods.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+
+/// A screenshot; the image is at `screenshots/{id}` and a thumbnail of it at `screenshots/{id}/thumbnail`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenshotInfo {
+    pub id: Uuid,
+    /// zero based order in the gallery
+    pub position: i32,
+    /// `image/png` or `image/jpeg`, for the image and the thumbnail
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    /// shown in listings; the one picked by the author, otherwise the first
+    pub cover: bool,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScreenshotList {
+    /// in gallery order
+    pub screenshots: Vec<ScreenshotInfo>,
+}
+
+/// Body of `PATCH mod/{mod_id}/screenshots`; at least one of `order` and `cover` has to be given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ArrangeScreenshotsRequest {
+    pub username: String,
+    pub access_token: String,
+    /// every screenshot of the mod exactly once, in the new order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<Vec<Uuid>>,
+    /// the screenshot shown in listings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover: Option<Uuid>,
+}
+
+/// Body of `DELETE mod/{mod_id}/screenshots/{screenshot_id}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteScreenshotRequest {
+    pub username: String,
+    pub access_token: String,
+}
+
+/// A PNG or JPEG image.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "openapi", schema(value_type = String, format = Binary))]
+pub struct ScreenshotImage(pub Vec<u8>);
+
+
+/// Fields of the `multipart/form-data` body of `PUT mod/{mod_id}/screenshots`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadScreenshotForm {
+    pub username: String,
+    pub access_token: String,
+    /// PNG, JPEG or WebP; metadata like EXIF is removed, WebP is stored as PNG
+    #[cfg_attr(feature = "openapi", schema(format = Binary, value_type = String))]
+    pub image: Vec<u8>,
+    /// `true` to make it the cover of the mod
+    pub cover: Option<bool>,
+}
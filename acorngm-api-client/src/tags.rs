@@ -0,0 +1,90 @@
+//! Categories and tags of mods, and browsing by them.
+
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+
+/// The fixed set of categories a mod can be sorted into; a mod can be in several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ModCategory {
+    /// changes how the game plays
+    Gameplay,
+    /// sprites, music and other looks without gameplay changes
+    Cosmetic,
+    Translation,
+    /// quality of life
+    Qol,
+    /// new areas, fights, characters or story
+    Content,
+}
+
+impl ModCategory {
+    pub const ALL: [ModCategory; 5] = [
+        ModCategory::Gameplay,
+        ModCategory::Cosmetic,
+        ModCategory::Translation,
+        ModCategory::Qol,
+        ModCategory::Content,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModCategory::Gameplay => "gameplay",
+            ModCategory::Cosmetic => "cosmetic",
+            ModCategory::Translation => "translation",
+            ModCategory::Qol => "qol",
+            ModCategory::Content => "content",
+        }
+    }
+}
+
+impl FromStr for ModCategory {
+    type Err = String;
+
+    fn from_str(category: &str) -> Result<Self, Self::Err> {
+        match category {
+            "gameplay" => Ok(ModCategory::Gameplay),
+            "cosmetic" => Ok(ModCategory::Cosmetic),
+            "translation" => Ok(ModCategory::Translation),
+            "qol" => Ok(ModCategory::Qol),
+            "content" => Ok(ModCategory::Content),
+            _ => Err(format!("Unknown category \"{category}\"; use `gameplay`, `cosmetic`, `translation`, `qol` or `content`")),
+        }
+    }
+}
+
+/// A tag in use, or one made canonical by a moderator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TagInfo {
+    pub tag: String,
+    /// picked by a moderator; its aliases are replaced by it when mods are tagged
+    pub canonical: bool,
+    /// other spellings that mean this tag; only canonical tags have aliases
+    pub aliases: Vec<String>,
+    /// approved mods with this tag
+    pub mod_count: i64,
+}
+
+/// Response of `GET tags`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TagList {
+    /// canonical tags and the 200 most used others, most used first
+    pub tags: Vec<TagInfo>,
+}
+
+/// Body of `PUT tags/{tag}`; moderators only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CanonicalTagRequest {
+    pub username: String,
+    pub access_token: String,
+    /// replaces the earlier aliases; mods tagged with one of them are retagged with the canonical tag
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
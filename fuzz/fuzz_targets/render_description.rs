@@ -0,0 +1,20 @@
+//! Whatever authors write, the rendered description only has allowlisted tags and attributes, and links
+//! only go to http(s) and mail addresses. A browser can not build anything else from it either: when it
+//! parses misnested tags it only repeats formatting tags (with their attributes) that are already there.
+//! That is also why sanitizing the output again is not checked to change nothing; each parse rebuilds
+//! misnested `<a>` tags a little differently.
+//! `tests/markdown.rs` runs the same checks on generated input in every test run.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use acorngm_backend::markdown::{find_unsafe_html, render_description, sanitize_html};
+
+
+fuzz_target!(|input: &str| {
+    for html in [render_description(input), sanitize_html(input)] {
+        if let Some(problem) = find_unsafe_html(&html) {
+            panic!("{problem} in {html:?}");
+        }
+    }
+});
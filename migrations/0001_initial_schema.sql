@@ -0,0 +1,38 @@
+-- Tables as they existed before migrations were introduced.
+-- `IF NOT EXISTS` so that databases which were set up by hand can be baselined.
+
+CREATE TABLE IF NOT EXISTS accounts (
+    username        TEXT PRIMARY KEY,
+    discord_user_id TEXT NOT NULL UNIQUE,
+    created_at      TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS access_tokens (
+    token      TEXT PRIMARY KEY,
+    username   TEXT NOT NULL REFERENCES accounts (username) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+-- not referencing accounts: the token is stored before the client knows whether the account exists
+CREATE TABLE IF NOT EXISTS temp_login_tokens (
+    token      TEXT PRIMARY KEY,
+    username   TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS mods (
+    id                 UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    author             TEXT NOT NULL REFERENCES accounts (username) ON DELETE CASCADE,
+    file_data          BYTEA NOT NULL,
+    title              TEXT NOT NULL,
+    description        TEXT NOT NULL,
+    game_name          TEXT NOT NULL,
+    game_version_major INTEGER NOT NULL,
+    game_version_minor INTEGER NOT NULL,
+    mod_version        INTEGER NOT NULL DEFAULT 1,
+    created_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS access_tokens_username_idx ON access_tokens (username);
+CREATE INDEX IF NOT EXISTS temp_login_tokens_expires_at_idx ON temp_login_tokens (expires_at);
+CREATE INDEX IF NOT EXISTS mods_author_idx ON mods (author);
@@ -0,0 +1,15 @@
+-- Pre-publication review of mods by moderators.
+
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'user'
+        CHECK (role IN ('user', 'moderator', 'admin'));
+
+-- existing mods were already public, so they count as approved
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS review_state TEXT NOT NULL DEFAULT 'approved'
+        CHECK (review_state IN ('pending_review', 'approved', 'rejected')),
+    ADD COLUMN IF NOT EXISTS review_reason TEXT,
+    ADD COLUMN IF NOT EXISTS reviewed_by TEXT REFERENCES accounts (username) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS reviewed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS mods_pending_review_idx ON mods (created_at) WHERE review_state = 'pending_review';
@@ -0,0 +1,7 @@
+-- Token buckets shared between instances when `rate_limit.backend = "postgres"`.
+
+CREATE TABLE IF NOT EXISTS rate_limit_buckets (
+    key        TEXT PRIMARY KEY,
+    tokens     DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
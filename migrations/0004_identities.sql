@@ -0,0 +1,22 @@
+-- Accounts can be linked to any number of identities at external login providers
+-- (discord, github, oidc providers) instead of exactly one discord account.
+
+CREATE TABLE IF NOT EXISTS identities (
+    provider TEXT NOT NULL,
+    -- the provider's stable user id (discord user id, github user id, oidc `sub`)
+    subject TEXT NOT NULL,
+    username TEXT NOT NULL REFERENCES accounts (username) ON DELETE CASCADE,
+    -- name of the user at the provider when the identity was linked; only for display
+    provider_username TEXT,
+    linked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS identities_username_idx ON identities (username);
+
+INSERT INTO identities (provider, subject, username, linked_at)
+SELECT 'discord', discord_user_id, username, created_at
+FROM accounts
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE accounts DROP COLUMN IF EXISTS discord_user_id;
@@ -0,0 +1,21 @@
+-- Display name and avatar of accounts are refreshed from the login provider on every login.
+
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS display_name TEXT,
+    ADD COLUMN IF NOT EXISTS avatar_url TEXT;
+
+ALTER TABLE identities
+    ADD COLUMN IF NOT EXISTS refresh_token TEXT,
+    ADD COLUMN IF NOT EXISTS last_login_at TIMESTAMPTZ;
+
+-- Moving an account from one identity to another: started by the account, confirmed by the new identity.
+CREATE TABLE IF NOT EXISTS identity_moves (
+    code TEXT PRIMARY KEY,
+    username TEXT NOT NULL REFERENCES accounts (username) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    from_subject TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY (provider, from_subject) REFERENCES identities (provider, subject) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS identity_moves_expires_at_idx ON identity_moves (expires_at);
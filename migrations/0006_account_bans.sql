@@ -0,0 +1,11 @@
+-- Operators can ban accounts and take down mods with the `acorngm-admin` tool.
+
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS banned_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS ban_reason TEXT;
+
+-- removed mods are hidden like rejected ones, but can not be resubmitted by their author; an operator can restore them
+ALTER TABLE mods
+    DROP CONSTRAINT IF EXISTS mods_review_state_check,
+    ADD CONSTRAINT mods_review_state_check
+        CHECK (review_state IN ('pending_review', 'approved', 'rejected', 'removed'));
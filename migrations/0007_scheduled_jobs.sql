@@ -0,0 +1,20 @@
+-- Housekeeping jobs run inside the server; with several instances, this table decides whose turn it is.
+
+CREATE TABLE IF NOT EXISTS scheduled_jobs (
+    name TEXT PRIMARY KEY,
+    -- an instance that crashed mid-job gives up the lock when this passes
+    locked_until TIMESTAMPTZ NOT NULL,
+    last_started_at TIMESTAMPTZ NOT NULL,
+    last_finished_at TIMESTAMPTZ,
+    -- NULL if the last run succeeded
+    last_error TEXT
+);
+
+-- Downloads are recorded cheaply here and added to `mods.download_count` by a job.
+CREATE TABLE IF NOT EXISTS mod_download_events (
+    mod_id UUID NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    downloaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS download_count BIGINT NOT NULL DEFAULT 0;
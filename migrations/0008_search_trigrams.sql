@@ -0,0 +1,7 @@
+-- Trigram indexes for the `fuzzy` search mode (typos, partial words).
+-- pg_trgm is a trusted extension, so the owner of the database can create it.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS mods_title_trgm_idx ON mods USING GIN (title gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS mods_description_trgm_idx ON mods USING GIN (description gin_trgm_ops);
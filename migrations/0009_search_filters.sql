@@ -0,0 +1,20 @@
+-- Filters and facets of mod search.
+
+-- when the file or description of a mod last changed
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ;
+UPDATE mods SET updated_at = created_at WHERE updated_at IS NULL;
+ALTER TABLE mods
+    ALTER COLUMN updated_at SET NOT NULL,
+    ALTER COLUMN updated_at SET DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS mods_updated_at_idx ON mods (updated_at);
+CREATE INDEX IF NOT EXISTS mods_game_idx ON mods (game_name, game_version_minor, game_version_major);
+
+CREATE TABLE IF NOT EXISTS mod_tags (
+    mod_id UUID NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (mod_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS mod_tags_tag_idx ON mod_tags (tag);
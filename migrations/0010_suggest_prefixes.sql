@@ -0,0 +1,7 @@
+-- Prefix indexes for `GET mods/suggest`. The "C" collation compares bytes, so a prefix is a range
+-- (`>= prefix AND < prefix || U+10FFFF`) that a btree can scan in order, even in a generic prepared plan.
+
+CREATE INDEX IF NOT EXISTS mods_title_prefix_idx ON mods ((lower(title) COLLATE "C")) WHERE review_state = 'approved';
+CREATE INDEX IF NOT EXISTS accounts_username_prefix_idx ON accounts ((lower(username) COLLATE "C"));
+-- counts the approved mods of an author without visiting the rows
+CREATE INDEX IF NOT EXISTS mods_approved_author_idx ON mods (author) WHERE review_state = 'approved';
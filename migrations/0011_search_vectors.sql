@@ -0,0 +1,9 @@
+-- Stored, weighted search vectors, so full text search uses an index instead of running
+-- `to_tsvector` over every row. Titles weigh more than descriptions (A > B) when ranking.
+
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', description), 'B')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS mods_search_vector_idx ON mods USING GIN (search_vector);
@@ -0,0 +1,54 @@
+-- The language of a mod's title and description (ISO 639-1), and search vectors stemmed for it.
+-- Languages PostgreSQL has no stemmer for (Japanese, Chinese, Korean, ...) use the `simple` configuration,
+-- which only lowercases words. Every vector also gets the unstemmed words (with the lowest weight),
+-- so a search in any language finds words exactly as they are written.
+
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS language TEXT NOT NULL DEFAULT 'en' CHECK (language ~ '^[a-z]{2}$');
+
+CREATE OR REPLACE FUNCTION mod_search_config(language TEXT) RETURNS REGCONFIG
+LANGUAGE SQL IMMUTABLE PARALLEL SAFE AS $$
+    SELECT (CASE language
+        WHEN 'ar' THEN 'arabic'
+        WHEN 'hy' THEN 'armenian'
+        WHEN 'eu' THEN 'basque'
+        WHEN 'ca' THEN 'catalan'
+        WHEN 'da' THEN 'danish'
+        WHEN 'nl' THEN 'dutch'
+        WHEN 'en' THEN 'english'
+        WHEN 'fi' THEN 'finnish'
+        WHEN 'fr' THEN 'french'
+        WHEN 'de' THEN 'german'
+        WHEN 'el' THEN 'greek'
+        WHEN 'hi' THEN 'hindi'
+        WHEN 'hu' THEN 'hungarian'
+        WHEN 'id' THEN 'indonesian'
+        WHEN 'ga' THEN 'irish'
+        WHEN 'it' THEN 'italian'
+        WHEN 'lt' THEN 'lithuanian'
+        WHEN 'ne' THEN 'nepali'
+        WHEN 'no' THEN 'norwegian'
+        WHEN 'pt' THEN 'portuguese'
+        WHEN 'ro' THEN 'romanian'
+        WHEN 'ru' THEN 'russian'
+        WHEN 'sr' THEN 'serbian'
+        WHEN 'es' THEN 'spanish'
+        WHEN 'sv' THEN 'swedish'
+        WHEN 'ta' THEN 'tamil'
+        WHEN 'tr' THEN 'turkish'
+        WHEN 'yi' THEN 'yiddish'
+        ELSE 'simple'
+    END)::REGCONFIG
+$$;
+
+-- a generated column's expression can not be changed in place
+DROP INDEX IF EXISTS mods_search_vector_idx;
+ALTER TABLE mods DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE mods
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector(mod_search_config(language), title), 'A')
+            || setweight(to_tsvector(mod_search_config(language), description), 'B')
+            || setweight(to_tsvector('simple', title || ' ' || description), 'D')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS mods_search_vector_idx ON mods USING GIN (search_vector);
@@ -0,0 +1,18 @@
+-- Ratings of mods, 1 to 5 stars, one per account and mod. Count and sum are kept on `mods`
+-- (updated together with the rating) so ranking and listings do not have to aggregate.
+
+CREATE TABLE IF NOT EXISTS mod_ratings (
+    mod_id   UUID NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    username TEXT NOT NULL REFERENCES accounts (username) ON DELETE CASCADE,
+    rating   SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    rated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (mod_id, username)
+);
+
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS rating_count BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS rating_sum BIGINT NOT NULL DEFAULT 0;
+
+-- the `downloads` and `newest` sort orders of search
+CREATE INDEX IF NOT EXISTS mods_approved_download_count_idx ON mods (download_count DESC) WHERE review_state = 'approved';
+CREATE INDEX IF NOT EXISTS mods_approved_created_at_idx ON mods (created_at DESC) WHERE review_state = 'approved';
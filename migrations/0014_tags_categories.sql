@@ -0,0 +1,25 @@
+-- Curated categories of mods, and canonical tags with aliases picked by moderators.
+-- Tags themselves are free-form and live in `mod_tags` (see `0009_search_filters.sql`).
+
+CREATE TABLE IF NOT EXISTS mod_categories (
+    mod_id   UUID NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    category TEXT NOT NULL CHECK (category IN ('gameplay', 'cosmetic', 'translation', 'qol', 'content')),
+    PRIMARY KEY (mod_id, category)
+);
+
+CREATE INDEX IF NOT EXISTS mod_categories_category_idx ON mod_categories (category);
+
+CREATE TABLE IF NOT EXISTS canonical_tags (
+    tag        TEXT PRIMARY KEY,
+    created_by TEXT REFERENCES accounts (username) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- other spellings of a canonical tag; mods are tagged with the canonical tag instead
+CREATE TABLE IF NOT EXISTS tag_aliases (
+    alias TEXT PRIMARY KEY,
+    tag   TEXT NOT NULL REFERENCES canonical_tags (tag) ON DELETE CASCADE,
+    CHECK (alias <> tag)
+);
+
+CREATE INDEX IF NOT EXISTS tag_aliases_tag_idx ON tag_aliases (tag);
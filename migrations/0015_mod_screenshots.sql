@@ -0,0 +1,21 @@
+-- Screenshots of mods. Uploaded images are decoded and encoded again on the server, which drops EXIF and
+-- other metadata, and a thumbnail is made at the same time. Both are stored like mod files.
+
+CREATE TABLE IF NOT EXISTS mod_screenshots (
+    id             UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mod_id         UUID NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    -- zero based order in the gallery
+    position       INT NOT NULL,
+    content_type   TEXT NOT NULL CHECK (content_type IN ('image/png', 'image/jpeg')),
+    image_data     BYTEA NOT NULL,
+    thumbnail_data BYTEA NOT NULL,
+    width          INT NOT NULL,
+    height         INT NOT NULL,
+    uploaded_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- deferred so the gallery can be reordered with one update
+    UNIQUE (mod_id, position) DEFERRABLE INITIALLY DEFERRED
+);
+
+-- the first screenshot is the cover unless the author picked another one
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS cover_screenshot_id UUID REFERENCES mod_screenshots (id) ON DELETE SET NULL;
@@ -0,0 +1,6 @@
+-- Descriptions are Markdown; the sanitized HTML rendering of them is stored next to them.
+-- The server renders the descriptions of existing mods right after running the migrations,
+-- since the renderer lives in the server and not in the database.
+
+ALTER TABLE mods
+    ADD COLUMN IF NOT EXISTS description_html TEXT;
@@ -0,0 +1,8 @@
+-- What a username looks like (its confusable skeleton, see `sanitize.rs`), so a new account
+-- can not pass for an existing one by swapping letters that look alike.
+-- The server fills it in for existing accounts right after running the migrations.
+
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS username_skeleton TEXT;
+
+CREATE INDEX IF NOT EXISTS accounts_username_skeleton_idx ON accounts (username_skeleton);
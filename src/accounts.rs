@@ -1,17 +1,30 @@
+use std::str::FromStr;
+use std::sync::LazyLock;
 use chrono::{DateTime, Duration, Utc};
-use rocket::http::Status;
+use regex::Regex;
 use sqlx::error::DatabaseError;
 use sqlx::postgres::{PgDatabaseError, PgQueryResult};
-use crate::{pool, respond_err, respond_ok_empty, ApiResponse};
+use crate::pool;
+use crate::api_error::ApiError;
+use crate::identity::ExternalIdentity;
+use crate::sanitize::{confusable_skeleton, sanitize_string};
+use acorngm_api_client::auth::{AccountProfile, LinkedIdentity};
 
 
 #[derive(Debug, Clone)]
 pub struct AcornAccount {
     pub username: String,
-    pub discord_user_id: String,
     pub created_at: DateTime<Utc>,
 }
 
+/// A started move of an account from one of its identities to another identity of the same provider.
+#[derive(Debug, Clone)]
+pub struct IdentityMove {
+    pub username: String,
+    pub provider: String,
+    pub from_subject: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct AcornAccessToken {
     pub token: String,
@@ -19,8 +32,69 @@ pub struct AcornAccessToken {
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone)]
+pub struct TempLoginToken {
+    pub token: String,
+    pub username: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// How many credentials [`revoke_credentials`] threw away.
+#[derive(Debug, Clone, Copy)]
+pub struct RevokedCredentials {
+    pub access_tokens: u64,
+    pub refresh_tokens: u64,
+    pub temp_login_tokens: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountRole {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl AccountRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountRole::User => "user",
+            AccountRole::Moderator => "moderator",
+            AccountRole::Admin => "admin",
+        }
+    }
+
+    /// admins can do everything moderators can
+    pub fn is_moderator(&self) -> bool {
+        matches!(self, AccountRole::Moderator | AccountRole::Admin)
+    }
+}
+
+impl FromStr for AccountRole {
+    type Err = String;
 
-pub async fn check_if_account_exists(username: &str) -> Result<bool, String> {
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "user" => Ok(AccountRole::User),
+            "moderator" => Ok(AccountRole::Moderator),
+            "admin" => Ok(AccountRole::Admin),
+            other => Err(format!("Unknown account role \"{other}\"")),
+        }
+    }
+}
+
+
+pub fn validate_username(username: &str) -> Result<(), ApiError> {
+    static USERNAME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9_-]{3,32}$")
+        .expect("Could not load username verification pattern"));
+
+    if !USERNAME_REGEX.is_match(username) {
+        return Err(ApiError::InvalidUsername)
+    }
+    Ok(())
+}
+
+
+pub async fn check_if_account_exists(username: &str) -> Result<bool, ApiError> {
     let result: Option<bool> = sqlx::query_scalar!(
         r#"
         SELECT EXISTS (
@@ -33,60 +107,144 @@ pub async fn check_if_account_exists(username: &str) -> Result<bool, String> {
     )
         .fetch_one(pool())
         .await
-        .map_err(|e| format!("Failed to check if account with username {username} exists: {e}"))?;
+        .map_err(|e| ApiError::Internal(format!("Failed to check if account with username {username} exists: {e}")))?;
 
     Ok(result.unwrap_or(false))
 }
 
-pub async fn check_if_account_exists_discord(username: &str, discord_user_id: &str) -> Result<bool, String> {
-    let result: Option<bool> = sqlx::query_scalar!(
+/// Another account whose username looks like this one, like `AIice` for `alice`.
+pub async fn find_confusable_account(username: &str) -> Result<Option<String>, ApiError> {
+    sqlx::query_scalar!(
         r#"
-        SELECT EXISTS (
-            SELECT 1
-            FROM accounts
-            WHERE username = $1 OR discord_user_id = $2
-        )
+        SELECT username
+        FROM accounts
+        WHERE username_skeleton = $1 AND username <> $2
+        LIMIT 1
         "#,
+        confusable_skeleton(username),
         username,
-        discord_user_id,
     )
-        .fetch_one(pool())
+        .fetch_optional(pool())
         .await
-        .map_err(|e| format!("Failed to check if account with username {username} exists: {e}"))?;
-
-    Ok(result.unwrap_or(false))
+        .map_err(|e| ApiError::Internal(format!("Could not look for accounts confusable with username {username}: {e}")))
 }
 
+/// Fills in the skeletons of accounts created before they were stored, see `0017_username_skeletons.sql`.
+pub async fn compute_missing_username_skeletons() -> Result<u64, ApiError> {
+    let usernames: Vec<String> = sqlx::query_scalar!(
+        r#"
+        SELECT username
+        FROM accounts
+        WHERE username_skeleton IS NULL
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch accounts without username skeleton: {e}")))?;
+    if usernames.is_empty() {
+        return Ok(0)
+    }
 
-pub async fn ensure_account_authentication(username: &str, access_token: &str) -> ApiResponse {
-    let result: Option<bool> = sqlx::query_scalar!(
+    let skeletons: Vec<String> = usernames.iter().map(|username| confusable_skeleton(username)).collect();
+    let result = sqlx::query!(
         r#"
-        SELECT EXISTS (
-            SELECT 1
-            FROM access_tokens
-            WHERE username = $1 AND token = $2
-        )
+        UPDATE accounts
+        SET username_skeleton = computed.skeleton
+        FROM UNNEST($1::TEXT[], $2::TEXT[]) AS computed(username, skeleton)
+        WHERE accounts.username = computed.username
+        "#,
+        &usernames,
+        &skeletons,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not save username skeletons: {e}")))?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn ensure_account_authentication(username: &str, access_token: &str) -> Result<(), ApiError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT accounts.banned_at, accounts.ban_reason
+        FROM access_tokens
+        JOIN accounts ON accounts.username = access_tokens.username
+        WHERE access_tokens.username = $1 AND access_tokens.token = $2
         "#,
         username,
         access_token,
     )
-        .fetch_one(pool())
+        .fetch_optional(pool())
         .await
-        .map_err(|e| respond_err(Status::InternalServerError, &format!("Failed to verify authentication of {username}: {e}")))?;
+        .map_err(|e| ApiError::Internal(format!("Failed to verify authentication of {username}: {e}")))?;
 
-    let authenticated: bool = result.unwrap_or(false);  // if account doesn't exist; authentication failed
-    if !authenticated {
-        return Err(respond_err(Status::Unauthorized, "Not authenticated; invalid username or access token"))
+    // if account doesn't exist; authentication failed
+    let row = row.ok_or(ApiError::NotAuthenticated)?;
+    if row.banned_at.is_some() {
+        return Err(ApiError::AccountBanned(row.ban_reason))
     }
-    
-    respond_ok_empty()   // will NOT actually be used as a response to the http request
+    Ok(())
 }
 
-pub async fn get_account(username: &str) -> Result<AcornAccount, String> {
-    let account: AcornAccount = sqlx::query_as!(
+/// Banned accounts can not log in either, so they do not collect new access tokens.
+pub async fn ensure_account_not_banned(username: &str) -> Result<(), ApiError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT banned_at, ban_reason
+        FROM accounts
+        WHERE username = $1
+        "#,
+        username,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not check whether account with username {username} is banned: {e}")))?
+        .ok_or(ApiError::AccountNotFound)?;
+
+    if row.banned_at.is_some() {
+        return Err(ApiError::AccountBanned(row.ban_reason))
+    }
+    Ok(())
+}
+
+pub async fn get_account(username: &str) -> Result<AcornAccount, ApiError> {
+    let account: Option<AcornAccount> = sqlx::query_as!(
         AcornAccount,
         r#"
-        SELECT username, discord_user_id, created_at
+        SELECT username, created_at
+        FROM accounts
+        WHERE username = $1
+        "#,
+        username,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch account with username {username}: {e}")))?;
+    account.ok_or(ApiError::AccountNotFound)
+}
+
+
+pub async fn get_account_profile(username: &str) -> Result<AccountProfile, ApiError> {
+    let profile: Option<AccountProfile> = sqlx::query_as!(
+        AccountProfile,
+        r#"
+        SELECT username, display_name, avatar_url, created_at
+        FROM accounts
+        WHERE username = $1
+        "#,
+        username,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch profile of account with username {username}: {e}")))?;
+    profile.ok_or(ApiError::AccountNotFound)
+}
+
+
+pub async fn get_account_role(username: &str) -> Result<AccountRole, ApiError> {
+    let role: String = sqlx::query_scalar!(
+        r#"
+        SELECT role
         FROM accounts
         WHERE username = $1
         "#,
@@ -94,69 +252,335 @@ pub async fn get_account(username: &str) -> Result<AcornAccount, String> {
     )
         .fetch_one(pool())
         .await
-        .map_err(|e| format!("Could not fetch account with username {username}: {e}"))?;
-    Ok(account)
+        .map_err(|e| ApiError::Internal(format!("Could not fetch role of account with username {username}: {e}")))?;
+    AccountRole::from_str(&role).map_err(ApiError::Internal)
 }
 
 
-pub async fn get_account_by_discord_id(discord_user_id: &str) -> Result<Option<AcornAccount>, String> {
+pub async fn set_account_role(username: &str, role: AccountRole) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET role = $2
+        WHERE username = $1
+        "#,
+        username,
+        role.as_str(),
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not set role of account with username {username}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::AccountNotFound)
+    }
+    Ok(())
+}
+
+
+/// Access tokens are kept, so an account does not have to log in again once the ban is lifted.
+pub async fn ban_account(username: &str, reason: Option<&str>) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET banned_at = NOW(), ban_reason = $2
+        WHERE username = $1
+        "#,
+        username,
+        reason,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not ban account with username {username}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::AccountNotFound)
+    }
+    Ok(())
+}
+
+
+pub async fn unban_account(username: &str) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET banned_at = NULL, ban_reason = NULL
+        WHERE username = $1
+        "#,
+        username,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not unban account with username {username}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::AccountNotFound)
+    }
+    Ok(())
+}
+
+
+pub async fn get_account_by_identity(provider: &str, subject: &str) -> Result<Option<AcornAccount>, ApiError> {
     let account: Option<AcornAccount> = sqlx::query_as!(
         AcornAccount,
         r#"
-        SELECT username, discord_user_id, created_at
-        FROM accounts
-        WHERE discord_user_id = $1
+        SELECT accounts.username, accounts.created_at
+        FROM identities
+        JOIN accounts ON accounts.username = identities.username
+        WHERE identities.provider = $1 AND identities.subject = $2
         "#,
-        discord_user_id,
+        provider,
+        subject,
     )
         .fetch_optional(pool())
         .await
-        .map_err(|e| format!("Could not fetch account with discord user id {discord_user_id}: {e}"))?;
+        .map_err(|e| ApiError::Internal(format!("Could not fetch account with {provider} identity {subject}: {e}")))?;
     Ok(account)
 }
 
 
-pub async fn get_access_token(username: &str, token: &str) -> Result<AcornAccessToken, String> {
-    let row = sqlx::query!(
+pub async fn get_identities(username: &str) -> Result<Vec<LinkedIdentity>, ApiError> {
+    sqlx::query_as!(
+        LinkedIdentity,
         r#"
-        SELECT token, username, created_at
-        FROM access_tokens
-        WHERE username = $1 AND token = $2
+        SELECT provider, subject AS provider_user_id, provider_username, linked_at
+        FROM identities
+        WHERE username = $1
+        ORDER BY linked_at ASC
         "#,
         username,
-        token,
     )
-        .fetch_one(pool())
+        .fetch_all(pool())
         .await
-        .map_err(|e| format!("Could not fetch access token with username {username}: {e}"))?;
-    
-    let access_token = AcornAccessToken {
-        token: row.token,
-        username: row.username,
-        created_at: row.created_at,
-    };
-    Ok(access_token)
+        .map_err(|e| ApiError::Internal(format!("Could not fetch identities of account with username {username}: {e}")))
 }
 
 
-pub async fn insert_account(account: &AcornAccount) -> Result<(), String> {
+/// Providers let people put anything into their display name, and it is shown like any other user text;
+/// `None` if nothing visible is left of it.
+fn sanitized_display_name(identity: &ExternalIdentity) -> Option<String> {
+    identity.display_name.as_deref().and_then(sanitize_string)
+}
+
+/// Creates the account together with the identity used to register it, so there are no accounts nobody can log into.
+pub async fn insert_account(account: &AcornAccount, provider: &str, identity: &ExternalIdentity, refresh_token: Option<&str>) -> Result<(), ApiError> {
+    let internal_error = |e: sqlx::Error| ApiError::Internal(format!("Could not insert account row for account with username {}: {e}", account.username));
+    let mut transaction = pool().begin().await.map_err(internal_error)?;
+
     sqlx::query!(
         r#"
-        INSERT INTO accounts (username, discord_user_id, created_at)
-        VALUES ($1, $2, $3)
+        INSERT INTO accounts (username, created_at, display_name, avatar_url, username_skeleton)
+        VALUES ($1, $2, $3, $4, $5)
         "#,
         account.username,
-        account.discord_user_id,
         account.created_at,
+        sanitized_display_name(identity),
+        identity.avatar_url,
+        confusable_skeleton(&account.username),
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| unique_violation_as(e, ApiError::AccountAlreadyExists).unwrap_or_else(internal_error))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO identities (provider, subject, username, provider_username, linked_at, refresh_token)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        provider,
+        identity.subject,
+        account.username,
+        identity.username,
+        account.created_at,
+        refresh_token,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| unique_violation_as(e, ApiError::IdentityAlreadyLinked).unwrap_or_else(internal_error))?;
+
+    transaction.commit().await.map_err(internal_error)?;
+    Ok(())
+}
+
+
+pub async fn link_identity(username: &str, provider: &str, identity: &ExternalIdentity) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO identities (provider, subject, username, provider_username)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        provider,
+        identity.subject,
+        username,
+        identity.username,
     )
         .execute(pool())
         .await
-        .map_err(|e| format!("Could not insert account row for account with username {}: {e}", account.username))?;
+        .map_err(|e| unique_violation_as(e, ApiError::IdentityAlreadyLinked).unwrap_or_else(
+            |e| ApiError::Internal(format!("Could not link {provider} identity {} to account {username}: {e}", identity.subject))
+        ))?;
     Ok(())
 }
 
 
-pub async fn insert_access_token(access_token: &AcornAccessToken) -> Result<(), String> {
+pub async fn unlink_identity(username: &str, provider: &str, subject: &str) -> Result<(), ApiError> {
+    // the count check is part of the delete so two concurrent unlinks can not remove the last two identities
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM identities
+        WHERE provider = $1 AND subject = $2 AND username = $3
+            AND (SELECT COUNT(*) FROM identities WHERE username = $3) > 1
+        "#,
+        provider,
+        subject,
+        username,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not unlink {provider} identity {subject} from account {username}: {e}")))?;
+
+    if result.rows_affected() > 0 {
+        return Ok(())
+    }
+    let is_linked: bool = get_identities(username).await?.iter()
+        .any(|identity| identity.provider == provider && identity.provider_user_id == subject);
+    Err(if is_linked { ApiError::LastIdentity } else { ApiError::IdentityNotLinked })
+}
+
+/// Updates what the provider currently says about the identity; display name and avatar of the account follow the identity last logged in with.
+/// Providers only hand out a new refresh token sometimes, so a missing one keeps the stored one.
+pub async fn record_identity_login(username: &str, provider: &str, identity: &ExternalIdentity, refresh_token: Option<&str>) -> Result<(), ApiError> {
+    let internal_error = |e: sqlx::Error| ApiError::Internal(format!("Could not record login of account {username} with {provider} identity {}: {e}", identity.subject));
+    let mut transaction = pool().begin().await.map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE identities
+        SET provider_username = $3, refresh_token = COALESCE($4, refresh_token), last_login_at = NOW()
+        WHERE provider = $1 AND subject = $2
+        "#,
+        provider,
+        identity.subject,
+        identity.username,
+        refresh_token,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET display_name = $2, avatar_url = $3
+        WHERE username = $1
+        "#,
+        username,
+        sanitized_display_name(identity),
+        identity.avatar_url,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+    Ok(())
+}
+
+
+/// The account side of an identity move; the new identity has to confirm the move with the code.
+pub async fn insert_identity_move(username: &str, provider: &str, from_subject: &str, code: &str) -> Result<(), ApiError> {
+    let expires_at: DateTime<Utc> = Utc::now() + Duration::minutes(15);
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO identity_moves (code, username, provider, from_subject, expires_at)
+        SELECT $1, username, provider, subject, $5
+        FROM identities
+        WHERE provider = $2 AND subject = $3 AND username = $4
+        "#,
+        code,
+        provider,
+        from_subject,
+        username,
+        expires_at,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not start moving {provider} identity {from_subject} of account {username}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::IdentityNotLinked)
+    }
+    Ok(())
+}
+
+
+/// The identity side of an identity move: replaces the old identity with the new one, if the move code is still valid.
+pub async fn complete_identity_move(code: &str, provider: &str, identity: &ExternalIdentity) -> Result<IdentityMove, ApiError> {
+    let internal_error = |e: sqlx::Error| ApiError::Internal(format!("Could not move account to identity {}: {e}", identity.subject));
+    let mut transaction = pool().begin().await.map_err(internal_error)?;
+
+    let row = sqlx::query!(
+        r#"
+        DELETE FROM identity_moves
+        WHERE code = $1 AND provider = $2
+        RETURNING username, provider, from_subject, expires_at
+        "#,
+        code,
+        provider,
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    let row = match row {
+        Some(row) if row.expires_at > Utc::now() => row,
+        _ => return Err(ApiError::IdentityMoveNotFound),
+    };
+    let identity_move = IdentityMove { username: row.username, provider: row.provider, from_subject: row.from_subject };
+
+    // other moves of the old identity would point at nothing afterwards
+    sqlx::query!(
+        r#"
+        DELETE FROM identity_moves
+        WHERE provider = $1 AND from_subject = $2
+        "#,
+        identity_move.provider,
+        identity_move.from_subject,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE identities
+        SET subject = $3, provider_username = $4, refresh_token = NULL, linked_at = NOW(), last_login_at = NULL
+        WHERE provider = $1 AND subject = $2
+        "#,
+        identity_move.provider,
+        identity_move.from_subject,
+        identity.subject,
+        identity.username,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| unique_violation_as(e, ApiError::IdentityAlreadyLinked).unwrap_or_else(internal_error))?;
+
+    transaction.commit().await.map_err(internal_error)?;
+    Ok(identity_move)
+}
+
+
+/// `Ok(error)` if the insert failed because the row already exists
+fn unique_violation_as(error: sqlx::Error, api_error: ApiError) -> Result<ApiError, sqlx::Error> {
+    match &error {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some("23505") => Ok(api_error),
+        _ => Err(error),
+    }
+}
+
+
+pub async fn insert_access_token(access_token: &AcornAccessToken) -> Result<(), ApiError> {
     sqlx::query!(
         r#"
         INSERT INTO access_tokens (token, username, created_at)
@@ -168,13 +592,12 @@ pub async fn insert_access_token(access_token: &AcornAccessToken) -> Result<(),
     )
         .execute(pool())
         .await
-        .map_err(|e| format!("Could not insert access token row for username {}: {e}", access_token.username))?;
+        .map_err(|e| ApiError::Internal(format!("Could not insert access token row for username {}: {e}", access_token.username)))?;
     Ok(())
 }
 
 
-/// returns whether the temp login token already exists (-> respond 404)
-pub async fn insert_temp_login_token(temp_login_token: &str, username: &str) -> Result<bool, String> {
+pub async fn insert_temp_login_token(temp_login_token: &str, username: &str) -> Result<(), ApiError> {
     let expires_at: DateTime<Utc> = Utc::now() + Duration::minutes(5);
 
     // Insert the account row
@@ -191,34 +614,77 @@ pub async fn insert_temp_login_token(temp_login_token: &str, username: &str) ->
         .await;
 
     let result: sqlx::Error = match result {
-        Ok(_) => return Ok(false),
+        Ok(_) => return Ok(()),
         Err(e) => e,
     };
 
     let error: Box<dyn DatabaseError> = match result {
         sqlx::Error::Database(e) => e,
-        e => return Err(format!("(generic) Could not insert temp login token row for username {username}: {e}")),
+        e => return Err(ApiError::Internal(format!("(generic) Could not insert temp login token row for username {username}: {e}"))),
     };
 
     let error: &PgDatabaseError = error.downcast_ref::<PgDatabaseError>();
     if error.code() == "23505" {    // "unique violation"; temp login token already exists
-        return Ok(true)
+        return Err(ApiError::TempLoginTokenAlreadyExists)
     }
 
-    Err(format!("Could not insert temp login token row for username {username}: {error}"))
+    Err(ApiError::Internal(format!("Could not insert temp login token row for username {username}: {error}")))
 }
 
 
-pub async fn delete_expired_temp_login_tokens() -> Result<(), String> {
-    sqlx::query!("DELETE FROM temp_login_tokens WHERE expires_at < NOW()")
+pub async fn get_temp_login_tokens() -> Result<Vec<TempLoginToken>, ApiError> {
+    sqlx::query_as!(
+        TempLoginToken,
+        r#"
+        SELECT token, username, expires_at
+        FROM temp_login_tokens
+        ORDER BY expires_at ASC
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch temp login tokens: {e}")))
+}
+
+
+/// Returns how many were deleted.
+pub async fn delete_expired_temp_login_tokens() -> Result<u64, ApiError> {
+    let result = sqlx::query!("DELETE FROM temp_login_tokens WHERE expires_at < NOW()")
         .execute(pool())
         .await
-        .map_err(|e| format!("Could not delete expired temp login tokens: {e}"))?;
-    Ok(())
+        .map_err(|e| ApiError::Internal(format!("Could not delete expired temp login tokens: {e}")))?;
+    Ok(result.rows_affected())
 }
 
 
-pub async fn temp_login_token_get_username(temp_login_token: &str) -> Result<Option<String>, String> {
+/// Returns how many were deleted.
+pub async fn delete_expired_identity_moves() -> Result<u64, ApiError> {
+    let result = sqlx::query!("DELETE FROM identity_moves WHERE expires_at < NOW()")
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not delete expired identity moves: {e}")))?;
+    Ok(result.rows_affected())
+}
+
+
+/// Logs out every session that logged in at least `days` ago; returns how many.
+pub async fn delete_access_tokens_older_than(days: i64) -> Result<u64, ApiError> {
+    let created_before: DateTime<Utc> = Utc::now() - Duration::days(days);
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM access_tokens
+        WHERE created_at < $1
+        "#,
+        created_before,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not delete old access tokens: {e}")))?;
+    Ok(result.rows_affected())
+}
+
+
+pub async fn temp_login_token_get_username(temp_login_token: &str) -> Result<Option<String>, ApiError> {
     let result = sqlx::query!(
         r#"
         SELECT username FROM temp_login_tokens
@@ -228,7 +694,7 @@ pub async fn temp_login_token_get_username(temp_login_token: &str) -> Result<Opt
     )
         .fetch_optional(pool())
         .await
-        .map_err(|e| format!("Could not get username for temp login token: {e}"))?;
+        .map_err(|e| ApiError::Internal(format!("Could not get username for temp login token: {e}")))?;
 
     let record = match result {
         None => return Ok(None),
@@ -240,7 +706,7 @@ pub async fn temp_login_token_get_username(temp_login_token: &str) -> Result<Opt
     Ok(Some(record.username))
 }
 
-pub async fn remove_temp_login_token(temp_login_token: &str) -> Result<(), String> {
+pub async fn remove_temp_login_token(temp_login_token: &str) -> Result<(), ApiError> {
     sqlx::query!(
         r#"
         DELETE FROM temp_login_tokens
@@ -250,8 +716,56 @@ pub async fn remove_temp_login_token(temp_login_token: &str) -> Result<(), Strin
     )
         .execute(pool())
         .await
-        .map_err(|e| format!("Could not remove temp login token: {e}"))?;
+        .map_err(|e| ApiError::Internal(format!("Could not remove temp login token: {e}")))?;
 
     Ok(())
 }
 
+
+/// Logs the account (or every account, if `None`) out everywhere and forgets the refresh tokens of its identities.
+/// Meant for when credentials may have leaked; a login with a provider gets everything back.
+pub async fn revoke_credentials(username: Option<&str>) -> Result<RevokedCredentials, ApiError> {
+    let internal_error = |e: sqlx::Error| ApiError::Internal(format!("Could not revoke credentials of {}: {e}", username.unwrap_or("all accounts")));
+    let mut transaction = pool().begin().await.map_err(internal_error)?;
+
+    let access_tokens = sqlx::query!(
+        r#"
+        DELETE FROM access_tokens
+        WHERE $1::TEXT IS NULL OR username = $1
+        "#,
+        username,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    let refresh_tokens = sqlx::query!(
+        r#"
+        UPDATE identities
+        SET refresh_token = NULL
+        WHERE refresh_token IS NOT NULL AND ($1::TEXT IS NULL OR username = $1)
+        "#,
+        username,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    let temp_login_tokens = sqlx::query!(
+        r#"
+        DELETE FROM temp_login_tokens
+        WHERE $1::TEXT IS NULL OR username = $1
+        "#,
+        username,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(internal_error)?;
+
+    transaction.commit().await.map_err(internal_error)?;
+    Ok(RevokedCredentials {
+        access_tokens: access_tokens.rows_affected(),
+        refresh_tokens: refresh_tokens.rows_affected(),
+        temp_login_tokens: temp_login_tokens.rows_affected(),
+    })
+}
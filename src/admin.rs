@@ -0,0 +1,274 @@
+//! The `acorngm-admin` tool for server operators. Uses the same config (and so the same database) as the server.
+
+use std::time::Duration;
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use uuid::Uuid;
+use crate::{migrations, pool};
+use crate::api_error::ApiError;
+use crate::accounts::{
+    ban_account,
+    check_if_account_exists,
+    delete_expired_temp_login_tokens,
+    find_confusable_account,
+    get_temp_login_tokens,
+    insert_account,
+    revoke_credentials,
+    set_account_role,
+    unban_account,
+    validate_username,
+    AccountRole,
+    AcornAccount,
+    RevokedCredentials,
+    TempLoginToken,
+};
+use crate::identity::{get_provider, ExternalIdentity};
+use crate::jobs::{find_job, run_job, Job};
+use crate::mods::{delete_mod, remove_mod, restore_mod};
+
+
+#[derive(Debug, Parser)]
+#[command(name = "acorngm-admin", version, about = "Administrate an AcornGM server")]
+pub struct AdminCli {
+    #[command(subcommand)]
+    pub command: AdminCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AdminCommand {
+    /// Bring the database schema up to date
+    Migrate,
+    /// Create an admin account that logs in with an identity at a login provider
+    CreateAdmin {
+        username: String,
+        /// name of a configured login provider, e.g. `discord`
+        #[arg(long)]
+        provider: String,
+        /// the user id at the provider
+        #[arg(long)]
+        provider_user_id: String,
+        /// only for display; the name of the user at the provider
+        #[arg(long)]
+        provider_username: Option<String>,
+    },
+    /// Promote or demote an account: `user`, `moderator` or `admin`
+    SetRole {
+        username: String,
+        role: AccountRole,
+    },
+    /// Keep an account from logging in and from using its access tokens
+    Ban {
+        username: String,
+        /// shown to the user
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    Unban {
+        username: String,
+    },
+    /// List or purge temp login tokens
+    TempTokens {
+        #[command(subcommand)]
+        action: TempTokenAction,
+    },
+    /// Take a mod down; it is kept and can be restored
+    RemoveMod {
+        mod_id: Uuid,
+        /// shown to the author
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Bring a removed mod back (approved)
+    RestoreMod {
+        mod_id: Uuid,
+    },
+    /// Delete a mod for good, no matter who it belongs to
+    DeleteMod {
+        mod_id: Uuid,
+    },
+    /// Revoke all access tokens and stored provider refresh tokens, of one account or of everyone.
+    /// Client secrets of login providers live in the config; rotate them at the provider.
+    RotateSecrets {
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Run a housekeeping job now (unless another instance is running it):
+    /// `expired_login_codes`, `expired_access_tokens`, `removed_mod_purge` or `download_count_rollup`
+    RunJob {
+        name: String,
+    },
+    /// Print numbers about accounts, mods and tokens
+    Stats,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TempTokenAction {
+    List,
+    /// Delete the expired ones
+    Purge,
+}
+
+
+/// Expects the backend to be initialized (see [`crate::init`]).
+pub async fn run(command: AdminCommand) -> Result<(), String> {
+    match command {
+        AdminCommand::Migrate => {
+            migrations::run_migrations().await?;
+            println!("Database migrations are up to date");
+        }
+        AdminCommand::CreateAdmin { username, provider, provider_user_id, provider_username } => {
+            validate_username(&username).map_err(|e| e.describe())?;
+            if check_if_account_exists(&username).await.map_err(|e| e.describe())? {
+                return Err(ApiError::AccountAlreadyExists.describe())
+            }
+            if let Some(existing) = find_confusable_account(&username).await.map_err(|e| e.describe())? {
+                return Err(ApiError::ConfusableUsername(existing).describe())
+            }
+            let provider: &str = get_provider(&provider).map_err(|e| e.describe())?.name();
+            let account = AcornAccount { username: username.clone(), created_at: Utc::now() };
+            let identity = ExternalIdentity {
+                subject: provider_user_id,
+                username: provider_username.unwrap_or_else(|| username.clone()),
+                display_name: None,
+                avatar_url: None,
+            };
+            insert_account(&account, provider, &identity, None).await.map_err(|e| e.describe())?;
+            set_account_role(&username, AccountRole::Admin).await.map_err(|e| e.describe())?;
+            println!("Created admin account {username}; it logs in with {provider} user {}", identity.subject);
+        }
+        AdminCommand::SetRole { username, role } => {
+            set_account_role(&username, role).await.map_err(|e| e.describe())?;
+            println!("{username} is now {}", role.as_str());
+        }
+        AdminCommand::Ban { username, reason } => {
+            ban_account(&username, reason.as_deref()).await.map_err(|e| e.describe())?;
+            println!("Banned {username}");
+        }
+        AdminCommand::Unban { username } => {
+            unban_account(&username).await.map_err(|e| e.describe())?;
+            println!("Unbanned {username}");
+        }
+        AdminCommand::TempTokens { action: TempTokenAction::List } => {
+            let now = Utc::now();
+            let tokens: Vec<TempLoginToken> = get_temp_login_tokens().await.map_err(|e| e.describe())?;
+            for token in &tokens {
+                // enough to tell them apart, not enough to use them
+                let token_prefix: &str = token.token.get(0..6).unwrap_or(&token.token);
+                let state: &str = if token.expires_at < now { "expired" } else { "valid" };
+                println!("{token_prefix}...\t{}\t{state} (expires at {})", token.username, token.expires_at);
+            }
+            println!("{} temp login tokens", tokens.len());
+        }
+        AdminCommand::TempTokens { action: TempTokenAction::Purge } => {
+            let deleted: u64 = delete_expired_temp_login_tokens().await.map_err(|e| e.describe())?;
+            println!("Deleted {deleted} expired temp login tokens");
+        }
+        AdminCommand::RemoveMod { mod_id, reason } => {
+            remove_mod(mod_id, reason.as_deref()).await.map_err(|e| e.describe())?;
+            println!("Removed mod {mod_id}");
+        }
+        AdminCommand::RestoreMod { mod_id } => {
+            restore_mod(mod_id).await.map_err(|e| match e {
+                ApiError::ModNotFound => format!("There is no removed mod with ID {mod_id}"),
+                e => e.describe(),
+            })?;
+            println!("Restored mod {mod_id}");
+        }
+        AdminCommand::DeleteMod { mod_id } => {
+            delete_mod(mod_id).await.map_err(|e| e.describe())?;
+            println!("Deleted mod {mod_id}");
+        }
+        AdminCommand::RotateSecrets { username } => {
+            if let Some(username) = &username && !check_if_account_exists(username).await.map_err(|e| e.describe())? {
+                return Err(ApiError::AccountNotFound.describe())
+            }
+            let revoked: RevokedCredentials = revoke_credentials(username.as_deref()).await.map_err(|e| e.describe())?;
+            println!(
+                "Revoked {} access tokens, {} provider refresh tokens and {} temp login tokens of {}",
+                revoked.access_tokens, revoked.refresh_tokens, revoked.temp_login_tokens, username.as_deref().unwrap_or("all accounts"),
+            );
+        }
+        AdminCommand::RunJob { name } => {
+            let job: &Job = find_job(&name).ok_or_else(|| format!("Unknown job \"{name}\""))?;
+            match run_job(job, Duration::ZERO).await.map_err(|e| e.describe())? {
+                Some(affected_rows) => println!("Job {name} finished; {affected_rows} rows affected"),
+                None => println!("Job {name} is running on another instance right now"),
+            }
+        }
+        AdminCommand::Stats => print_stats().await.map_err(|e| e.describe())?,
+    }
+    Ok(())
+}
+
+
+async fn print_stats() -> Result<(), ApiError> {
+    let internal_error = |e: sqlx::Error| ApiError::Internal(format!("Could not fetch stats: {e}"));
+
+    let accounts = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "total!",
+            COUNT(*) FILTER (WHERE role = 'moderator') AS "moderators!",
+            COUNT(*) FILTER (WHERE role = 'admin') AS "admins!",
+            COUNT(*) FILTER (WHERE banned_at IS NOT NULL) AS "banned!"
+        FROM accounts
+        "#,
+    )
+        .fetch_one(pool())
+        .await
+        .map_err(internal_error)?;
+    println!("Accounts: {} ({} moderators, {} admins, {} banned)", accounts.total, accounts.moderators, accounts.admins, accounts.banned);
+
+    let identities = sqlx::query!(
+        r#"
+        SELECT provider, COUNT(*) AS "count!"
+        FROM identities
+        GROUP BY provider
+        ORDER BY provider
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(internal_error)?;
+    for identity in identities {
+        println!("  {} identities: {}", identity.provider, identity.count);
+    }
+
+    let mods = sqlx::query!(
+        r#"
+        SELECT
+            review_state,
+            COUNT(*) AS "count!",
+            COALESCE(SUM(OCTET_LENGTH(file_data)), 0)::BIGINT AS "file_bytes!",
+            COALESCE(SUM(download_count), 0)::BIGINT AS "downloads!"
+        FROM mods
+        GROUP BY review_state
+        ORDER BY review_state
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(internal_error)?;
+    let total_mods: i64 = mods.iter().map(|row| row.count).sum();
+    let total_bytes: i64 = mods.iter().map(|row| row.file_bytes).sum();
+    let total_downloads: i64 = mods.iter().map(|row| row.downloads).sum();
+    println!("Mods: {total_mods} ({total_bytes} bytes of files, {total_downloads} downloads)");
+    for row in mods {
+        println!("  {}: {} ({} bytes)", row.review_state, row.count, row.file_bytes);
+    }
+
+    let tokens = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM access_tokens) AS "access_tokens!",
+            (SELECT COUNT(*) FROM temp_login_tokens WHERE expires_at >= NOW()) AS "valid_temp_login_tokens!",
+            (SELECT COUNT(*) FROM temp_login_tokens WHERE expires_at < NOW()) AS "expired_temp_login_tokens!"
+        "#,
+    )
+        .fetch_one(pool())
+        .await
+        .map_err(internal_error)?;
+    println!("Access tokens: {}", tokens.access_tokens);
+    println!("Temp login tokens: {} valid, {} expired", tokens.valid_temp_login_tokens, tokens.expired_temp_login_tokens);
+    Ok(())
+}
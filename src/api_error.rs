@@ -0,0 +1,273 @@
+use rocket::http::Status;
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use acorngm_api_client::error::ErrorResponse;
+
+
+/// Every error the api can respond with.
+///
+/// Error responses look like `{"error": "<english message>", "code": "<code>"}`.
+/// The code is stable and meant for clients to match on (and localize); the message
+/// is only meant for humans and may change. All codes are listed in `docs/error_codes.md`.
+///
+/// Details of internal and upstream errors are logged, never sent to the client.
+#[derive(Debug)]
+pub enum ApiError {
+    /// the request body or form could not be parsed or is missing a field; the string says what is wrong
+    MalformedRequest(String),
+    UnsupportedContentType(String),
+    ImageTooLarge,
+    InvalidUsername,
+    /// the string says which constraint the title violates
+    InvalidTitle(&'static str),
+    /// the string says which constraint the description violates
+    InvalidDescription(&'static str),
+    InvalidGameName,
+    InvalidGameVersion,
+    InvalidLanguage,
+    InvalidModId,
+    InvalidRating,
+    /// the string says which tag is wrong and why
+    InvalidTag(String),
+    /// the unknown category
+    InvalidCategory(String),
+    /// the string says what is wrong with the image
+    InvalidImage(String),
+    InvalidScreenshotOrder,
+    NothingToUpdate,
+    /// the string says which constraint the rejection reason violates
+    InvalidRejectionReason(String),
+    /// the string says which parameter is wrong and why
+    InvalidSearchParameter(String),
+    NotAuthenticated,
+    /// the display name of the identity provider
+    InvalidAuthorizationCode(String),
+    /// the display name of the identity provider
+    InvalidProviderToken(String),
+    /// the display name of the identity provider
+    IdentityMismatch(String),
+    NotModOwner,
+    OwnModRating,
+    NotModerator,
+    /// the reason given by the operator, if any
+    AccountBanned(Option<String>),
+    ModRemoved,
+    /// the path of the unknown url
+    UnknownUrl(String),
+    UnknownIdentityProvider(String),
+    AccountNotFound,
+    ModNotFound,
+    PendingModNotFound,
+    CanonicalTagNotFound,
+    ScreenshotNotFound,
+    TempLoginTokenNotFound,
+    IdentityNotLinked,
+    IdentityMoveNotFound,
+    AccountAlreadyExists,
+    /// the existing username that looks like the requested one
+    ConfusableUsername(String),
+    IdentityAlreadyLinked,
+    LastIdentity,
+    TempLoginTokenAlreadyExists,
+    TooManyScreenshots,
+    UnprocessableJson,
+    RateLimited,
+    AccountRateLimited,
+    /// the identity provider (display name) could not be reached or sent something unexpected; the details
+    IdentityProvider(String, String),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::MalformedRequest(_)
+            | ApiError::InvalidUsername
+            | ApiError::InvalidTitle(_)
+            | ApiError::InvalidDescription(_)
+            | ApiError::InvalidGameName
+            | ApiError::InvalidGameVersion
+            | ApiError::InvalidLanguage
+            | ApiError::InvalidModId
+            | ApiError::InvalidRating
+            | ApiError::InvalidTag(_)
+            | ApiError::InvalidCategory(_)
+            | ApiError::InvalidImage(_)
+            | ApiError::InvalidScreenshotOrder
+            | ApiError::NothingToUpdate
+            | ApiError::InvalidRejectionReason(_)
+            | ApiError::InvalidSearchParameter(_) => Status::BadRequest,
+            ApiError::ImageTooLarge => Status::PayloadTooLarge,
+            ApiError::UnsupportedContentType(_) => Status::UnsupportedMediaType,
+            ApiError::NotAuthenticated
+            | ApiError::InvalidAuthorizationCode(_)
+            | ApiError::InvalidProviderToken(_)
+            | ApiError::IdentityMismatch(_) => Status::Unauthorized,
+            ApiError::NotModOwner
+            | ApiError::OwnModRating
+            | ApiError::NotModerator
+            | ApiError::AccountBanned(_)
+            | ApiError::ModRemoved => Status::Forbidden,
+            ApiError::UnknownUrl(_)
+            | ApiError::UnknownIdentityProvider(_)
+            | ApiError::IdentityNotLinked
+            | ApiError::IdentityMoveNotFound
+            | ApiError::AccountNotFound
+            | ApiError::ModNotFound
+            | ApiError::PendingModNotFound
+            | ApiError::CanonicalTagNotFound
+            | ApiError::ScreenshotNotFound
+            | ApiError::TempLoginTokenNotFound => Status::NotFound,
+            ApiError::AccountAlreadyExists
+            | ApiError::ConfusableUsername(_)
+            | ApiError::IdentityAlreadyLinked
+            | ApiError::LastIdentity
+            | ApiError::TempLoginTokenAlreadyExists
+            | ApiError::TooManyScreenshots => Status::Conflict,
+            ApiError::UnprocessableJson => Status::UnprocessableEntity,
+            ApiError::RateLimited | ApiError::AccountRateLimited => Status::TooManyRequests,
+            ApiError::IdentityProvider(_, _) => Status::BadGateway,
+            ApiError::Internal(_) => Status::InternalServerError,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::MalformedRequest(_) => "malformed_request",
+            ApiError::UnsupportedContentType(_) => "unsupported_content_type",
+            ApiError::InvalidUsername => "invalid_username",
+            ApiError::InvalidTitle(_) => "invalid_title",
+            ApiError::InvalidDescription(_) => "invalid_description",
+            ApiError::InvalidGameName => "invalid_game_name",
+            ApiError::InvalidGameVersion => "invalid_game_version",
+            ApiError::InvalidLanguage => "invalid_language",
+            ApiError::InvalidModId => "invalid_mod_id",
+            ApiError::InvalidRating => "invalid_rating",
+            ApiError::InvalidTag(_) => "invalid_tag",
+            ApiError::InvalidCategory(_) => "invalid_category",
+            ApiError::InvalidImage(_) => "invalid_image",
+            ApiError::InvalidScreenshotOrder => "invalid_screenshot_order",
+            ApiError::ImageTooLarge => "image_too_large",
+            ApiError::NothingToUpdate => "nothing_to_update",
+            ApiError::InvalidRejectionReason(_) => "invalid_rejection_reason",
+            ApiError::InvalidSearchParameter(_) => "invalid_search_parameter",
+            ApiError::NotAuthenticated => "not_authenticated",
+            ApiError::InvalidAuthorizationCode(_) => "invalid_authorization_code",
+            ApiError::InvalidProviderToken(_) => "invalid_provider_token",
+            ApiError::IdentityMismatch(_) => "identity_mismatch",
+            ApiError::NotModOwner => "not_mod_owner",
+            ApiError::OwnModRating => "own_mod_rating",
+            ApiError::NotModerator => "not_moderator",
+            ApiError::AccountBanned(_) => "account_banned",
+            ApiError::ModRemoved => "mod_removed",
+            ApiError::UnknownUrl(_) => "unknown_url",
+            ApiError::UnknownIdentityProvider(_) => "unknown_identity_provider",
+            ApiError::AccountNotFound => "account_not_found",
+            ApiError::ModNotFound => "mod_not_found",
+            ApiError::PendingModNotFound => "pending_mod_not_found",
+            ApiError::CanonicalTagNotFound => "canonical_tag_not_found",
+            ApiError::ScreenshotNotFound => "screenshot_not_found",
+            ApiError::TempLoginTokenNotFound => "temp_login_token_not_found",
+            ApiError::IdentityNotLinked => "identity_not_linked",
+            ApiError::IdentityMoveNotFound => "identity_move_not_found",
+            ApiError::AccountAlreadyExists => "account_already_exists",
+            ApiError::ConfusableUsername(_) => "confusable_username",
+            ApiError::IdentityAlreadyLinked => "identity_already_linked",
+            ApiError::LastIdentity => "last_identity",
+            ApiError::TempLoginTokenAlreadyExists => "temp_login_token_already_exists",
+            ApiError::TooManyScreenshots => "too_many_screenshots",
+            ApiError::UnprocessableJson => "unprocessable_json",
+            ApiError::RateLimited => "rate_limited",
+            ApiError::AccountRateLimited => "account_rate_limited",
+            ApiError::IdentityProvider(_, _) => "identity_provider_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::MalformedRequest(detail) => detail.clone(),
+            ApiError::UnsupportedContentType(content_type) => format!("Unsupported content type {content_type}"),
+            ApiError::InvalidUsername => "Invalid username! Username must be 3-32 characters long \
+                and contain only latin letters, digits, underscores, and hyphens; without spaces.".to_string(),
+            ApiError::InvalidTitle(detail) => detail.to_string(),
+            ApiError::InvalidDescription(detail) => detail.to_string(),
+            ApiError::InvalidGameName => "Invalid or unknown game name".to_string(),
+            ApiError::InvalidGameVersion => "Invalid game version".to_string(),
+            ApiError::InvalidLanguage => "Language has to be a two letter ISO 639-1 code, like `en`".to_string(),
+            ApiError::InvalidModId => "Invalid Mod UUID".to_string(),
+            ApiError::InvalidRating => "Rating has to be 1 to 5 stars".to_string(),
+            ApiError::InvalidTag(detail) => detail.clone(),
+            ApiError::InvalidCategory(category) => format!("Unknown category \"{category}\"; use `gameplay`, `cosmetic`, `translation`, `qol` or `content`"),
+            ApiError::InvalidImage(detail) => detail.clone(),
+            ApiError::InvalidScreenshotOrder => "The new order has to contain every screenshot of the mod exactly once".to_string(),
+            ApiError::ImageTooLarge => "The image file is too large".to_string(),
+            ApiError::NothingToUpdate => "Nothing to update".to_string(),
+            ApiError::InvalidRejectionReason(detail) => detail.clone(),
+            ApiError::InvalidSearchParameter(detail) => detail.clone(),
+            ApiError::NotAuthenticated => "Not authenticated; invalid username or access token".to_string(),
+            ApiError::InvalidAuthorizationCode(provider) => format!("The provided {provider} code is invalid, expired or already used"),
+            ApiError::InvalidProviderToken(provider) => format!("The provided {provider} access token is invalid"),
+            ApiError::IdentityMismatch(provider) => format!("The provided {provider} user ID does not belong to the provided {provider} access token!"),
+            ApiError::NotModOwner => "Unauthorized; you do not have permission to modify this mod".to_string(),
+            ApiError::OwnModRating => "You can not rate your own mod".to_string(),
+            ApiError::NotModerator => "Forbidden; only moderators can do this".to_string(),
+            ApiError::AccountBanned(None) => "This account is banned".to_string(),
+            ApiError::AccountBanned(Some(reason)) => format!("This account is banned: {reason}"),
+            ApiError::ModRemoved => "This mod was removed by the server operators and can not be changed".to_string(),
+            ApiError::UnknownUrl(path) => format!("Unknown URL: {path}"),
+            ApiError::UnknownIdentityProvider(provider) => format!("Unknown or disabled login provider \"{provider}\""),
+            ApiError::AccountNotFound => "Account does not exist".to_string(),
+            ApiError::ModNotFound => "Mod does not exist".to_string(),
+            ApiError::PendingModNotFound => "There is no mod with this ID waiting for review".to_string(),
+            ApiError::CanonicalTagNotFound => "This tag is not a canonical tag".to_string(),
+            ApiError::ScreenshotNotFound => "Screenshot does not exist".to_string(),
+            ApiError::TempLoginTokenNotFound => "Could not find username for temp login token. \
+                It may have expired or the user has not finished logging in yet.".to_string(),
+            ApiError::IdentityNotLinked => "This identity is not linked to your account".to_string(),
+            ApiError::IdentityMoveNotFound => "Account move code is invalid or expired".to_string(),
+            ApiError::AccountAlreadyExists => "Account with this username already exists!".to_string(),
+            ApiError::ConfusableUsername(existing) => format!("Username looks too much like the existing username {existing}"),
+            ApiError::IdentityAlreadyLinked => "This identity is already linked to an AcornGM account!".to_string(),
+            ApiError::LastIdentity => "The last linked identity of an account can not be unlinked".to_string(),
+            ApiError::TempLoginTokenAlreadyExists => "Temp login token already exists".to_string(),
+            ApiError::TooManyScreenshots => "This mod already has as many screenshots as allowed".to_string(),
+            ApiError::UnprocessableJson => "The request body is not valid json for this endpoint".to_string(),
+            ApiError::RateLimited => "Too many requests!".to_string(),
+            ApiError::AccountRateLimited => "Too many requests for this account!".to_string(),
+            ApiError::IdentityProvider(provider, _) => format!("Could not communicate with {provider}; please try again later"),
+            ApiError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+}
+
+impl ApiError {
+    /// For logs and operators: the message together with the details that are never sent to clients.
+    pub fn describe(&self) -> String {
+        match self {
+            ApiError::Internal(detail) => detail.clone(),
+            ApiError::IdentityProvider(provider, detail) => format!("{provider}: {detail}"),
+            error => format!("{} ({})", error.message(), error.code()),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        match &self {
+            // the path only; query strings can contain login codes
+            ApiError::Internal(detail) => error!("Internal error while handling {} {}: {detail}", req.method(), req.uri().path()),
+            ApiError::IdentityProvider(provider, detail) => error!("{provider} error while handling {} {}: {detail}", req.method(), req.uri().path()),
+            _ => {}
+        }
+
+        status::Custom(
+            self.status(),
+            Json(ErrorResponse {
+                error: self.message(),
+                code: self.code().to_string(),
+            })
+        ).respond_to(req)
+    }
+}
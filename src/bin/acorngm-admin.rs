@@ -0,0 +1,23 @@
+use std::process::ExitCode;
+use clap::Parser;
+use acorngm_backend::admin::{run, AdminCli};
+use acorngm_backend::{config, init};
+
+
+#[rocket::main]
+async fn main() -> ExitCode {
+    let cli = AdminCli::parse();
+    dotenvy::dotenv().ok();
+
+    if let Err(e) = init(&config::figment()).await {
+        eprintln!("{e}");
+        return ExitCode::FAILURE
+    }
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,7 +1,7 @@
-use rocket::http::Status;
 use rocket::Request;
 use rocket_dyn_templates::{context, Template};
-use crate::{respond_err, ApiResponse};
+use crate::ApiResponse;
+use crate::api_error::ApiError;
 
 
 #[catch(404)]
@@ -13,16 +13,16 @@ pub fn html_catch_404(req: &Request) -> Template {
 
 #[catch(404)]
 pub fn api_catch_404(req: &Request) -> ApiResponse {
-    Err(respond_err(Status::NotFound, &format!("Unknown URL: {}", req.uri().path().as_str())))
+    Err(ApiError::UnknownUrl(req.uri().path().to_string()))
 }
 
 #[catch(422)]
 pub fn api_catch_422() -> ApiResponse {
-    Err(respond_err(Status::UnprocessableEntity, "ur json is fucked"))
+    Err(ApiError::UnprocessableJson)
 }
 
 #[catch(429)]
 pub fn api_catch_429() -> ApiResponse {
-    Err(respond_err(Status::TooManyRequests, "Too many requests!"))
+    Err(ApiError::RateLimited)
 }
 
@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use reqwest::Url;
+use rocket::data::ByteUnit;
+use rocket::figment::Figment;
+use rocket::figment::providers::{Env, Format, Toml};
+use serde::{Deserialize, Deserializer};
+use crate::logging::LogFormat;
+use crate::rate_limit::Budget;
+use crate::review::ReviewPolicy;
+
+
+/// Everything the server needs to know at runtime. Loaded once at startup (see [`figment`])
+/// so the same binary can serve staging and production.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AppConfig {
+    pub database_url: String,
+    #[serde(default = "default_database_pool_size")]
+    pub database_pool_size: u32,
+    /// otherwise migrations have to be run with the `migrate` subcommand before starting the server
+    #[serde(default = "default_migrate_on_startup")]
+    pub migrate_on_startup: bool,
+    /// where the frontend is reachable from the outside, without trailing slash
+    pub public_base_url: String,
+    #[serde(default = "default_frontend_dir")]
+    pub frontend_dir: PathBuf,
+    #[serde(default = "default_max_mod_file_size")]
+    pub max_mod_file_size: ByteUnit,
+    pub discord: DiscordConfig,
+    /// github login is only offered if this is set
+    pub github: Option<GitHubConfig>,
+    /// generic openid connect providers, keyed by the name used in urls and the database
+    #[serde(default)]
+    pub oidc: HashMap<String, OidcConfig>,
+    #[serde(default)]
+    pub review: ReviewPolicy,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub search_ranking: SearchRanking,
+    #[serde(default)]
+    pub screenshots: ScreenshotConfig,
+    /// `text` or `json` (one object per line)
+    #[serde(default)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DiscordConfig {
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_id: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_secret: String,
+    /// defaults to the discord auth page of the frontend
+    pub redirect_uri: Option<String>,
+    #[serde(default = "default_discord_api_base_url")]
+    pub api_base_url: String,
+    #[serde(default = "default_discord_authorize_url")]
+    pub authorize_url: String,
+    /// avatars are served from here
+    #[serde(default = "default_discord_cdn_base_url")]
+    pub cdn_base_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GitHubConfig {
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_id: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_secret: String,
+    /// defaults to the generic auth page of the frontend
+    pub redirect_uri: Option<String>,
+    /// where the authorize and access token endpoints live
+    #[serde(default = "default_github_oauth_base_url")]
+    pub oauth_base_url: String,
+    #[serde(default = "default_github_api_base_url")]
+    pub api_base_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct OidcConfig {
+    /// shown to users, e.g. "Codeberg"
+    pub display_name: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_id: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub client_secret: String,
+    /// defaults to the generic auth page of the frontend
+    pub redirect_uri: Option<String>,
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    #[serde(default = "default_oidc_scope")]
+    pub scope: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    Memory,
+    Postgres,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RateLimitConfig {
+    #[serde(default = "default_rate_limit_backend")]
+    pub backend: RateLimitBackend,
+    #[serde(default = "default_auth_budget")]
+    pub auth: Budget,
+    #[serde(default = "default_upload_budget")]
+    pub upload: Budget,
+    #[serde(default = "default_read_budget")]
+    pub read: Budget,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            backend: default_rate_limit_backend(),
+            auth: default_auth_budget(),
+            upload: default_upload_budget(),
+            read: default_read_budget(),
+        }
+    }
+}
+
+/// Housekeeping jobs (see `src/jobs.rs`).
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct JobsConfig {
+    /// whether this instance runs the jobs; with several instances, any number of them can, they take turns
+    #[serde(default = "default_jobs_enabled")]
+    pub enabled: bool,
+    /// access tokens older than this are deleted; they never expire if unset
+    pub access_token_max_age_days: Option<i64>,
+    /// mods removed by an operator are deleted for good after this long; they are kept forever if unset
+    pub purge_removed_mods_after_days: Option<i64>,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            enabled: default_jobs_enabled(),
+            access_token_max_age_days: None,
+            purge_removed_mods_after_days: None,
+        }
+    }
+}
+
+/// Prometheus metrics (see `src/metrics.rs`), served by their own listener so they stay internal.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// has to be a loopback or private network address
+    #[serde(default = "default_metrics_address")]
+    pub address: IpAddr,
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: false,
+            address: default_metrics_address(),
+            port: default_metrics_port(),
+        }
+    }
+}
+
+/// How the `relevance` order of search (see `src/search_mods.rs`) blends its signals. Each signal is scaled
+/// to 0-1 and multiplied by its weight; a weight of 0 turns a signal off.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SearchRanking {
+    /// how well the title and description match the search terms
+    pub text_weight: f64,
+    /// downloads, on a log scale that reaches 1 at `downloads_for_full_score`
+    pub downloads_weight: f64,
+    pub downloads_for_full_score: f64,
+    /// average rating, pulled towards 3 stars while a mod has few ratings
+    pub rating_weight: f64,
+    /// 1 right after the file or description changed, halving every `update_half_life_days`
+    pub recent_update_weight: f64,
+    pub update_half_life_days: f64,
+    /// 1 right after upload, halving every `freshness_half_life_days`
+    pub freshness_weight: f64,
+    pub freshness_half_life_days: f64,
+}
+
+impl Default for SearchRanking {
+    fn default() -> Self {
+        SearchRanking {
+            text_weight: 1.0,
+            downloads_weight: 0.3,
+            downloads_for_full_score: 10_000.0,
+            rating_weight: 0.2,
+            recent_update_weight: 0.1,
+            update_half_life_days: 14.0,
+            freshness_weight: 0.1,
+            freshness_half_life_days: 30.0,
+        }
+    }
+}
+
+/// Screenshots of mods (see `src/screenshots.rs`).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ScreenshotConfig {
+    pub max_per_mod: i64,
+    /// of the uploaded file
+    pub max_file_size: ByteUnit,
+    /// larger images are rejected instead of decoded, in pixels per side
+    pub max_dimension: u32,
+    /// thumbnails fit into this box and keep the aspect ratio of the image
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            max_per_mod: 8,
+            max_file_size: ByteUnit::Mebibyte(8),
+            max_dimension: 8192,
+            thumbnail_width: 480,
+            thumbnail_height: 270,
+        }
+    }
+}
+
+/// figment parses environment variables that look like numbers (discord ids for example) as numbers
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(crate = "rocket::serde", untagged)]
+    enum StringOrNumber {
+        String(String),
+        Unsigned(u64),
+        Signed(i64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(string) => string,
+        StringOrNumber::Unsigned(number) => number.to_string(),
+        StringOrNumber::Signed(number) => number.to_string(),
+    })
+}
+
+fn default_database_pool_size() -> u32 { 5 }
+fn default_migrate_on_startup() -> bool { true }
+fn default_frontend_dir() -> PathBuf { PathBuf::from("./frontend/") }
+fn default_max_mod_file_size() -> ByteUnit { ByteUnit::Mebibyte(16) }
+fn default_discord_api_base_url() -> String { "https://discord.com/api/v10".to_string() }
+fn default_discord_authorize_url() -> String { "https://discord.com/oauth2/authorize".to_string() }
+fn default_discord_cdn_base_url() -> String { "https://cdn.discordapp.com".to_string() }
+fn default_github_oauth_base_url() -> String { "https://github.com/login/oauth".to_string() }
+fn default_github_api_base_url() -> String { "https://api.github.com".to_string() }
+fn default_oidc_scope() -> String { "openid profile".to_string() }
+fn default_rate_limit_backend() -> RateLimitBackend { RateLimitBackend::Memory }
+fn default_auth_budget() -> Budget { Budget { capacity: 10.0, refill_per_second: 10.0 / 60.0 } }
+fn default_upload_budget() -> Budget { Budget { capacity: 10.0, refill_per_second: 10.0 / 3600.0 } }
+fn default_read_budget() -> Budget { Budget { capacity: 120.0, refill_per_second: 2.0 } }
+fn default_jobs_enabled() -> bool { true }
+fn default_metrics_address() -> IpAddr { IpAddr::V4(Ipv4Addr::LOCALHOST) }
+fn default_metrics_port() -> u16 { 9464 }
+
+
+impl AppConfig {
+    pub fn discord_redirect_uri(&self) -> String {
+        self.discord.redirect_uri.clone().unwrap_or_else(|| format!("{}/discord_auth_page.html", self.public_base_url))
+    }
+
+    /// where providers other than discord redirect to after the user authorized AcornGM
+    pub fn default_redirect_uri(&self, provider: &str) -> String {
+        format!("{}/auth_page.html?provider={provider}", self.public_base_url)
+    }
+
+    /// the url users get sent to in order to authorize AcornGM on discord
+    pub fn discord_authorize_url(&self) -> Result<Url, String> {
+        Url::parse_with_params(&self.discord.authorize_url, &[
+            ("client_id", self.discord.client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", &self.discord_redirect_uri()),
+            ("scope", "identify"),
+        ]).map_err(|e| format!("Invalid discord authorize url \"{}\": {e}", self.discord.authorize_url))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.database_url.starts_with("postgres://") && !self.database_url.starts_with("postgresql://") {
+            return Err("`database_url` has to be a postgres connection url".to_string())
+        }
+        if self.database_pool_size == 0 {
+            return Err("`database_pool_size` has to be at least 1".to_string())
+        }
+
+        validate_http_url("public_base_url", &self.public_base_url)?;
+        if self.public_base_url.ends_with('/') {
+            return Err("`public_base_url` must not end with a slash".to_string())
+        }
+        if !self.frontend_dir.is_dir() {
+            return Err(format!("`frontend_dir` {} is not a directory", self.frontend_dir.display()))
+        }
+        if self.max_mod_file_size.as_u64() == 0 {
+            return Err("`max_mod_file_size` has to be greater than zero".to_string())
+        }
+
+        if self.discord.client_id.is_empty() {
+            return Err("`discord.client_id` is not set".to_string())
+        }
+        if self.discord.client_secret.is_empty() {
+            return Err("`discord.client_secret` is not set".to_string())
+        }
+        validate_http_url("discord.redirect_uri", &self.discord_redirect_uri())?;
+        validate_http_url("discord.api_base_url", &self.discord.api_base_url)?;
+        validate_http_url("discord.cdn_base_url", &self.discord.cdn_base_url)?;
+        self.discord_authorize_url()?;
+
+        if let Some(github) = &self.github {
+            if github.client_id.is_empty() || github.client_secret.is_empty() {
+                return Err("`github.client_id` and `github.client_secret` have to be set".to_string())
+            }
+            if let Some(redirect_uri) = &github.redirect_uri {
+                validate_http_url("github.redirect_uri", redirect_uri)?;
+            }
+            validate_http_url("github.oauth_base_url", &github.oauth_base_url)?;
+            validate_http_url("github.api_base_url", &github.api_base_url)?;
+        }
+
+        for (name, oidc) in &self.oidc {
+            let valid_name: bool = !name.is_empty() && name.chars().all(|char| matches!(char, 'a'..='z' | '0'..='9' | '_' | '-'));
+            if !valid_name || matches!(name.as_str(), "discord" | "github") {
+                return Err(format!("`oidc.{name}`: provider names have to be lowercase alphanumeric and must not be discord or github"))
+            }
+            if oidc.client_id.is_empty() || oidc.client_secret.is_empty() {
+                return Err(format!("`oidc.{name}.client_id` and `oidc.{name}.client_secret` have to be set"))
+            }
+            if let Some(redirect_uri) = &oidc.redirect_uri {
+                validate_http_url(&format!("oidc.{name}.redirect_uri"), redirect_uri)?;
+            }
+            validate_http_url(&format!("oidc.{name}.authorize_url"), &oidc.authorize_url)?;
+            validate_http_url(&format!("oidc.{name}.token_url"), &oidc.token_url)?;
+            validate_http_url(&format!("oidc.{name}.userinfo_url"), &oidc.userinfo_url)?;
+        }
+
+        if self.review.min_account_age_days.is_some_and(|days| days < 0) {
+            return Err("`review.min_account_age_days` must not be negative".to_string())
+        }
+        if self.review.min_approved_mods.is_some_and(|count| count < 0) {
+            return Err("`review.min_approved_mods` must not be negative".to_string())
+        }
+
+        for (name, budget) in [("auth", self.rate_limit.auth), ("upload", self.rate_limit.upload), ("read", self.rate_limit.read)] {
+            if budget.capacity < 1.0 || budget.refill_per_second <= 0.0 {
+                return Err(format!("`rate_limit.{name}` needs a capacity of at least 1 and a positive refill rate"))
+            }
+        }
+
+        if self.jobs.access_token_max_age_days.is_some_and(|days| days < 1) {
+            return Err("`jobs.access_token_max_age_days` has to be at least 1".to_string())
+        }
+        if self.jobs.purge_removed_mods_after_days.is_some_and(|days| days < 0) {
+            return Err("`jobs.purge_removed_mods_after_days` must not be negative".to_string())
+        }
+
+        if self.metrics.enabled && !is_internal_address(self.metrics.address) {
+            return Err(format!("`metrics.address` {} is not a loopback or private network address", self.metrics.address))
+        }
+
+        let ranking: SearchRanking = self.search_ranking;
+        for (name, weight) in [
+            ("text_weight", ranking.text_weight),
+            ("downloads_weight", ranking.downloads_weight),
+            ("rating_weight", ranking.rating_weight),
+            ("recent_update_weight", ranking.recent_update_weight),
+            ("freshness_weight", ranking.freshness_weight),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("`search_ranking.{name}` has to be a number of at least 0"))
+            }
+        }
+        for (name, value) in [
+            ("downloads_for_full_score", ranking.downloads_for_full_score),
+            ("update_half_life_days", ranking.update_half_life_days),
+            ("freshness_half_life_days", ranking.freshness_half_life_days),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("`search_ranking.{name}` has to be greater than 0"))
+            }
+        }
+
+        let screenshots: ScreenshotConfig = self.screenshots;
+        if screenshots.max_per_mod < 1 || screenshots.max_file_size.as_u64() == 0 {
+            return Err("`screenshots.max_per_mod` and `screenshots.max_file_size` have to be greater than 0".to_string())
+        }
+        if screenshots.thumbnail_width == 0 || screenshots.thumbnail_height == 0 || screenshots.max_dimension < screenshots.thumbnail_width.max(screenshots.thumbnail_height) {
+            return Err("`screenshots.thumbnail_width` and `screenshots.thumbnail_height` have to be between 1 and `screenshots.max_dimension`".to_string())
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_http_url(name: &str, url: &str) -> Result<(), String> {
+    let parsed: Url = Url::parse(url).map_err(|e| format!("`{name}` is not a valid url: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("`{name}` has to be an http(s) url"))
+    }
+    Ok(())
+}
+
+/// loopback, private and link-local networks; not `0.0.0.0`, which would listen on every interface
+fn is_internal_address(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        // fc00::/7 are unique local addresses
+        IpAddr::V6(v6) => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    }
+}
+
+
+/// Configuration sources, from lowest to highest priority:
+/// 1. `Rocket.toml` and `ROCKET_*` environment variables (Rocket's own figment)
+/// 2. the config file at `ACORNGM_CONFIG` (default `AcornGM.toml`), with the same profiles as `Rocket.toml`
+/// 3. `DATABASE_URL` and `DISCORD_CLIENT_SECRET` environment variables
+/// 4. `ACORNGM_*` environment variables; nested keys are separated by `__`,
+///    e.g. `ACORNGM_DISCORD__CLIENT_ID`
+pub fn figment() -> Figment {
+    let config_file: String = std::env::var("ACORNGM_CONFIG").unwrap_or_else(|_| "AcornGM.toml".to_string());
+
+    rocket::Config::figment()
+        .merge(Toml::file(config_file).nested())
+        .merge(Env::raw().only(&["database_url"]).global())
+        .merge(Env::raw().only(&["discord_client_secret"]).map(|_| "discord.client_secret".into()).global())
+        .merge(Env::prefixed("ACORNGM_").ignore(&["config"]).split("__").global())
+}
@@ -0,0 +1,121 @@
+//! Probes for container orchestration. `/healthz` only says the process is serving requests;
+//! `/readyz` checks everything a request needs and answers 503 if any of it is missing.
+
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use serde::Serialize;
+use crate::pool;
+use crate::api_error::ApiError;
+use crate::migrations::MIGRATOR;
+
+
+/// a check that takes longer than this counts as failed, so a hanging database does not hang the probe
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    /// `ok` or `unavailable`
+    pub status: &'static str,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    /// `ok` or `unavailable`
+    pub status: &'static str,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+
+#[get("/healthz")]
+pub fn healthz() -> Json<HealthReport> {
+    Json(HealthReport { status: "ok", components: BTreeMap::new() })
+}
+
+#[get("/readyz")]
+pub async fn readyz() -> (Status, Json<HealthReport>) {
+    let (database, storage, migrations) = rocket::tokio::join!(
+        check(check_database()),
+        check(check_storage()),
+        check(check_migrations()),
+    );
+    let components = BTreeMap::from([("database", database), ("storage", storage), ("migrations", migrations)]);
+
+    let ready: bool = components.values().all(|component| component.error.is_none());
+    if !ready {
+        warn!("Not ready: {:?}", components);
+    }
+    let status: Status = if ready { Status::Ok } else { Status::ServiceUnavailable };
+    (status, Json(HealthReport { status: if ready { "ok" } else { "unavailable" }, components }))
+}
+
+
+async fn check(check: impl Future<Output = Result<(), ApiError>>) -> ComponentHealth {
+    let started = Instant::now();
+    let result: Result<(), String> = match rocket::tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result.map_err(|e| e.describe()),
+        Err(_) => Err(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    ComponentHealth {
+        status: if result.is_ok() { "ok" } else { "unavailable" },
+        duration_ms: started.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
+
+async fn check_database() -> Result<(), ApiError> {
+    sqlx::query_scalar!("SELECT 1 AS \"one!\"")
+        .fetch_one(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not query database: {e}")))?;
+    Ok(())
+}
+
+/// Mod files are stored in the database, so storage is writable if the database accepts writes
+/// (it is not a read-only replica or in a read-only transaction mode).
+async fn check_storage() -> Result<(), ApiError> {
+    let writable: bool = sqlx::query_scalar!(
+        r#"
+        SELECT NOT pg_is_in_recovery() AND current_setting('transaction_read_only') = 'off' AS "writable!"
+        "#,
+    )
+        .fetch_one(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not check whether the database is writable: {e}")))?;
+    if !writable {
+        return Err(ApiError::Internal("Database is read-only".to_string()))
+    }
+    Ok(())
+}
+
+/// Every migration embedded in this binary has been applied successfully.
+async fn check_migrations() -> Result<(), ApiError> {
+    // sqlx's own bookkeeping table, not part of our schema, so the query can not be checked at compile time
+    let applied: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT version
+        FROM _sqlx_migrations
+        WHERE success
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch applied migrations: {e}")))?;
+    let applied: HashSet<i64> = applied.into_iter().collect();
+
+    let pending: Vec<String> = MIGRATOR.iter()
+        .filter(|migration| migration.migration_type.is_up_migration() && !applied.contains(&migration.version))
+        .map(|migration| format!("{} {}", migration.version, migration.description))
+        .collect();
+    if !pending.is_empty() {
+        return Err(ApiError::Internal(format!("Pending migrations: {}", pending.join(", "))))
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use reqwest::Url;
+use serde::Deserialize;
+use crate::app_config;
+use crate::api_error::ApiError;
+use crate::config::DiscordConfig;
+use crate::identity::{exchange_code_basic_auth, get_with_access_token, ExternalIdentity, IdentityProvider, ProviderTokens};
+
+
+pub struct DiscordProvider {
+    pub config: &'static DiscordConfig,
+}
+
+/// `global_name` and `avatar` are null for accounts that never set them
+#[derive(Debug, Deserialize)]
+struct DiscordUserInfo {
+    id: String,
+    username: String,
+    global_name: Option<String>,
+    avatar: Option<String>,
+}
+
+#[rocket::async_trait]
+impl IdentityProvider for DiscordProvider {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn display_name(&self) -> &str {
+        "Discord"
+    }
+
+    fn authorize_url(&self) -> Result<Url, String> {
+        app_config().discord_authorize_url()
+    }
+
+    async fn exchange_code(&self, code: &str) -> Result<ProviderTokens, ApiError> {
+        exchange_code_basic_auth(
+            self,
+            &format!("{}/oauth2/token", self.config.api_base_url),
+            &self.config.client_id,
+            &self.config.client_secret,
+            &app_config().discord_redirect_uri(),
+            code,
+        ).await
+    }
+
+    async fn fetch_identity(&self, access_token: &str) -> Result<ExternalIdentity, ApiError> {
+        let user_info: DiscordUserInfo = get_with_access_token(self, &format!("{}/users/@me", self.config.api_base_url), access_token).await?;
+        let avatar_url: Option<String> = user_info.avatar.map(|avatar_hash| format!("{}/avatars/{}/{avatar_hash}.png", self.config.cdn_base_url, user_info.id));
+        Ok(ExternalIdentity {
+            subject: user_info.id,
+            username: user_info.username,
+            display_name: user_info.global_name,
+            avatar_url,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use reqwest::Url;
+use serde::Deserialize;
+use crate::app_config;
+use crate::api_error::ApiError;
+use crate::config::GitHubConfig;
+use crate::identity::{describe_token_response, get_with_access_token, http_client, ExternalIdentity, IdentityProvider, ProviderTokens};
+
+
+pub struct GitHubProvider {
+    pub config: &'static GitHubConfig,
+}
+
+impl GitHubProvider {
+    fn redirect_uri(&self) -> String {
+        self.config.redirect_uri.clone().unwrap_or_else(|| app_config().default_redirect_uri(self.name()))
+    }
+}
+
+/// github answers failed token exchanges with 200 and an `error` field
+#[derive(Debug, Deserialize)]
+struct GitHubTokenResponse {
+    access_token: Option<String>,
+    /// only for apps with expiring user tokens
+    refresh_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    id: u64,
+    login: String,
+    name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+#[rocket::async_trait]
+impl IdentityProvider for GitHubProvider {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn display_name(&self) -> &str {
+        "GitHub"
+    }
+
+    fn authorize_url(&self) -> Result<Url, String> {
+        let authorize_url: String = format!("{}/authorize", self.config.oauth_base_url);
+        Url::parse_with_params(&authorize_url, &[
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", &self.redirect_uri()),
+            ("scope", "read:user"),
+        ]).map_err(|e| format!("Invalid github authorize url \"{authorize_url}\": {e}"))
+    }
+
+    async fn exchange_code(&self, code: &str) -> Result<ProviderTokens, ApiError> {
+        let provider_error = |detail: String| ApiError::IdentityProvider(self.display_name().to_string(), detail);
+
+        let res = http_client()
+            .post(format!("{}/access_token", self.config.oauth_base_url))
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", &self.config.client_secret),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri()),
+            ])
+            .send()
+            .await
+            .map_err(|e| provider_error(format!("Request for access token failed: {e}")))?;
+
+        let status = res.status();
+        let body: String = res.text().await
+            .map_err(|e| provider_error(format!("Could not get text from response body while getting access token: {e}")))?;
+        if !status.is_success() {
+            return Err(provider_error(format!("Error while getting access token - {status}: {}", describe_token_response(&body))))
+        }
+
+        let token: GitHubTokenResponse = serde_json::from_str(&body)
+            .map_err(|e| provider_error(format!("Failed to parse JSON while getting access token: {e}; got {}", describe_token_response(&body))))?;
+        match (token.access_token, token.error) {
+            (Some(access_token), None) => Ok(ProviderTokens { access_token, refresh_token: token.refresh_token }),
+            (_, Some(error)) if error == "bad_verification_code" => Err(ApiError::InvalidAuthorizationCode(self.display_name().to_string())),
+            (_, error) => Err(provider_error(format!("Error while getting access token: {error:?} - {:?}", token.error_description))),
+        }
+    }
+
+    async fn fetch_identity(&self, access_token: &str) -> Result<ExternalIdentity, ApiError> {
+        let user: GitHubUser = get_with_access_token(self, &format!("{}/user", self.config.api_base_url), access_token).await?;
+        Ok(ExternalIdentity {
+            subject: user.id.to_string(),
+            username: user.login,
+            display_name: user.name,
+            avatar_url: user.avatar_url,
+        })
+    }
+}
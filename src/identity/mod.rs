@@ -0,0 +1,179 @@
+mod discord;
+mod github;
+mod oidc;
+
+use std::sync::LazyLock;
+use std::time::Duration;
+use reqwest::{Client, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use crate::app_config;
+use crate::api_error::ApiError;
+use crate::identity::discord::DiscordProvider;
+use crate::identity::github::GitHubProvider;
+use crate::identity::oidc::OidcProvider;
+
+
+/// A user at an external login provider, as the provider told us.
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    /// the provider's stable user id; usernames at providers can change
+    pub subject: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+pub struct ProviderTokens {
+    pub access_token: String,
+    /// not every provider hands out refresh tokens
+    pub refresh_token: Option<String>,
+}
+
+/// An OAuth2 login provider AcornGM accounts can be linked to.
+#[rocket::async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// used in urls and stored in `identities.provider`; must never change for a configured provider
+    fn name(&self) -> &str;
+
+    /// shown to users
+    fn display_name(&self) -> &str;
+
+    /// where users get sent to in order to authorize AcornGM
+    fn authorize_url(&self) -> Result<Url, String>;
+
+    /// exchanges the code the provider redirected with for tokens of the user
+    async fn exchange_code(&self, code: &str) -> Result<ProviderTokens, ApiError>;
+
+    /// looks up who the access token belongs to
+    async fn fetch_identity(&self, access_token: &str) -> Result<ExternalIdentity, ApiError>;
+}
+
+
+static PROVIDERS: LazyLock<Vec<Box<dyn IdentityProvider>>> = LazyLock::new(|| {
+    let config = app_config();
+    let mut providers: Vec<Box<dyn IdentityProvider>> = vec![Box::new(DiscordProvider { config: &config.discord })];
+    if let Some(github) = &config.github {
+        providers.push(Box::new(GitHubProvider { config: github }));
+    }
+    for (name, oidc) in &config.oidc {
+        providers.push(Box::new(OidcProvider { name, config: oidc }));
+    }
+    providers
+});
+
+pub fn get_provider(name: &str) -> Result<&'static dyn IdentityProvider, ApiError> {
+    PROVIDERS.iter()
+        .find(|provider| provider.name() == name)
+        .map(|provider| provider.as_ref())
+        .ok_or_else(|| ApiError::UnknownIdentityProvider(name.to_string()))
+}
+
+pub fn providers() -> impl Iterator<Item = &'static dyn IdentityProvider> {
+    PROVIDERS.iter().map(|provider| provider.as_ref())
+}
+
+
+/// A provider that does not answer fails the login with `identity_provider_unavailable` instead of holding it open.
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+const PROVIDER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// github's user api refuses requests without user agent
+static HTTP_CLIENT: LazyLock<Client> = LazyLock::new(|| Client::builder()
+    .user_agent(concat!("AcornGM/", env!("CARGO_PKG_VERSION")))
+    .timeout(PROVIDER_TIMEOUT)
+    .connect_timeout(PROVIDER_CONNECT_TIMEOUT)
+    .build()
+    .expect("Could not build http client"));
+
+fn http_client() -> &'static Client {
+    &HTTP_CLIENT
+}
+
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+}
+
+/// What a token endpoint responded with, for error messages: the body itself holds the tokens, so only its shape is told.
+fn describe_token_response(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(body) {
+        Ok(object) => format!("JSON object with keys {:?}", object.keys().collect::<Vec<_>>()),
+        Err(_) => format!("{} bytes that are not a JSON object", body.len()),
+    }
+}
+
+/// Standard OAuth2 authorization code exchange with the client credentials in basic auth.
+async fn exchange_code_basic_auth(
+    provider: &dyn IdentityProvider,
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    redirect_uri: &str,
+    code: &str,
+) -> Result<ProviderTokens, ApiError> {
+    let provider_error = |detail: String| ApiError::IdentityProvider(provider.display_name().to_string(), detail);
+
+    let res = http_client()
+        .post(token_url)
+        .basic_auth(client_id, Some(client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+        ])
+        .send()
+        .await
+        .map_err(|e| provider_error(format!("Request for access token failed: {e}")))?;
+
+    let status = res.status();
+    let body: String = res.text().await
+        .map_err(|e| provider_error(format!("Could not get text from response body while getting access token: {e}")))?;
+
+    if !status.is_success() {
+        // check if code is invalid; because if it is, the error is the client's fault
+        if serde_json::from_str::<TokenErrorResponse>(&body).is_ok_and(|error| error.error == "invalid_grant") {
+            return Err(ApiError::InvalidAuthorizationCode(provider.display_name().to_string()))
+        }
+        return Err(provider_error(format!("Error while getting access token - {status}: {}", describe_token_response(&body))))
+    }
+
+    serde_json::from_str::<TokenResponse>(&body)
+        .map(|token| ProviderTokens { access_token: token.access_token, refresh_token: token.refresh_token })
+        .map_err(|e| provider_error(format!("Failed to parse JSON while getting access token: {e}; got {}", describe_token_response(&body))))
+}
+
+/// GET request authorized with the user's access token; 401 means the token is invalid.
+async fn get_with_access_token<T: DeserializeOwned>(provider: &dyn IdentityProvider, url: &str, access_token: &str) -> Result<T, ApiError> {
+    let provider_error = |detail: String| ApiError::IdentityProvider(provider.display_name().to_string(), detail);
+    let token_prefix: &str = access_token.get(0..6).unwrap_or(access_token);
+
+    let res = http_client()
+        .get(url)
+        .bearer_auth(access_token)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .map_err(|e| provider_error(format!("Could not request {url} for access token \"{token_prefix}...\": {e}")))?;
+
+    let status = res.status();
+    let body: String = res.text().await
+        .map_err(|e| provider_error(format!("Could not get text from response body of {url} for access token \"{token_prefix}...\": {e}")))?;
+
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(ApiError::InvalidProviderToken(provider.display_name().to_string()))
+    }
+    if !status.is_success() {
+        return Err(provider_error(format!("Error while requesting {url}: {status} - {body}")))
+    }
+
+    serde_json::from_str::<T>(&body)
+        .map_err(|e| provider_error(format!("Failed to parse JSON from {url}: {e}\nRaw response text: {body}")))
+}
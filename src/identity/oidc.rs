@@ -0,0 +1,68 @@
+use reqwest::Url;
+use serde::Deserialize;
+use crate::app_config;
+use crate::api_error::ApiError;
+use crate::config::OidcConfig;
+use crate::identity::{exchange_code_basic_auth, get_with_access_token, ExternalIdentity, IdentityProvider, ProviderTokens};
+
+
+/// Any OpenID Connect provider; the endpoints are configured explicitly instead of discovered.
+pub struct OidcProvider {
+    pub name: &'static str,
+    pub config: &'static OidcConfig,
+}
+
+impl OidcProvider {
+    fn redirect_uri(&self) -> String {
+        self.config.redirect_uri.clone().unwrap_or_else(|| app_config().default_redirect_uri(self.name))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UserInfo {
+    sub: String,
+    preferred_username: Option<String>,
+    name: Option<String>,
+    picture: Option<String>,
+}
+
+#[rocket::async_trait]
+impl IdentityProvider for OidcProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn display_name(&self) -> &str {
+        &self.config.display_name
+    }
+
+    fn authorize_url(&self) -> Result<Url, String> {
+        Url::parse_with_params(&self.config.authorize_url, &[
+            ("client_id", self.config.client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", &self.redirect_uri()),
+            ("scope", &self.config.scope),
+        ]).map_err(|e| format!("Invalid authorize url \"{}\" of oidc provider {}: {e}", self.config.authorize_url, self.name))
+    }
+
+    async fn exchange_code(&self, code: &str) -> Result<ProviderTokens, ApiError> {
+        exchange_code_basic_auth(
+            self,
+            &self.config.token_url,
+            &self.config.client_id,
+            &self.config.client_secret,
+            &self.redirect_uri(),
+            code,
+        ).await
+    }
+
+    async fn fetch_identity(&self, access_token: &str) -> Result<ExternalIdentity, ApiError> {
+        let user_info: UserInfo = get_with_access_token(self, &self.config.userinfo_url, access_token).await?;
+        Ok(ExternalIdentity {
+            username: user_info.preferred_username.clone().unwrap_or_else(|| user_info.sub.clone()),
+            subject: user_info.sub,
+            display_name: user_info.name,
+            avatar_url: user_info.picture,
+        })
+    }
+}
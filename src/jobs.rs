@@ -0,0 +1,230 @@
+//! Recurring housekeeping jobs, run inside the server by every instance with `jobs.enabled`.
+//!
+//! Instances take turns through the `scheduled_jobs` table: whoever claims a due job runs it,
+//! the others skip it until it is due again. Mod files are stored in the mod rows themselves,
+//! so deleting a mod frees its file right away and there are no orphaned blobs to collect.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::task::JoinHandle;
+use rocket::{Orbit, Rocket, Shutdown};
+use crate::{app_config, pool};
+use crate::api_error::ApiError;
+use crate::accounts::{delete_access_tokens_older_than, delete_expired_identity_moves, delete_expired_temp_login_tokens};
+use crate::mods::{purge_removed_mods, roll_up_download_counts};
+
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<u64, ApiError>> + Send>>;
+
+/// A recurring job; `run` returns how many rows it touched.
+pub struct Job {
+    pub name: &'static str,
+    pub interval: Duration,
+    run: fn() -> JobFuture,
+    pub metrics: JobMetrics,
+}
+
+/// Counted per process since startup.
+pub struct JobMetrics {
+    pub runs: AtomicU64,
+    pub failures: AtomicU64,
+    /// the job was not due yet or another instance had it
+    pub skipped: AtomicU64,
+    pub last_duration_ms: AtomicU64,
+    pub last_affected_rows: AtomicU64,
+}
+
+impl JobMetrics {
+    const fn new() -> Self {
+        JobMetrics {
+            runs: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            last_duration_ms: AtomicU64::new(0),
+            last_affected_rows: AtomicU64::new(0),
+        }
+    }
+}
+
+/// an instance that crashed mid-job blocks the job for at most this long
+const LOCK_LEASE: Duration = Duration::from_secs(15 * 60);
+
+static JOBS: [Job; 4] = [
+    Job {
+        name: "expired_login_codes",
+        interval: Duration::from_secs(10 * 60),
+        run: || Box::pin(delete_expired_login_codes()),
+        metrics: JobMetrics::new(),
+    },
+    Job {
+        name: "expired_access_tokens",
+        interval: Duration::from_secs(60 * 60),
+        run: || Box::pin(delete_expired_access_tokens()),
+        metrics: JobMetrics::new(),
+    },
+    Job {
+        name: "removed_mod_purge",
+        interval: Duration::from_secs(6 * 60 * 60),
+        run: || Box::pin(purge_old_removed_mods()),
+        metrics: JobMetrics::new(),
+    },
+    Job {
+        name: "download_count_rollup",
+        interval: Duration::from_secs(5 * 60),
+        run: || Box::pin(roll_up_download_counts()),
+        metrics: JobMetrics::new(),
+    },
+];
+
+pub fn jobs() -> &'static [Job] {
+    &JOBS
+}
+
+pub fn find_job(name: &str) -> Option<&'static Job> {
+    JOBS.iter().find(|job| job.name == name)
+}
+
+
+async fn delete_expired_login_codes() -> Result<u64, ApiError> {
+    Ok(delete_expired_temp_login_tokens().await? + delete_expired_identity_moves().await?)
+}
+
+async fn delete_expired_access_tokens() -> Result<u64, ApiError> {
+    match app_config().jobs.access_token_max_age_days {
+        Some(days) => delete_access_tokens_older_than(days).await,
+        None => Ok(0),
+    }
+}
+
+async fn purge_old_removed_mods() -> Result<u64, ApiError> {
+    match app_config().jobs.purge_removed_mods_after_days {
+        Some(days) => purge_removed_mods(days).await,
+        None => Ok(0),
+    }
+}
+
+
+/// Runs the job unless it ran less than `min_gap` ago or is running somewhere else right now.
+/// Returns the number of affected rows, or `None` if the job was skipped.
+pub async fn run_job(job: &Job, min_gap: Duration) -> Result<Option<u64>, ApiError> {
+    if !claim_job(job.name, min_gap).await? {
+        job.metrics.skipped.fetch_add(1, Ordering::Relaxed);
+        return Ok(None)
+    }
+
+    let started = Instant::now();
+    let result: Result<u64, ApiError> = (job.run)().await;
+    job.metrics.runs.fetch_add(1, Ordering::Relaxed);
+    job.metrics.last_duration_ms.store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+    let error: Option<String> = result.as_ref().err().map(ApiError::describe);
+    release_job(job.name, error.as_deref()).await?;
+    match result {
+        Ok(affected_rows) => {
+            job.metrics.last_affected_rows.store(affected_rows, Ordering::Relaxed);
+            Ok(Some(affected_rows))
+        }
+        Err(e) => {
+            job.metrics.failures.fetch_add(1, Ordering::Relaxed);
+            Err(e)
+        }
+    }
+}
+
+async fn claim_job(name: &str, min_gap: Duration) -> Result<bool, ApiError> {
+    let claimed: Option<String> = sqlx::query_scalar!(
+        r#"
+        INSERT INTO scheduled_jobs (name, locked_until, last_started_at)
+        VALUES ($1, NOW() + $3 * INTERVAL '1 second', NOW())
+        ON CONFLICT (name) DO UPDATE
+        SET locked_until = EXCLUDED.locked_until, last_started_at = EXCLUDED.last_started_at
+        WHERE scheduled_jobs.locked_until < NOW() AND scheduled_jobs.last_started_at <= NOW() - $2 * INTERVAL '1 second'
+        RETURNING name
+        "#,
+        name,
+        min_gap.as_secs_f64(),
+        LOCK_LEASE.as_secs_f64(),
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not claim job {name}: {e}")))?;
+    Ok(claimed.is_some())
+}
+
+async fn release_job(name: &str, error: Option<&str>) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_jobs
+        SET locked_until = NOW(), last_finished_at = NOW(), last_error = $2
+        WHERE name = $1
+        "#,
+        name,
+        error,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not release job {name}: {e}")))?;
+    Ok(())
+}
+
+
+async fn run_on_schedule(job: &'static Job, shutdown: Shutdown) {
+    // a little slack, so the instance that ran the job last time does not miss its own next turn
+    let min_gap: Duration = job.interval.mul_f64(0.9);
+    loop {
+        match run_job(job, min_gap).await {
+            Ok(Some(affected_rows)) => info!("Job {} finished in {}ms; {affected_rows} rows affected",
+                job.name, job.metrics.last_duration_ms.load(Ordering::Relaxed)),
+            Ok(None) => debug!("Job {} is not due or running on another instance", job.name),
+            Err(e) => error!("Job {} failed: {}", job.name, e.describe()),
+        }
+
+        rocket::tokio::select! {
+            _ = rocket::tokio::time::sleep(job.interval) => {}
+            _ = shutdown.clone() => break,
+        }
+    }
+}
+
+
+/// Starts the jobs on liftoff; on shutdown, running jobs get to finish.
+pub struct Scheduler {
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+pub fn fairing() -> Scheduler {
+    Scheduler { tasks: Mutex::new(Vec::new()) }
+}
+
+#[rocket::async_trait]
+impl Fairing for Scheduler {
+    fn info(&self) -> Info {
+        Info { name: "Job scheduler", kind: Kind::Liftoff | Kind::Shutdown }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        if !app_config().jobs.enabled {
+            info!("Housekeeping jobs are disabled on this instance");
+            return
+        }
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        for job in jobs() {
+            tasks.push(rocket::tokio::spawn(run_on_schedule(job, rocket.shutdown())));
+        }
+        info!("Started {} housekeeping jobs", tasks.len());
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        let tasks: Vec<JoinHandle<()>> = std::mem::take(&mut *self.tasks.lock().unwrap_or_else(|e| e.into_inner()));
+        for task in tasks {
+            if let Err(e) = task.await {
+                error!("Housekeeping job task panicked: {e}");
+            }
+        }
+        info!("Housekeeping jobs stopped");
+    }
+}
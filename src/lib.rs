@@ -0,0 +1,168 @@
+mod accounts;
+mod login;
+mod mods;
+mod search_mods;
+mod tags;
+mod screenshots;
+pub mod sanitize;
+pub mod markdown;
+mod catchers;
+mod review;
+mod rate_limit;
+mod jobs;
+mod health;
+pub mod metrics;
+pub mod logging;
+pub mod config;
+pub mod migrations;
+mod api_error;
+mod identity;
+pub mod openapi;
+pub mod admin;
+
+#[macro_use]
+extern crate rocket;
+
+use once_cell::sync::OnceCell;
+use crate::login::api_confirm_identity_move;
+use crate::login::api_get_access_token;
+use crate::login::api_get_account;
+use crate::login::api_get_auth_providers;
+use crate::login::api_get_discord_auth;
+use crate::login::api_get_identities;
+use crate::login::api_get_provider_auth;
+use crate::login::api_link_identity;
+use crate::login::api_post_register;
+use crate::login::api_post_temp_login;
+use crate::login::api_start_identity_move;
+use crate::login::api_unlink_identity;
+use crate::login::redirect_goto_auth;
+use crate::login::redirect_goto_discord_auth;
+use crate::login::redirect_goto_move;
+use log::info;
+use rocket::fs::FileServer;
+use rocket::response::Redirect;
+use rocket::{Build, Rocket};
+use rocket::figment::Figment;
+use sqlx::{Pool, Postgres};
+use sqlx::postgres::PgPoolOptions;
+use rocket::serde::json::Json;
+use rocket_dyn_templates::Template;
+use serde_json::{json, Value};
+use crate::catchers::{api_catch_404, api_catch_422, api_catch_429, html_catch_404};
+use crate::mods::{api_delete_mod, api_download_mod, api_get_mod, api_get_own_mods, api_list_mods, api_rate_mod, api_search_mods, api_suggest_mods, api_update_mod, api_upload_mod};
+use crate::openapi::{api_get_openapi, redirect_api_docs};
+use crate::health::{healthz, readyz};
+use crate::logging::with_request_ids;
+use crate::review::{api_approve_mod, api_download_pending_mod, api_get_pending_mods, api_reject_mod};
+use crate::screenshots::{api_arrange_screenshots, api_delete_screenshot, api_get_screenshot, api_get_screenshot_thumbnail, api_list_screenshots, api_upload_screenshot};
+use crate::tags::{api_list_mods_in_category, api_list_mods_with_tag, api_list_tags, api_remove_canonical_tag, api_set_canonical_tag};
+use crate::config::AppConfig;
+use crate::api_error::ApiError;
+
+#[get("/")]
+fn html_index() -> Redirect {
+    Redirect::to("index.html")
+}
+#[get("/eula")]
+fn html_eula() -> Redirect {
+    Redirect::to("eula.html")
+}
+
+type ApiResponse = Result<Option<Json<Value>>, ApiError>;
+fn respond_ok_value(json_response: Value) -> ApiResponse {
+    Ok(Some(Json(json!(json_response))))
+}
+/// `Ok(None)` would make rocket respond with 404, so "empty" is an empty object
+fn respond_ok_empty() -> ApiResponse {
+    Ok(Some(Json(json!({}))))
+}
+
+fn pool<'a>() -> &'a Pool<Postgres> {
+    POOL.get().expect("Database pool not initialized")
+}
+
+pub fn app_config<'a>() -> &'a AppConfig {
+    APP_CONFIG.get().expect("App config not initialized")
+}
+
+static POOL: OnceCell<Pool<Postgres>> = OnceCell::new();
+static APP_CONFIG: OnceCell<AppConfig> = OnceCell::new();
+
+/// Loads and validates the configuration and connects to the database.
+/// Has to be called exactly once per process, before [`build_rocket`].
+pub async fn init(figment: &Figment) -> Result<(), String> {
+    let config: AppConfig = figment.extract::<AppConfig>()
+        .map_err(|e| format!("Invalid configuration: {e}"))?;
+    config.validate().map_err(|e| format!("Invalid configuration: {e}"))?;
+    info!("Pre-publication review policy: {:?}", config.review);
+    info!("Rate limiting backend: {:?}", config.rate_limit.backend);
+    APP_CONFIG.set(config).map_err(|_| "App config was already initialized".to_string())?;
+
+    let pool = PgPoolOptions::new()
+        .max_connections(app_config().database_pool_size)
+        .connect(&app_config().database_url)
+        .await
+        .map_err(|e| format!("Could not initialize database: {e}"))?;
+    POOL.set(pool).map_err(|_| "Database pool was already initialized".to_string())?;
+    Ok(())
+}
+
+
+pub fn build_rocket(figment: Figment) -> Rocket<Build> {
+    rocket::custom(figment)
+        .attach(logging::fairing())
+        .attach(Template::fairing())
+        .attach(rate_limit::fairing())
+        .attach(jobs::fairing())
+        .attach(metrics::fairing())
+        .register("/api/v1", catchers![api_catch_404, api_catch_422, api_catch_429])
+        .register("/", catchers![html_catch_404])
+        .mount("/", with_request_ids(routes![html_index, html_eula, redirect_goto_auth, redirect_goto_discord_auth, redirect_goto_move]))
+        .mount("/", with_request_ids(routes![healthz, readyz]))
+        .mount(
+            "/api/v1",
+            with_request_ids(routes![
+                api_get_auth_providers,
+                api_get_provider_auth,
+                api_get_discord_auth,
+                api_post_register,
+                api_post_temp_login,
+                api_get_access_token,
+                api_get_identities,
+                api_link_identity,
+                api_unlink_identity,
+                api_start_identity_move,
+                api_confirm_identity_move,
+                api_get_account,
+                api_upload_mod,
+                api_update_mod,
+                api_delete_mod,
+                api_list_mods,
+                api_search_mods,
+                api_suggest_mods,
+                api_get_mod,
+                api_download_mod,
+                api_get_own_mods,
+                api_rate_mod,
+                api_list_tags,
+                api_list_mods_with_tag,
+                api_list_mods_in_category,
+                api_set_canonical_tag,
+                api_remove_canonical_tag,
+                api_upload_screenshot,
+                api_list_screenshots,
+                api_get_screenshot,
+                api_get_screenshot_thumbnail,
+                api_arrange_screenshots,
+                api_delete_screenshot,
+                api_get_pending_mods,
+                api_download_pending_mod,
+                api_approve_mod,
+                api_reject_mod,
+                api_get_openapi,
+                redirect_api_docs,
+            ]),
+        )
+        .mount("/", FileServer::from(&app_config().frontend_dir))
+}
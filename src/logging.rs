@@ -0,0 +1,258 @@
+//! Logging. Every request gets an ID (taken from an `X-Request-Id` header set by a proxy, or generated),
+//! which is sent back in `X-Request-Id` and tagged onto every log line written while handling the request.
+//! Lines are written to stdout as text or as JSON objects, one per line (see `log_format` in the config).
+//! Tokens, codes and other secrets in a line are cut down by the logger itself (see [`redact_secrets`]).
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Write;
+use std::sync::LazyLock;
+use std::time::Instant;
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, Key, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use regex::{Captures, Regex};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::route::{self, Handler, Route};
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+rocket::tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Names of values that are secrets, in `snake_case`, `camelCase` or prose. Not a bare `code`, which is mostly
+/// a status or error code; authorization codes are logged as `oauth code`.
+const SECRET_NAMES: &str = r"(?:(?:provider|discord)[_ ]?)?(?:access|refresh|id|temp[_ ]?login)[_ ]?token|client[_ ]?secret|(?:move|discord|oauth)[_ ]?code|password";
+
+/// A secret name followed by its value, as in `code=...` in a url, `"access_token":"..."` in a response body
+/// (also escaped inside a JSON string) or `temp login token "..."` in a message; or a bearer token.
+static SECRET: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(
+    r#"(?i)\b({SECRET_NAMES})(\\?"?\s*[:=]\s*\\?"?|\s+\\?")([^\s"\\&,;}}]+)|\b(Bearer\s+)([^\s"\\,;}}]+)"#
+)).expect("Could not load secret pattern"));
+
+static SECRET_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!("(?i)^(?:{SECRET_NAMES})$"))
+    .expect("Could not load secret name pattern"));
+
+
+/// Logs of this crate from `info` up, of dependencies from `warn` up.
+pub struct Logger {
+    format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(format: LogFormat) -> Self {
+        Logger { format }
+    }
+
+    /// One line, without the trailing newline.
+    pub fn format(&self, record: &Record) -> String {
+        let request_id: Option<String> = REQUEST_ID.try_with(String::clone).ok();
+        let mut fields = Fields(Vec::new());
+        // only fails if the visitor does, which ours never does
+        let _ = record.key_values().visit(&mut fields);
+        let timestamp: String = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        match self.format {
+            LogFormat::Text => {
+                let mut line: String = format!("{timestamp} {:<5} {}", record.level(), record.target());
+                if let Some(request_id) = &request_id {
+                    line += &format!(" [{request_id}]");
+                }
+                line += &format!(" {}", redact_secrets(&record.args().to_string()));
+                for (key, value) in &fields.0 {
+                    line += &format!(" {key}={value}");
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut object = Map::new();
+                object.insert("timestamp".to_string(), Value::from(timestamp));
+                object.insert("level".to_string(), Value::from(record.level().as_str()));
+                object.insert("target".to_string(), Value::from(record.target()));
+                if let Some(request_id) = request_id {
+                    object.insert("request_id".to_string(), Value::from(request_id));
+                }
+                object.insert("message".to_string(), Value::from(redact_secrets(&record.args().to_string())));
+                for (key, value) in fields.0 {
+                    object.insert(key, value);
+                }
+                Value::Object(object).to_string()
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if metadata.target().starts_with("acorngm") {
+            metadata.level() <= Level::Info
+        } else {
+            metadata.level() <= Level::Warn
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return
+        }
+        let line: String = self.format(record);
+        // there is nowhere left to report a failing stdout to
+        let _ = writeln!(std::io::stdout().lock(), "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// The structured fields of a record (`info!(status = 200; "...")`).
+struct Fields(Vec<(String, Value)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value: Value = if let Some(number) = value.to_u64() {
+            Value::from(number)
+        } else if let Some(number) = value.to_i64() {
+            Value::from(number)
+        } else if let Some(number) = value.to_f64() {
+            Value::from(number)
+        } else if let Some(boolean) = value.to_bool() {
+            Value::from(boolean)
+        } else if SECRET_NAME.is_match(key.as_str()) {
+            Value::from(Redacted(&value.to_string()).to_string())
+        } else {
+            Value::from(redact_secrets(&value.to_string()))
+        };
+        self.0.push((key.as_str().to_string(), value));
+        Ok(())
+    }
+}
+
+/// Cuts every secret in a log line down like [`Redacted`], so log lines can not leak them
+/// whether or not whoever wrote the line thought of it.
+pub fn redact_secrets(text: &str) -> Cow<'_, str> {
+    SECRET.replace_all(text, |caps: &Captures| match caps.get(3) {
+        // already cut down by whoever wrote the line
+        Some(value) if value.as_str().ends_with('…') => caps[0].to_string(),
+        Some(value) => format!("{}{}{}", &caps[1], &caps[2], Redacted(value.as_str())),
+        None => format!("{}{}", &caps[4], Redacted(&caps[5])),
+    })
+}
+
+/// Installs the [`Logger`]; does nothing if there already is one.
+pub fn init(format: LogFormat) {
+    let logger: &'static Logger = Box::leak(Box::new(Logger::new(format)));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+
+/// Shows only the first few characters of a secret (tokens, codes), enough to tell log lines apart.
+pub struct Redacted<'a>(pub &'a str);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.get(0..4) {
+            Some(prefix) if self.0.len() > 8 => write!(f, "{prefix}…"),
+            _ => write!(f, "…"),
+        }
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+
+struct RequestContext {
+    id: String,
+    started: Instant,
+}
+
+/// IDs from proxies are kept if they are short and harmless in a log line.
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=64).contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn request_context<'r>(req: &'r Request<'_>) -> &'r RequestContext {
+    req.local_cache(|| {
+        let id: String = req.headers().get_one(REQUEST_ID_HEADER)
+            .filter(|id| is_valid_request_id(id))
+            .map_or_else(|| Uuid::new_v4().simple().to_string(), str::to_string);
+        RequestContext { id, started: Instant::now() }
+    })
+}
+
+
+/// Runs a route's handler (including its guards) with the request ID in scope of the logger.
+#[derive(Clone)]
+struct WithRequestId(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for WithRequestId {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let id: String = request_context(req).id.clone();
+        REQUEST_ID.scope(id, self.0.handle(req, data)).await
+    }
+}
+
+/// Wrap routes with this when mounting them, so their log lines carry the request ID.
+pub fn with_request_ids(routes: Vec<Route>) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(WithRequestId(route.handler));
+            route
+        })
+        .collect()
+}
+
+
+/// Assigns request IDs and writes an access log line per request.
+pub struct RequestLogger;
+
+pub fn fairing() -> RequestLogger {
+    RequestLogger
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info { name: "Request logger", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        request_context(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let context: &RequestContext = request_context(req);
+        res.set_header(Header::new(REQUEST_ID_HEADER, context.id.clone()));
+
+        // the path only; query strings can contain login codes
+        let method: &str = req.method().as_str();
+        let path: &str = req.uri().path().as_str();
+        let status: u16 = res.status().code;
+        let duration_ms: f64 = context.started.elapsed().as_secs_f64() * 1000.0;
+        REQUEST_ID.sync_scope(context.id.clone(), || {
+            log::info!(target: "acorngm_backend::access", method, path, status, duration_ms; "{method} {path} {status} {duration_ms:.1}ms");
+        });
+    }
+}
@@ -1,228 +1,254 @@
-use std::collections::HashMap;
-use std::sync::LazyLock;
 use base64::Engine;
 use chrono::Utc;
 use rand::TryRngCore;
-use serde::Deserialize;
-use reqwest::Client;
-use rocket::http::Status;
 use crate::accounts::{
     check_if_account_exists,
-    check_if_account_exists_discord,
-    get_account_by_discord_id,
+    complete_identity_move,
+    ensure_account_authentication,
+    ensure_account_not_banned,
+    find_confusable_account,
+    get_account_by_identity,
+    get_account_profile,
+    get_identities,
     insert_access_token,
     insert_account,
+    insert_identity_move,
     insert_temp_login_token,
+    link_identity,
+    record_identity_login,
     temp_login_token_get_username,
+    unlink_identity,
+    validate_username,
     AcornAccessToken,
     AcornAccount,
+    IdentityMove,
+};
+use acorngm_api_client::auth::{
+    AccessTokenResponse,
+    AccountProfile,
+    AccountRequest,
+    AuthProvider,
+    AuthProviderList,
+    ConfirmIdentityMoveRequest,
+    DiscordAuthResponse,
+    IdentityMoveStarted,
+    IdentityMoved,
+    LinkIdentityRequest,
+    LinkedIdentity,
+    LinkedIdentityList,
+    ProviderAuthResponse,
+    RegisterRequest,
+    TempLoginRequest,
+    UnlinkIdentityRequest,
 };
+use rocket::http::Status;
 use rocket::serde::json::Json;
 use serde_json::json;
-use regex::Regex;
 use rocket::response::content::RawHtml;
-use crate::{respond_err, respond_ok_empty, respond_ok_value, ApiResponse};
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct TokenResponse {
-    access_token: String,
-    token_type: String,
-    expires_in: i64,
-    refresh_token: String,
-    scope: String,
+use crate::{respond_ok_empty, respond_ok_value, ApiResponse};
+use crate::api_error::ApiError;
+use acorngm_api_client::error::ErrorResponse;
+use crate::identity::{get_provider, providers, ExternalIdentity, IdentityProvider, ProviderTokens};
+use crate::logging::Redacted;
+use crate::metrics::observe_provider_call;
+use crate::rate_limit::{AuthRoutes, RateLimit, ReadRoutes};
+
+/// What the provider told us about the user behind an authorization code, and whether they already have an account.
+struct ProviderAuth {
+    provider_access_token: String,
+    identity: ExternalIdentity,
+    account: Option<AcornAccount>,
 }
 
-#[derive(Debug, Deserialize)]
-struct DiscordUserInfo {
-    pub id: String,
-    pub username: String,
-    pub global_name: String,
+/// For existing accounts, this also counts as a login of the identity: the account gets the current display name and avatar.
+async fn authenticate(provider: &dyn IdentityProvider, code: &str) -> Result<ProviderAuth, ApiError> {
+    // Get access token from OAuth2 code
+    let tokens: ProviderTokens = observe_provider_call(provider.name(), "exchange_code", provider.exchange_code(code)).await?;
+    info!("Exchanged oauth code {:?} with {}; getting user info", Redacted(code), provider.name());
+
+    // Get provider user ID
+    let identity: ExternalIdentity = observe_provider_call(provider.name(), "fetch_identity", provider.fetch_identity(&tokens.access_token)).await?;
+    info!("Got {} user info for oauth code {:?}; user id: {}, username: \"{}\", displayname: {:?}",
+        provider.name(), Redacted(code), identity.subject, identity.username, identity.display_name);
+
+    // check if account already exists
+    let account: Option<AcornAccount> = get_account_by_identity(provider.name(), &identity.subject).await?;
+    if let Some(account) = &account {
+        record_identity_login(&account.username, provider.name(), &identity, tokens.refresh_token.as_deref()).await?;
+    }
+    Ok(ProviderAuth { provider_access_token: tokens.access_token, identity, account })
 }
 
+/// fetches the identity behind the access token and makes sure it is the one the client claims
+async fn verify_identity(provider: &dyn IdentityProvider, provider_user_id: &str, provider_access_token: &str) -> Result<ExternalIdentity, ApiError> {
+    info!("Getting {} user info for user id {provider_user_id}", provider.name());
+    let identity: ExternalIdentity = observe_provider_call(provider.name(), "fetch_identity", provider.fetch_identity(provider_access_token)).await?;
+    if identity.subject != provider_user_id {
+        return Err(ApiError::IdentityMismatch(provider.display_name().to_string()))
+    }
+    Ok(identity)
+}
 
 
-const DISCORD_API_BASE_URL: &'static str = "https://discord.com/api/v10";
-const REDIRECT_URI: &'static str = "https://acorngm.biotomatede.hackclub.app/discord_auth_page.html";
-const DISCORD_APP_CLIENT_ID: &'static str = "1360325253766578479";
-const DISCORD_CLIENT_SECRET: LazyLock<String> = LazyLock::new(
-    || std::env::var("DISCORD_CLIENT_SECRET")
-        .expect("DISCORD_CLIENT_SECRET environment variable not set")
-);
-
-async fn exchange_code(discord_code: &str) -> Result<TokenResponse, (Status, String)> {
-    let mut params = HashMap::new();
-    params.insert("grant_type", "authorization_code");
-    params.insert("code", &discord_code);
-    params.insert("redirect_uri", REDIRECT_URI);
-
-    let res = Client::new()
-        .post(format!("{}/oauth2/token", DISCORD_API_BASE_URL))
-        .basic_auth(DISCORD_APP_CLIENT_ID, Some(&*DISCORD_CLIENT_SECRET))
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| (Status::InternalServerError, format!("Request failed: {e}")))?;
-
-    let status = res.status();
-    let body: String = res.text().await
-        .map_err(|e| {
-            error!("Could not get text from response body while getting discord access token: {e}",);
-            (Status::InternalServerError, format!("Could not get text from response body: {e}"))
-        })?;
-    
-    if !status.is_success() {
-        // check if code is invalid; because if it is, the error is the client's fault
-        if body.contains("Invalid \\\"code\\\" in request") {
-            return Err((Status::Unauthorized, "Could not get discord token because the provided code is invalid".to_string()));
-        }
-        if body.contains("invalid_grant") {
-            return Err((Status::Unauthorized, "Could not get discord token because the provided discord refresh token is invalid or already used".to_string()));
-        }
-        error!("Error while getting access token from discord - {}: {}", status, body);
-        return Err((Status::InternalServerError, format!("Could not get discord token because discord returned a failure response: {}", status)));
+/// The configured login providers.
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, description = "Login providers", body = AuthProviderList)),
+)]
+#[get("/auth/providers")]
+pub async fn api_get_auth_providers() -> ApiResponse {
+    let providers: Vec<AuthProvider> = providers()
+        .map(|provider| AuthProvider {
+            name: provider.name().to_string(),
+            display_name: provider.display_name().to_string(),
+        })
+        .collect();
+    respond_ok_value(json!(AuthProviderList { providers }))
+}
+
+
+/// Finishes logging in with the code the login provider redirected with.
+///
+/// Identities without account get `register: true` and the provider access token to register or link them with.
+#[utoipa::path(
+    tag = "auth",
+    params(("code" = String, Query, description = "OAuth2 authorization code from the provider's redirect")),
+    responses(
+        (status = 200, description = "Who logged in", body = ProviderAuthResponse),
+        (status = 401, description = "`invalid_authorization_code`", body = ErrorResponse),
+        (status = 404, description = "`unknown_identity_provider`", body = ErrorResponse),
+        (status = 502, description = "`identity_provider_unavailable`", body = ErrorResponse),
+    ),
+)]
+#[get("/auth/<provider>?<code>")]
+pub async fn api_get_provider_auth(_rate_limit: RateLimit<'_, AuthRoutes>, provider: &str, code: &str) -> ApiResponse {
+    info!("Handling `GET auth/{provider}` with oauth code {:?}", Redacted(code));
+    let provider: &dyn IdentityProvider = get_provider(provider)?;
+    let auth: ProviderAuth = authenticate(provider, code).await?;
+
+    if let Some(account) = auth.account {
+        info!("Got {} auth for existing user {}: user id: {}", provider.name(), account.username, auth.identity.subject);
+        return respond_ok_value(json!(ProviderAuthResponse {
+            register: false,
+            provider: provider.name().to_string(),
+            provider_user_id: auth.identity.subject,
+            username: Some(account.username),
+            provider_access_token: None,
+            provider_username: None,
+        }))
     }
 
-    serde_json::from_str::<TokenResponse>(&body).map_err(|e| (Status::InternalServerError, {
-        error!("Failed to parse JSON while getting discord token: {e}\nRaw response text: {body}");
-        format!("Failed to parse JSON while getting discord token: {e}")
+    // account does not exist; let client register (or link the identity to an existing account)
+    info!("Got {} auth for new user: user id: {}", provider.name(), auth.identity.subject);
+    respond_ok_value(json!(ProviderAuthResponse {
+        register: true,
+        provider: provider.name().to_string(),
+        provider_user_id: auth.identity.subject,
+        username: None,
+        provider_access_token: Some(auth.provider_access_token),
+        provider_username: Some(auth.identity.username),
     }))
 }
 
-async fn get_user_info(access_token: &str) -> Result<DiscordUserInfo, String> {
-    let client = Client::new();
-    let res = client
-        .get(format!("{}/users/@me", DISCORD_API_BASE_URL))
-        .bearer_auth(access_token)
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Could not request discord user info for discord access token \"{}...\": {e}", access_token.get(0..6).unwrap_or(access_token));
-            format!("Could not request discord user info: {e}")
-        })?;
-
-    let status = res.status();
-
-    let body: String = res.text().await
-        .map_err(|e| {
-            error!("Could not get text from response body while getting discord user info \
-                for discord access token \"{}...\": {e}", access_token.get(0..6).unwrap_or(access_token));
-            format!("Could not get text from response body: {e}")
-        })?;
-
-    if !status.is_success() {
-        error!("Error while getting discord user info: {} - {}", status, body);
-        return Err(format!("Could not get discord user info because discord returned a failure response: {status}"));
+
+/// Kept for the discord auth page and older clients; same as `auth/discord` with discord specific keys.
+#[utoipa::path(
+    tag = "auth",
+    params(("discord_code" = String, Query, description = "OAuth2 authorization code from discord's redirect")),
+    responses(
+        (status = 200, description = "Who logged in", body = DiscordAuthResponse),
+        (status = 401, description = "`invalid_authorization_code`", body = ErrorResponse),
+        (status = 502, description = "`identity_provider_unavailable`", body = ErrorResponse),
+    ),
+)]
+#[get("/discord_auth?<discord_code>")]
+pub async fn api_get_discord_auth(_rate_limit: RateLimit<'_, AuthRoutes>, discord_code: &str) -> ApiResponse {
+    info!("Handling `GET discord_auth` with oauth code {:?}", Redacted(discord_code));
+    let auth: ProviderAuth = authenticate(get_provider("discord")?, discord_code).await?;
+
+    if let Some(account) = auth.account {
+        return respond_ok_value(json!(DiscordAuthResponse {
+            register: false,
+            discord_user_id: auth.identity.subject,
+            username: Some(account.username),
+            discord_access_token: None,
+            discord_username: None,
+        }))
     }
 
-    serde_json::from_str::<DiscordUserInfo>(&body).map_err(|e| {
-        error!("Failed to parse JSON from discord user info response: {e}\nRaw response text: {body}");
-        format!("Failed to parse JSON from discord user info response: {e}")
-    })
+    respond_ok_value(json!(DiscordAuthResponse {
+        register: true,
+        discord_user_id: auth.identity.subject,
+        username: None,
+        discord_access_token: Some(auth.provider_access_token),
+        discord_username: Some(auth.identity.username),
+    }))
 }
 
 
-#[allow(private_interfaces)]
+/// Creates an account for an identity that `auth/{provider}` answered with `register: true`.
+#[utoipa::path(
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Registered; log in with `temp_login`"),
+        (status = 400, description = "`invalid_username`", body = ErrorResponse),
+        (status = 401, description = "`invalid_provider_token` or `identity_mismatch`", body = ErrorResponse),
+        (status = 404, description = "`unknown_identity_provider`", body = ErrorResponse),
+        (status = 409, description = "`account_already_exists`, `confusable_username` or `identity_already_linked`", body = ErrorResponse),
+    ),
+)]
 #[post("/register", data="<request_data>")]
-pub async fn api_post_register(request_data: Json<RegisterRequest>) -> ApiResponse {
-    info!("Handling `POST register` with username \"{}\" and discord user id {}", request_data.username, request_data.discord_user_id);
-    static USERNAME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9_-]{3,32}$")
-        .expect("Could not load username verification pattern"));
-
-    if !USERNAME_REGEX.is_match(&request_data.username) {
-        return Err(respond_err(Status::BadRequest, &("Invalid username! Username must be 3-32 characters long \
-        and contain only latin letters, digits, underscores, and hyphens; without spaces.")))
+pub async fn api_post_register(rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<RegisterRequest>) -> ApiResponse {
+    info!("Handling `POST register` with username \"{}\" and {} user id {}", request_data.username, request_data.provider, request_data.provider_user_id);
+    validate_username(&request_data.username)?;
+
+    // validate access token and provider user id
+    let provider: &dyn IdentityProvider = get_provider(&request_data.provider)?;
+    let identity: ExternalIdentity = verify_identity(provider, &request_data.provider_user_id, &request_data.provider_access_token).await?;
+    // only now, or anyone could use up the budget of someone else's username
+    rate_limit.check_account(&request_data.username).await?;
+
+    // check if there is already an AcornGM account connected to this identity or with this username
+    if check_if_account_exists(&request_data.username).await? {
+        return Err(ApiError::AccountAlreadyExists)
     }
-
-    // validate access token and discord user id
-    info!("Getting discord user info for discord user id {}", request_data.discord_user_id);
-    let user_info: DiscordUserInfo = get_user_info(&request_data.discord_access_token).await
-        .map_err(|e| respond_err(Status::Unauthorized, &e))?;
-
-    if user_info.id != request_data.discord_user_id {
-        return Err(respond_err(Status::Unauthorized, "The provided discord user ID does not belong to the provided discord access token!"));
+    if let Some(existing) = find_confusable_account(&request_data.username).await? {
+        return Err(ApiError::ConfusableUsername(existing))
     }
-
-    // check if there is already an AcornGM account connected to this discord user or with this username
-    info!("Got discord user info for discord user id {}: username: \"{}\", displayname: \"{}\"", request_data.discord_user_id, user_info.username, user_info.global_name);
-    let account_exists: bool = check_if_account_exists_discord(&request_data.username, &request_data.discord_user_id).await
-        .map_err(|e| respond_err(Status::InternalServerError, &e))?;
-
-    if account_exists {
-        return Err(respond_err(Status::Conflict, "Account with this username or discord user id already exists!"))
+    if get_account_by_identity(provider.name(), &identity.subject).await?.is_some() {
+        return Err(ApiError::IdentityAlreadyLinked)
     }
 
     // add to account list
-    info!("Success, adding user with discord user id {} to account list", request_data.discord_user_id);
     let account = AcornAccount {
         username: request_data.username.clone(),
-        discord_user_id: request_data.discord_user_id.clone(),
         created_at: Utc::now(),
     };
 
     info!("Adding account: {account:?}");
-    if let Err(e) = insert_account(&account).await {
-        return Err(respond_err(Status::InternalServerError, &e))
-    }
+    insert_account(&account, provider.name(), &identity, None).await?;
 
-    info!("User {} with Discord ID {} registered successfully.", request_data.username, request_data.discord_user_id);
+    info!("User {} with {} ID {} registered successfully.", request_data.username, provider.name(), identity.subject);
     respond_ok_empty()
 }
 
 
-#[get("/discord_auth?<discord_code>")]
-pub async fn api_get_discord_auth(discord_code: &str) -> ApiResponse {
-    // Get access/refresh tokens from OAuth2 code
-    info!("Handling `GET discord_auth` with code \"{discord_code}\"");
-    let token_response: TokenResponse = exchange_code(discord_code).await
-        .map_err(|(status, e)| respond_err(status, &format!("Error while getting discord access token: {e}")))?;
-    info!("Exchanged code with discord for code \"{discord_code}\"; getting discord user info");
-
-    // Get Discord User ID
-    let user_info: DiscordUserInfo = get_user_info(&token_response.access_token).await
-        .map_err(|e| respond_err(Status::InternalServerError, &format!("Error while getting discord user info: {e}")))?;
-
-    info!("Got user info for code \"{discord_code}\"; username: \"{}\", displayname: \"{}\"", user_info.username, user_info.global_name);
-
-    // check if account already exists
-    let account_maybe: Option<AcornAccount> = get_account_by_discord_id(&user_info.id).await
-        .map_err(|e| respond_err(Status::InternalServerError, &e))?;
-
-    if let Some(account) = account_maybe {
-        info!("Got discord auth for existing user {} with code \"{}\": \
-            Discord ID: {}; Discord Username: {}", account.username, discord_code, user_info.id, user_info.username);
-
-        return respond_ok_value(json!({
-            "register": false,
-            "discordUserId": user_info.id,
-            "username": account.username,
-        }))
-    }
-
-    // account does not exist; let client register
-    info!("Got discord auth for new user with code \"{}\": \
-        Discord ID: {}; Discord Username: {}", discord_code, user_info.id, user_info.username);
-
-    respond_ok_value(json!({
-        "register": true,
-        "discordAccessToken": token_response.access_token,
-        "discordUserId": user_info.id,
-        "discordUsername": user_info.username,
-    }))
-}
-
-
-#[allow(private_interfaces)]
+/// Called by the auth page once the user logged in; AcornGM then trades the temp login token for an access token.
+#[utoipa::path(
+    tag = "auth",
+    request_body = TempLoginRequest,
+    responses(
+        (status = 200, description = "Temp login token can be traded for an access token for 5 minutes"),
+        (status = 409, description = "`temp_login_token_already_exists`", body = ErrorResponse),
+    ),
+)]
 #[post("/temp_login", data="<request_data>")]
-pub async fn api_post_temp_login(request_data: Json<TempLoginRequest>) -> ApiResponse {
-    info!("Handling `POST temp_login` with username {} and temp login token \"{}\"", request_data.username, request_data.temp_login_token);
+pub async fn api_post_temp_login(_rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<TempLoginRequest>) -> ApiResponse {
+    info!("Handling `POST temp_login` with username {} and temp login token {:?}", request_data.username, Redacted(&request_data.temp_login_token));
+    // limited by ip only: nothing proves the request comes from the account, so it must not use up the account's budget
 
-    let already_exists: bool = insert_temp_login_token(&request_data.temp_login_token, &request_data.username).await
-        .map_err(|e| respond_err(Status::InternalServerError, &e))?;
-
-    if already_exists {
-        return Err(respond_err(Status::Conflict, "Temp login token already exists"))
-    }
+    insert_temp_login_token(&request_data.temp_login_token, &request_data.username).await?;
 
     info!("Inserted temp login token into database for username {}.", request_data.username);
     respond_ok_empty()
@@ -230,30 +256,32 @@ pub async fn api_post_temp_login(request_data: Json<TempLoginRequest>) -> ApiRes
 
 
 
-/// post request because json in body is easier to deal with than in params
-#[allow(private_interfaces)]
+/// Post request because json in body is easier to deal with than in params.
+///
+/// The body is the plain temp login token that was given to `temp_login`.
+#[utoipa::path(
+    tag = "auth",
+    request_body(content = String, content_type = "text/plain", description = "Temp login token"),
+    responses(
+        (status = 200, description = "Logged in", body = AccessTokenResponse),
+        (status = 403, description = "`account_banned`", body = ErrorResponse),
+        (status = 404, description = "`temp_login_token_not_found` or `account_not_found`", body = ErrorResponse),
+    ),
+)]
 #[post("/access_token", data="<temp_login_token>")]
-pub async fn api_get_access_token(temp_login_token: &str) -> ApiResponse {
-    info!("Handling `GET access_token` with temp login token \"{}\"", temp_login_token);
+pub async fn api_get_access_token(_rate_limit: RateLimit<'_, AuthRoutes>, temp_login_token: &str) -> ApiResponse {
+    info!("Handling `GET access_token` with temp login token {:?}", Redacted(temp_login_token));
     
-    let username: String = temp_login_token_get_username(&temp_login_token).await
-        .map_err(|e| respond_err(Status::InternalServerError, &e))?
-        .ok_or_else(|| respond_err(Status::NotFound, "Could not find username for temp login token. \
-            It may have expired or the user has not finished logging in yet."))?;
-
-    info!("Found username {} for temp login token \"{}\"", username, temp_login_token);
-    let account_exists: bool = check_if_account_exists(&username).await
-        .map_err(|e| respond_err(Status::InternalServerError, &e))?;
-    if !account_exists {
-        return Err(respond_err(Status::NotFound, &format!("Account with username \"{username}\" does not exist!")))
-    }
+    let username: String = temp_login_token_get_username(temp_login_token).await?
+        .ok_or(ApiError::TempLoginTokenNotFound)?;
+
+    info!("Found username {} for temp login token {:?}", username, Redacted(temp_login_token));
+    ensure_account_not_banned(&username).await?;
 
     // generate access token
     let mut buf = [0u8; 187];
-    rand::rngs::OsRng.try_fill_bytes(&mut buf).map_err(|e| {
-        error!("Could not generate cryptographically secure random bytes for token: {e}");
-        respond_err(Status::InternalServerError, "Could not generate access token!")
-    })?;
+    rand::rngs::OsRng.try_fill_bytes(&mut buf)
+        .map_err(|e| ApiError::Internal(format!("Could not generate cryptographically secure random bytes for token: {e}")))?;
     let generated_token: String = base64::prelude::BASE64_URL_SAFE.encode(buf);
     
     let acorn_token = AcornAccessToken {
@@ -262,49 +290,206 @@ pub async fn api_get_access_token(temp_login_token: &str) -> ApiResponse {
         created_at: Utc::now(),
     };
     
-    insert_access_token(&acorn_token).await.map_err(|e| respond_err(Status::InternalServerError, &e))?;
+    insert_access_token(&acorn_token).await?;
     // Success
     info!("User {} signed in", username);
-    respond_ok_value(json!({"access_token": generated_token}))
+    respond_ok_value(json!(AccessTokenResponse { access_token: generated_token, username }))
+}
+
+
+/// The login provider identities linked to the account.
+#[utoipa::path(
+    tag = "identities",
+    request_body = AccountRequest,
+    responses(
+        (status = 200, description = "Linked identities, oldest first", body = LinkedIdentityList),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+    ),
+)]
+#[post("/identities", data="<request_data>")]
+pub async fn api_get_identities(_rate_limit: RateLimit<'_, ReadRoutes>, request_data: Json<AccountRequest>) -> ApiResponse {
+    info!("Handling `POST identities` for user {}", request_data.username);
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    let identities: Vec<LinkedIdentity> = get_identities(&request_data.username).await?;
+    respond_ok_value(json!(LinkedIdentityList { identities }))
+}
+
+
+/// The provider access token comes from `auth/{provider}`, which says `register: true` for identities without account.
+#[utoipa::path(
+    tag = "identities",
+    request_body = LinkIdentityRequest,
+    responses(
+        (status = 200, description = "Linked; the identity logs into the account from now on"),
+        (status = 401, description = "`not_authenticated`, `invalid_provider_token` or `identity_mismatch`", body = ErrorResponse),
+        (status = 404, description = "`unknown_identity_provider`", body = ErrorResponse),
+        (status = 409, description = "`identity_already_linked`", body = ErrorResponse),
+    ),
+)]
+#[post("/identities/link", data="<request_data>")]
+pub async fn api_link_identity(rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<LinkIdentityRequest>) -> ApiResponse {
+    info!("Handling `POST identities/link` for user {} with {} user id {}", request_data.username, request_data.provider, request_data.provider_user_id);
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+
+    let provider: &dyn IdentityProvider = get_provider(&request_data.provider)?;
+    let identity: ExternalIdentity = verify_identity(provider, &request_data.provider_user_id, &request_data.provider_access_token).await?;
+    link_identity(&request_data.username, provider.name(), &identity).await?;
+
+    info!("Linked {} identity {} to account {}", provider.name(), identity.subject, request_data.username);
+    respond_ok_empty()
+}
+
+
+/// Accounts always keep at least one identity.
+#[utoipa::path(
+    tag = "identities",
+    request_body = UnlinkIdentityRequest,
+    responses(
+        (status = 200, description = "Unlinked"),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 404, description = "`identity_not_linked`", body = ErrorResponse),
+        (status = 409, description = "`last_identity`", body = ErrorResponse),
+    ),
+)]
+#[post("/identities/unlink", data="<request_data>")]
+pub async fn api_unlink_identity(rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<UnlinkIdentityRequest>) -> ApiResponse {
+    info!("Handling `POST identities/unlink` for user {} with {} user id {}", request_data.username, request_data.provider, request_data.provider_user_id);
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+
+    unlink_identity(&request_data.username, &request_data.provider, &request_data.provider_user_id).await?;
+    info!("Unlinked {} identity {} from account {}", request_data.provider, request_data.provider_user_id, request_data.username);
+    respond_ok_empty()
+}
+
+
+/// Starts moving the account from one of its identities to another identity at the same provider (e.g. a new discord account).
+///
+/// The new identity confirms with the returned code through `identities/move/confirm`, usually via `/goto_move/{provider}`.
+#[utoipa::path(
+    tag = "identities",
+    request_body = UnlinkIdentityRequest,
+    responses(
+        (status = 200, description = "Move started; the code is valid for 15 minutes", body = IdentityMoveStarted),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 404, description = "`unknown_identity_provider` or `identity_not_linked`", body = ErrorResponse),
+    ),
+)]
+#[post("/identities/move", data="<request_data>")]
+pub async fn api_start_identity_move(rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<UnlinkIdentityRequest>) -> ApiResponse {
+    info!("Handling `POST identities/move` for user {} with {} user id {}", request_data.username, request_data.provider, request_data.provider_user_id);
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    let provider: &dyn IdentityProvider = get_provider(&request_data.provider)?;
+
+    let mut buf = [0u8; 32];
+    rand::rngs::OsRng.try_fill_bytes(&mut buf)
+        .map_err(|e| ApiError::Internal(format!("Could not generate cryptographically secure random bytes for move code: {e}")))?;
+    let move_code: String = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(buf);
+
+    insert_identity_move(&request_data.username, provider.name(), &request_data.provider_user_id, &move_code).await?;
+    info!("Started moving account {} away from {} identity {}", request_data.username, provider.name(), request_data.provider_user_id);
+    respond_ok_value(json!(IdentityMoveStarted { move_code }))
+}
+
+
+/// The provider access token comes from `auth/{provider}` of the new identity, which must not have an account yet.
+#[utoipa::path(
+    tag = "identities",
+    request_body = ConfirmIdentityMoveRequest,
+    responses(
+        (status = 200, description = "The account now logs in with the new identity instead of the old one", body = IdentityMoved),
+        (status = 401, description = "`invalid_provider_token` or `identity_mismatch`", body = ErrorResponse),
+        (status = 404, description = "`unknown_identity_provider` or `identity_move_not_found`", body = ErrorResponse),
+        (status = 409, description = "`identity_already_linked`", body = ErrorResponse),
+    ),
+)]
+#[post("/identities/move/confirm", data="<request_data>")]
+pub async fn api_confirm_identity_move(_rate_limit: RateLimit<'_, AuthRoutes>, request_data: Json<ConfirmIdentityMoveRequest>) -> ApiResponse {
+    info!("Handling `POST identities/move/confirm` with {} user id {}", request_data.provider, request_data.provider_user_id);
+    let provider: &dyn IdentityProvider = get_provider(&request_data.provider)?;
+    let identity: ExternalIdentity = verify_identity(provider, &request_data.provider_user_id, &request_data.provider_access_token).await?;
+
+    let identity_move: IdentityMove = complete_identity_move(&request_data.move_code, provider.name(), &identity).await?;
+    record_identity_login(&identity_move.username, provider.name(), &identity, None).await?;
+
+    info!("Moved account {} from {} identity {} to {}", identity_move.username, provider.name(), identity_move.from_subject, identity.subject);
+    respond_ok_value(json!(IdentityMoved { username: identity_move.username }))
+}
+
+
+/// Public profile of an account.
+#[utoipa::path(
+    tag = "accounts",
+    responses(
+        (status = 200, description = "Profile", body = AccountProfile),
+        (status = 404, description = "`account_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/account/<username>")]
+pub async fn api_get_account(_rate_limit: RateLimit<'_, ReadRoutes>, username: &str) -> ApiResponse {
+    info!("Handling `GET account/{username}`");
+    let profile: AccountProfile = get_account_profile(username).await?;
+    respond_ok_value(json!(profile))
+}
+
+
+#[get("/goto_auth/<provider>?<temp_login_token>")]
+pub async fn redirect_goto_auth(provider: &str, temp_login_token: String) -> Result<RawHtml<String>, Status> {
+    redirect_to_provider(provider, "tempLoginToken", &temp_login_token)
 }
 
 
 #[get("/goto_discord_auth?<temp_login_token>")]
-pub async fn redirect_goto_discord_auth(temp_login_token: String) -> RawHtml<String> {
-    const DISCORD_AUTH_URL: &'static str = "https://discord.com/oauth2/authorize\
-        ?client_id=1360325253766578479\
-        &response_type=code\
-        &redirect_uri=https%3A%2F%2Facorngm.biotomatede.hackclub.app%2Fdiscord_auth_page.html\
-        &scope=identify";
-
-    RawHtml(format!("\
+pub async fn redirect_goto_discord_auth(temp_login_token: String) -> Result<RawHtml<String>, Status> {
+    redirect_goto_auth("discord", temp_login_token).await
+}
+
+
+/// the auth page confirms the move once the user is back from the provider
+#[get("/goto_move/<provider>?<move_code>")]
+pub async fn redirect_goto_move(provider: &str, move_code: String) -> Result<RawHtml<String>, Status> {
+    redirect_to_provider(provider, "moveCode", &move_code)
+}
+
+
+/// A JavaScript string literal that is safe inside a `<script>` element: JSON escapes the quotes and backslashes,
+/// and `<` and `>` are escaped so the value can not close the element.
+fn script_string_literal(value: &str) -> String {
+    serde_json::to_string(value)
+        .expect("Strings can always be serialized")
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+/// Remembers the value in the browser's local storage for the auth page, then sends the user to the provider.
+fn redirect_to_provider(provider: &str, storage_key: &str, value: &str) -> Result<RawHtml<String>, Status> {
+    let provider: &dyn IdentityProvider = get_provider(provider).map_err(|_| Status::NotFound)?;
+    // validated at startup, so this can not fail anymore
+    let auth_url: String = provider.authorize_url()
+        .expect("Authorize url should have been validated")
+        .to_string();
+    let storage_key: String = script_string_literal(storage_key);
+    let value: String = script_string_literal(value);
+    let auth_url: String = script_string_literal(&auth_url);
+
+    Ok(RawHtml(format!("\
     <!DOCTYPE html>\
     <html>\
     <head>\
     <title>AcornGM</title>\
     </head>\
     <body>\
-    <h1>Redirecting to Discord...</h1>\
+    <h1>Redirecting to {}...</h1>\
     <script>\
-    localStorage.setItem('tempLoginToken', '{temp_login_token}');\
-    window.location.replace('{DISCORD_AUTH_URL}')\
+    localStorage.setItem({storage_key}, {value});\
+    window.location.replace({auth_url})\
     </script>\
     </body>\
     </html>\
-    "))
-}
-
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-struct RegisterRequest {
-    username: String,
-    discord_user_id: String,
-    discord_access_token: String,
-}
-
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-struct TempLoginRequest {
-    temp_login_token: String,
-    username: String,
+    ", provider.display_name())))
 }
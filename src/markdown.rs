@@ -0,0 +1,116 @@
+//! Mod descriptions are written in Markdown and rendered to HTML when they are saved.
+//!
+//! Raw HTML in the Markdown is allowed, so everything goes through an allowlist afterwards:
+//! only formatting tags survive, links may only point to `http`, `https` and `mailto` URLs
+//! and are marked `nofollow`. Images are not allowed; mods have screenshot galleries for that,
+//! and images from other servers would let them track who reads a description.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Options, Parser};
+use regex::Regex;
+use crate::pool;
+use crate::api_error::ApiError;
+
+
+pub const ALLOWED_TAGS: [&str; 25] = [
+    "a", "blockquote", "br", "code", "del", "em", "h1", "h2", "h3", "h4", "h5", "h6",
+    "hr", "li", "ol", "p", "pre", "strong", "table", "tbody", "td", "th", "thead", "tr", "ul",
+];
+const LINK_REL: &str = "nofollow noopener noreferrer";
+
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::empty();
+    builder
+        .tags(HashSet::from(ALLOWED_TAGS))
+        .tag_attributes([("a", HashSet::from(["href", "title"])), ("ol", HashSet::from(["start"]))].into())
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        // relative links would point into this server
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some(LINK_REL))
+        .clean_content_tags(HashSet::from(["script", "style"]))
+        .strip_comments(true);
+    builder
+});
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<(/?)([^\s/>]*)((?:[^>"]|"[^"]*")*)>"#)
+    .expect("Could not load tag pattern"));
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"([^\s=]+)="([^"]*)""#)
+    .expect("Could not load attribute pattern"));
+
+
+/// Renders a description to HTML that can be put into a page as is.
+pub fn render_description(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH);
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, parser);
+    sanitize_html(&unsafe_html)
+}
+
+/// Drops everything that is not on the allowlist; the result is well-formed.
+pub fn sanitize_html(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
+
+
+/// Checks rendered HTML without relying on the sanitizer, for the tests and the fuzzer: it only has tags and
+/// attributes of the allowlist, links only go to http(s) and mail addresses and are all marked `nofollow`.
+/// Returns what is wrong with it.
+pub fn find_unsafe_html(html: &str) -> Option<String> {
+    for tag in TAG.captures_iter(html) {
+        let name: &str = &tag[2];
+        if !ALLOWED_TAGS.contains(&name) {
+            return Some(format!("tag {name:?}"))
+        }
+        for attribute in ATTRIBUTE.captures_iter(&tag[3]) {
+            if !matches!((name, &attribute[1]), ("a", "href" | "title" | "rel") | ("ol", "start")) {
+                return Some(format!("attribute {:?} of {name:?}", &attribute[1]))
+            }
+            let url: &str = &attribute[2];
+            if &attribute[1] == "href" && !["http://", "https://", "mailto:"].iter().any(|scheme| url.starts_with(scheme)) {
+                return Some(format!("link {url:?}"))
+            }
+        }
+        if name == "a" && tag[1].is_empty() && !tag[3].contains(&format!(r#"rel="{LINK_REL}""#)) {
+            return Some("link without rel".to_string())
+        }
+    }
+    None
+}
+
+
+/// Renders the descriptions that were saved before they were rendered on save, see `0016_description_html.sql`.
+pub async fn render_missing_descriptions() -> Result<u64, ApiError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, description
+        FROM mods
+        WHERE description_html IS NULL
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch descriptions to render: {e}")))?;
+    if rows.is_empty() {
+        return Ok(0)
+    }
+
+    let ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+    let rendered: Vec<String> = rows.iter().map(|row| render_description(&row.description)).collect();
+    let result = sqlx::query!(
+        r#"
+        UPDATE mods
+        SET description_html = rendered.html
+        FROM UNNEST($1::UUID[], $2::TEXT[]) AS rendered(id, html)
+        WHERE mods.id = rendered.id
+        "#,
+        &ids,
+        &rendered,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not save rendered descriptions: {e}")))?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,251 @@
+//! Prometheus metrics. They are always collected, but only served (at `/metrics`) if `metrics.enabled`,
+//! by a separate listener on `metrics.address`, which has to be an internal address.
+
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+use prometheus::{
+    Encoder,
+    HistogramOpts,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    IntGauge,
+    IntGaugeVec,
+    Opts,
+    Registry,
+    TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::figment::Figment;
+use rocket::http::ContentType;
+use rocket::{Data, Orbit, Request, Response, Rocket, Shutdown};
+use crate::{app_config, pool};
+use crate::api_error::ApiError;
+use crate::jobs::jobs;
+
+
+struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    upload_bytes: IntCounterVec,
+    provider_request_duration: HistogramVec,
+    provider_failures: IntCounterVec,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max_connections: IntGauge,
+    accounts: IntGauge,
+    mods: IntGaugeVec,
+    job_runs: IntCounterVec,
+    job_failures: IntCounterVec,
+    job_skipped: IntCounterVec,
+    job_last_duration: IntGaugeVec,
+    job_last_affected_rows: IntGaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new_custom(Some("acorngm".to_string()), None).expect("Could not create metrics registry");
+    let register = |collector: Box<dyn prometheus::core::Collector>| registry.register(collector).expect("Could not register metric");
+
+    let http_requests = IntCounterVec::new(
+        Opts::new("http_requests_total", "Handled requests"), &["method", "route", "status"]).unwrap();
+    let http_request_duration = HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "Time until the response was ready"), &["method", "route", "status"]).unwrap();
+    let upload_bytes = IntCounterVec::new(
+        Opts::new("upload_bytes_total", "Bytes of mod files and screenshots received"), &["kind"]).unwrap();
+    let provider_request_duration = HistogramVec::new(
+        HistogramOpts::new("identity_provider_request_duration_seconds", "Time login providers took to answer"), &["provider", "operation"]).unwrap();
+    let provider_failures = IntCounterVec::new(
+        Opts::new("identity_provider_failures_total", "Login provider calls that failed on the provider's side"), &["provider", "operation"]).unwrap();
+    let db_pool_connections = IntGaugeVec::new(
+        Opts::new("db_pool_connections", "Open database connections"), &["state"]).unwrap();
+    let db_pool_max_connections = IntGauge::new("db_pool_max_connections", "Configured size of the database pool").unwrap();
+    let accounts = IntGauge::new("accounts", "Registered accounts").unwrap();
+    let mods = IntGaugeVec::new(Opts::new("mods", "Mods"), &["review_state"]).unwrap();
+    let job_runs = IntCounterVec::new(
+        Opts::new("job_runs_total", "Runs of a housekeeping job by this instance"), &["job"]).unwrap();
+    let job_failures = IntCounterVec::new(
+        Opts::new("job_failures_total", "Failed runs of a housekeeping job by this instance"), &["job"]).unwrap();
+    let job_skipped = IntCounterVec::new(
+        Opts::new("job_skipped_total", "Times this instance skipped a job because it was not due or running elsewhere"), &["job"]).unwrap();
+    let job_last_duration = IntGaugeVec::new(
+        Opts::new("job_last_duration_milliseconds", "Duration of the last run of a job on this instance"), &["job"]).unwrap();
+    let job_last_affected_rows = IntGaugeVec::new(
+        Opts::new("job_last_affected_rows", "Rows the last run of a job on this instance touched"), &["job"]).unwrap();
+
+    register(Box::new(http_requests.clone()));
+    register(Box::new(http_request_duration.clone()));
+    register(Box::new(upload_bytes.clone()));
+    register(Box::new(provider_request_duration.clone()));
+    register(Box::new(provider_failures.clone()));
+    register(Box::new(db_pool_connections.clone()));
+    register(Box::new(db_pool_max_connections.clone()));
+    register(Box::new(accounts.clone()));
+    register(Box::new(mods.clone()));
+    register(Box::new(job_runs.clone()));
+    register(Box::new(job_failures.clone()));
+    register(Box::new(job_skipped.clone()));
+    register(Box::new(job_last_duration.clone()));
+    register(Box::new(job_last_affected_rows.clone()));
+
+    Metrics {
+        registry,
+        http_requests,
+        http_request_duration,
+        upload_bytes,
+        provider_request_duration,
+        provider_failures,
+        db_pool_connections,
+        db_pool_max_connections,
+        accounts,
+        mods,
+        job_runs,
+        job_failures,
+        job_skipped,
+        job_last_duration,
+        job_last_affected_rows,
+    }
+});
+
+
+/// `kind` is `upload`, `update` or `screenshot`
+pub fn record_upload(kind: &str, bytes: usize) {
+    METRICS.upload_bytes.with_label_values(&[kind]).inc_by(bytes as u64);
+}
+
+/// Times a call to a login provider; only errors on the provider's side count as failures, not rejected codes or tokens.
+pub async fn observe_provider_call<T>(provider: &str, operation: &str, call: impl Future<Output = Result<T, ApiError>>) -> Result<T, ApiError> {
+    let started = Instant::now();
+    let result: Result<T, ApiError> = call.await;
+    METRICS.provider_request_duration.with_label_values(&[provider, operation]).observe(started.elapsed().as_secs_f64());
+    if let Err(ApiError::IdentityProvider(_, _)) = &result {
+        METRICS.provider_failures.with_label_values(&[provider, operation]).inc();
+    }
+    result
+}
+
+
+/// The jobs count their runs themselves; their counters only ever move forward to that count.
+fn advance_to(counter: &IntCounter, total: u64) {
+    counter.inc_by(total.saturating_sub(counter.get()));
+}
+
+/// Everything in the Prometheus text format; gauges and the job counters are brought up to date first.
+pub async fn render() -> Result<String, ApiError> {
+    let metrics: &Metrics = &METRICS;
+
+    let pool_size: u32 = pool().size();
+    let idle: usize = pool().num_idle();
+    metrics.db_pool_connections.with_label_values(&["idle"]).set(idle as i64);
+    metrics.db_pool_connections.with_label_values(&["in_use"]).set(i64::from(pool_size) - idle as i64);
+    metrics.db_pool_max_connections.set(i64::from(app_config().database_pool_size));
+
+    let accounts: Option<i64> = sqlx::query_scalar!("SELECT COUNT(*) FROM accounts")
+        .fetch_one(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not count accounts: {e}")))?;
+    metrics.accounts.set(accounts.unwrap_or(0));
+
+    let mods = sqlx::query!(
+        r#"
+        SELECT review_state, COUNT(*) AS "count!"
+        FROM mods
+        GROUP BY review_state
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not count mods: {e}")))?;
+    // states without mods would otherwise keep their last count
+    metrics.mods.reset();
+    for row in mods {
+        metrics.mods.with_label_values(&[&row.review_state]).set(row.count);
+    }
+
+    for job in jobs() {
+        let labels: [&str; 1] = [job.name];
+        advance_to(&metrics.job_runs.with_label_values(&labels), job.metrics.runs.load(Ordering::Relaxed));
+        advance_to(&metrics.job_failures.with_label_values(&labels), job.metrics.failures.load(Ordering::Relaxed));
+        advance_to(&metrics.job_skipped.with_label_values(&labels), job.metrics.skipped.load(Ordering::Relaxed));
+        metrics.job_last_duration.with_label_values(&labels).set(job.metrics.last_duration_ms.load(Ordering::Relaxed) as i64);
+        metrics.job_last_affected_rows.with_label_values(&labels).set(job.metrics.last_affected_rows.load(Ordering::Relaxed) as i64);
+    }
+
+    let mut buffer: Vec<u8> = Vec::new();
+    TextEncoder::new().encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|e| ApiError::Internal(format!("Could not encode metrics: {e}")))?;
+    String::from_utf8(buffer).map_err(|e| ApiError::Internal(format!("Metrics are not valid UTF-8: {e}")))
+}
+
+
+#[get("/metrics")]
+async fn metrics_endpoint() -> Result<(ContentType, String), ApiError> {
+    let content_type = ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    Ok((content_type, render().await?))
+}
+
+
+/// When the request reached the fairing.
+struct RequestStart(Instant);
+
+/// Counts and times every request; on liftoff, starts the internal metrics listener if enabled.
+pub struct MetricsFairing {
+    listener_shutdown: Mutex<Option<Shutdown>>,
+}
+
+pub fn fairing() -> MetricsFairing {
+    MetricsFairing { listener_shutdown: Mutex::new(None) }
+}
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info { name: "Metrics", kind: Kind::Request | Kind::Response | Kind::Liftoff | Kind::Shutdown }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let started: Instant = req.local_cache(|| RequestStart(Instant::now())).0;
+        // the route's uri template, so ids in paths do not create new series
+        let route: String = req.route().map_or_else(|| "unmatched".to_string(), |route| route.uri.to_string());
+        let status: String = res.status().code.to_string();
+        let labels: [&str; 3] = [req.method().as_str(), &route, &status];
+        METRICS.http_requests.with_label_values(&labels).inc();
+        METRICS.http_request_duration.with_label_values(&labels).observe(started.elapsed().as_secs_f64());
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = &app_config().metrics;
+        if !config.enabled {
+            return
+        }
+
+        let figment: Figment = rocket.figment().clone()
+            .merge(("address", config.address))
+            .merge(("port", config.port));
+        let listener = match rocket::custom(figment).mount("/", routes![metrics_endpoint]).ignite().await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Could not start metrics listener: {e}");
+                return
+            }
+        };
+        *self.listener_shutdown.lock().unwrap_or_else(|e| e.into_inner()) = Some(listener.shutdown());
+        rocket::tokio::spawn(async move {
+            if let Err(e) = listener.launch().await {
+                error!("Metrics listener failed: {e}");
+            }
+        });
+        info!("Serving metrics at http://{}:{}/metrics", config.address, config.port);
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        if let Some(shutdown) = self.listener_shutdown.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            shutdown.notify();
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use sqlx::migrate::Migrator;
+use crate::pool;
+use crate::accounts::compute_missing_username_skeletons;
+use crate::markdown::render_missing_descriptions;
+
+
+/// All migrations in `migrations/`, embedded into the binary at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Also fills in data the migrations can not compute themselves.
+pub async fn run_migrations() -> Result<(), String> {
+    MIGRATOR.run(pool()).await.map_err(|e| format!("Could not run database migrations: {e}"))?;
+    render_missing_descriptions().await.map_err(|e| format!("Could not render mod descriptions: {}", e.describe()))?;
+    compute_missing_username_skeletons().await.map_err(|e| format!("Could not compute username skeletons: {}", e.describe()))?;
+    Ok(())
+}
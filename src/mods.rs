@@ -1,160 +1,403 @@
 use std::str::FromStr;
+use chrono::{DateTime, Duration, Utc};
 use rocket::Data;
 use rocket::form::validate::Contains;
-use rocket::http::{ContentType, Status};
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
 use rocket_multipart_form_data::{MultipartFormData, MultipartFormDataField, MultipartFormDataOptions};
-use sqlx::QueryBuilder;
+use serde_json::json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
-use crate::{pool, respond_err, respond_ok_empty, ApiResponse};
+use crate::{app_config, pool, respond_ok_empty, respond_ok_value, ApiResponse};
+use crate::api_error::ApiError;
 use crate::accounts::ensure_account_authentication;
-use crate::sanitize::sanitize_string;
+use crate::markdown::render_description;
+use crate::metrics::record_upload;
+use crate::rate_limit::{RateLimit, ReadRoutes, UploadRoutes};
+use crate::review::{get_mod_review_state, review_state_for_upload, ReviewState};
+use crate::sanitize::{find_mixed_script_word, grapheme_length, sanitize_string};
+use crate::search_mods::{search, suggest, SearchFilters};
+use crate::tags::{normalize_tag, parse_categories, parse_tags, resolve_aliases, set_mod_categories, set_mod_tags};
+use acorngm_api_client::error::ErrorResponse;
+use acorngm_api_client::tags::ModCategory;
+use acorngm_api_client::mods::{
+    DeleteModForm,
+    ModFile,
+    ModList,
+    ModRatings,
+    ModSearchResults,
+    ModSuggestions,
+    ModSummary,
+    ModUploaded,
+    OwnModList,
+    OwnModStatus,
+    OwnModsRequest,
+    RateModRequest,
+    SearchMode,
+    SearchSort,
+    UpdateModForm,
+    UploadModForm,
+};
 
 
-const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;   // 16 MB
+pub const MODS_PER_PAGE: i64 = 50;
+/// Everything a [`ModSummary`] is made of, selected from `mods`. Every query that lists mods uses this,
+/// so the tags, categories, cover and rating average are worked out the same way everywhere.
+/// `description_html` stays nullable: the descriptions saved before `0016_description_html.sql` are only rendered
+/// by the server once the migrations ran, so there is no point at which a migration could make it `NOT NULL`.
+pub const MOD_SUMMARY_COLUMNS: &str = "\
+    id, author, title, description, COALESCE(description_html, '') AS description_html, game_name, game_version_major, game_version_minor, mod_version, created_at, updated_at, language, \
+    download_count, rating_count, rating_sum::FLOAT8 / NULLIF(rating_count, 0) AS rating_average, \
+    ARRAY(SELECT tag FROM mod_tags WHERE mod_tags.mod_id = mods.id ORDER BY tag) AS tags, \
+    ARRAY(SELECT category FROM mod_categories WHERE mod_categories.mod_id = mods.id ORDER BY category) AS categories, \
+    COALESCE(cover_screenshot_id, (SELECT id FROM mod_screenshots WHERE mod_screenshots.mod_id = mods.id ORDER BY position LIMIT 1)) AS cover_screenshot_id";
+/// Completions are for a search box; longer text is not typed there.
+const MAX_SUGGEST_PREFIX_LENGTH: usize = 100;
+/// Counted in grapheme clusters, like all text lengths.
+const MAX_DESCRIPTION_LENGTH: usize = 10_000;
 
 
+/// A row of [`MOD_SUMMARY_COLUMNS`]; extra columns (like a search score) are ignored.
+#[derive(FromRow)]
+pub struct ModSummaryRow {
+    id: Uuid,
+    author: String,
+    title: String,
+    description: String,
+    description_html: String,
+    game_name: String,
+    game_version_major: i32,
+    game_version_minor: i32,
+    mod_version: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    language: String,
+    download_count: i64,
+    rating_average: Option<f64>,
+    rating_count: i64,
+    tags: Vec<String>,
+    categories: Vec<String>,
+    cover_screenshot_id: Option<Uuid>,
+}
+
+impl From<ModSummaryRow> for ModSummary {
+    fn from(row: ModSummaryRow) -> Self {
+        ModSummary {
+            id: row.id,
+            author: row.author,
+            title: row.title,
+            description: row.description,
+            description_html: row.description_html,
+            game_name: row.game_name,
+            game_version_major: row.game_version_major,
+            game_version_minor: row.game_version_minor,
+            mod_version: row.mod_version,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            language: row.language,
+            download_count: row.download_count,
+            rating_average: row.rating_average,
+            rating_count: row.rating_count,
+            tags: row.tags,
+            categories: row.categories,
+            cover_screenshot_id: row.cover_screenshot_id,
+        }
+    }
+}
+
+/// `SELECT` of the [`MOD_SUMMARY_COLUMNS`] from `mods`; push the conditions, then run it with [`fetch_mod_summaries`].
+pub fn select_mod_summaries<'a>() -> QueryBuilder<'a, Postgres> {
+    QueryBuilder::new(format!("SELECT {MOD_SUMMARY_COLUMNS} FROM mods"))
+}
+
+pub async fn fetch_mod_summaries(pool: &PgPool, mut query: QueryBuilder<'_, Postgres>) -> Result<Vec<ModSummary>, sqlx::Error> {
+    let rows: Vec<ModSummaryRow> = query.build_query_as().fetch_all(pool).await?;
+    Ok(rows.into_iter().map(ModSummary::from).collect())
+}
+
+
+fn validate_title(title: &str) -> Result<String, ApiError> {
+    let title: String = sanitize_string(title).ok_or(ApiError::InvalidTitle("Invalid title"))?;
+    if !(8..=256).contains(&grapheme_length(&title)) {
+        return Err(ApiError::InvalidTitle("Title should be 8-256 chars long"))
+    }
+    if title.contains('\n') {
+        return Err(ApiError::InvalidTitle("Title must not contain newlines"))
+    }
+    // a Latin title with a Cyrillic letter in it would pass for another mod
+    if find_mixed_script_word(&title).is_some() {
+        return Err(ApiError::InvalidTitle("Title must not mix letters of different scripts in one word"))
+    }
+    Ok(title)
+}
+
+fn validate_description(description: &str) -> Result<String, ApiError> {
+    let description: String = sanitize_string(description).ok_or(ApiError::InvalidDescription("Invalid description"))?;
+    if grapheme_length(&description) > MAX_DESCRIPTION_LENGTH {
+        return Err(ApiError::InvalidDescription("Description must not be longer than 10000 chars"))
+    }
+    Ok(description)
+}
+
+
+pub fn is_known_game(game_name: &str) -> bool {
+    matches!(game_name, "Undertale" | "Deltarune")
+}
+
+/// `"1.08"` becomes `(1, 8)`, in the order of the `game_version_minor` and `game_version_major` columns.
+pub fn parse_game_version(game_version: &str) -> Option<(i32, i32)> {
+    let mut game_version_parts = game_version.split('.');
+    let game_version_minor: i32 = game_version_parts.next()?.parse::<u32>().ok()? as i32;
+    let game_version_major: i32 = game_version_parts.next()?.parse::<u32>().ok()? as i32;
+    if game_version_parts.next().is_some() {
+        return None
+    }
+    Some((game_version_minor, game_version_major))
+}
+
+
+/// ISO 639-1 codes: two lowercase letters. Languages without a stemmer are still fine; search falls back to exact words for them.
+pub fn is_language_code(language: &str) -> bool {
+    language.len() == 2 && language.bytes().all(|c| c.is_ascii_lowercase())
+}
+
+
+/// Uploads a new mod; it is public right away unless the review policy holds it back.
+#[utoipa::path(
+    tag = "mods",
+    request_body(content = UploadModForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Uploaded", body = ModUploaded),
+        (status = 400, description = "`malformed_request`, `invalid_title`, `invalid_description`, `invalid_game_name`, `invalid_game_version`, `invalid_language`, `invalid_tag` or `invalid_category`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 415, description = "`unsupported_content_type`", body = ErrorResponse),
+    ),
+)]
 #[put("/mod", data = "<data>")]
-pub async fn api_upload_mod(content_type: &ContentType, data: Data<'_>) -> ApiResponse {
-    let err_400 = |e: String| respond_err(Status::BadRequest, &e);
+pub async fn api_upload_mod(rate_limit: RateLimit<'_, UploadRoutes>, content_type: &ContentType, data: Data<'_>) -> ApiResponse {
     info!("Handling `PUT` mod");
 
     // Check MIME type
-    if !content_type.is_binary() {
+    if !content_type.is_form_data() {
         warn!("Unsupported content type: {content_type}");
-        return Err(respond_err(Status::BadRequest, &format!("Unsupported content type {}", content_type)));
+        return Err(ApiError::UnsupportedContentType(content_type.to_string()));
     }
 
     let form_options = MultipartFormDataOptions::with_multipart_form_data_fields(vec![
         MultipartFormDataField::text("username"),
         MultipartFormDataField::text("access_token"),
-        MultipartFormDataField::raw("file_data").size_limit(MAX_FILE_SIZE),
+        MultipartFormDataField::raw("file_data").size_limit(app_config().max_mod_file_size.as_u64()),
         MultipartFormDataField::text("title"),
         MultipartFormDataField::text("description"),
         MultipartFormDataField::text("game_name"),
         MultipartFormDataField::text("game_version"),
+        MultipartFormDataField::text("language"),
+        MultipartFormDataField::text("tags"),
+        MultipartFormDataField::text("categories"),
     ]);
     let form_data: MultipartFormData = MultipartFormData::parse(content_type, data, form_options).await
-        .map_err(|e| format!("Could not parse form data: {e}")).map_err(err_400)?;
+        .map_err(|e| ApiError::MalformedRequest(format!("Could not parse form data: {e}")))?;
     
-    let username: &String = get_text_form_field(&form_data, "username").map_err(err_400)?;
-    let access_token: &String = get_text_form_field(&form_data, "access_token").map_err(err_400)?;
-    let file_data: &Vec<u8> = get_bytes_form_field(&form_data, "file_data").map_err(err_400)?;
-    let title: &String = get_text_form_field(&form_data, "title").map_err(err_400)?;
-    let description: &String = get_text_form_field(&form_data, "description").map_err(err_400)?;
-    let game_name: &String = get_text_form_field(&form_data, "game_name").map_err(err_400)?;
-    let game_version: &String = get_text_form_field(&form_data, "game_version").map_err(err_400)?;
-
-    ensure_account_authentication(&username, &access_token).await?;
+    let username: &String = get_text_form_field(&form_data, "username")?;
+    let access_token: &String = get_text_form_field(&form_data, "access_token")?;
+    let file_data: &Vec<u8> = get_bytes_form_field(&form_data, "file_data")?;
+    let title: &String = get_text_form_field(&form_data, "title")?;
+    let description: &String = get_text_form_field(&form_data, "description")?;
+    let game_name: &String = get_text_form_field(&form_data, "game_name")?;
+    let game_version: &String = get_text_form_field(&form_data, "game_version")?;
+    let language: &str = get_text_form_field_opt(&form_data, "language").map_or("en", String::as_str);
+    let tags: &str = get_text_form_field_opt(&form_data, "tags").map_or("", String::as_str);
+    let categories: &str = get_text_form_field_opt(&form_data, "categories").map_or("", String::as_str);
 
-    let title: String = sanitize_string(title).ok_or_else(|| respond_err(Status::BadRequest, "Invalid title"))?;
-    if title.len() > 256 || title.len() < 8 {
-        return Err(respond_err(Status::BadRequest, "Title should be 8-256 chars long"))
-    }
-    if title.contains("\n") || title.contains("\r") {
-        return Err(respond_err(Status::BadRequest, "Title must not contain newlines"))
-    }
+    ensure_account_authentication(username, access_token).await?;
+    rate_limit.check_account(username).await?;
 
-    let description: String = sanitize_string(description).ok_or_else(|| respond_err(Status::BadRequest, "Invalid description"))?;
+    let title: String = validate_title(title)?;
+    let description: String = validate_description(description)?;
     
-    if !matches!(game_name.as_str(), "Undertale" | "Deltarune") {
-        return Err(respond_err(Status::BadRequest, "Invalid or unknown game name"))
+    if !is_known_game(game_name) {
+        return Err(ApiError::InvalidGameName)
     }
     
-    let err_game_ver = || respond_err(Status::BadRequest, "Invalid game version");
-    let mut game_version_parts = game_version.split('.');
-    let game_version_minor: i32 = game_version_parts.next().ok_or_else(err_game_ver)?.parse::<u32>().map_err(|_| err_game_ver())? as i32;
-    let game_version_major: i32 = game_version_parts.next().ok_or_else(err_game_ver)?.parse::<u32>().map_err(|_| err_game_ver())? as i32;
-    if game_version_parts.next().is_some() {
-        return Err(err_game_ver())
+    let (game_version_minor, game_version_major): (i32, i32) = parse_game_version(game_version).ok_or(ApiError::InvalidGameVersion)?;
+
+    if !is_language_code(language) {
+        return Err(ApiError::InvalidLanguage)
     }
-    
-    sqlx::query!(
+
+    let tags: Vec<String> = resolve_aliases(parse_tags(tags)?).await?;
+    let categories: Vec<ModCategory> = parse_categories(categories)?;
+
+    let review_state: ReviewState = review_state_for_upload(username).await?;
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for upload: {e}")))?;
+    let mod_id: Uuid = sqlx::query_scalar!(
         r#"
-        INSERT INTO mods (author, file_data, title, description, game_name, game_version_major, game_version_minor)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO mods (author, file_data, title, description, description_html, game_name, game_version_major, game_version_minor, review_state, language)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id
         "#,
         username,
         file_data,
         title,
         description,
+        render_description(&description),
         game_name,
         game_version_major,
         game_version_minor,
+        review_state.as_str(),
+        language,
     )
-        .execute(pool())
+        .fetch_one(&mut *transaction)
         .await
-        .map_err(|e| respond_err(
-            Status::InternalServerError,
-            &format!("Could not create mod for mod with title \"{title}\": {e}"))
-        )?;
-    
-    respond_ok_empty()
+        .map_err(|e| ApiError::Internal(format!("Could not create mod for mod with title \"{title}\": {e}")))?;
+    set_mod_tags(&mut transaction, mod_id, &tags).await?;
+    set_mod_categories(&mut transaction, mod_id, &categories).await?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit upload of mod {mod_id}: {e}")))?;
+
+    record_upload("upload", file_data.len());
+    info!("User {username} uploaded mod {mod_id} ({})", review_state.as_str());
+    respond_ok_value(json!(ModUploaded { mod_id, review_state: review_state.as_str().to_string() }))
 }
 
 
+/// Replaces the file, description, language, tags and/or categories of a mod and bumps its version.
+#[utoipa::path(
+    tag = "mods",
+    request_body(content = UpdateModForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Updated; new files and descriptions of new uploaders have to be reviewed again"),
+        (status = 400, description = "`malformed_request`, `invalid_mod_id`, `invalid_description`, `invalid_language`, `invalid_tag`, `invalid_category` or `nothing_to_update`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_mod_owner` or `mod_removed`", body = ErrorResponse),
+        (status = 415, description = "`unsupported_content_type`", body = ErrorResponse),
+    ),
+)]
 #[patch("/mod", data = "<data>")]
-pub async fn api_update_mod(content_type: &ContentType, data: Data<'_>) -> ApiResponse {
-    let err_400 = |e: String| respond_err(Status::BadRequest, &e);
+pub async fn api_update_mod(rate_limit: RateLimit<'_, UploadRoutes>, content_type: &ContentType, data: Data<'_>) -> ApiResponse {
     info!("Handling `PATCH` mod");
 
     // Check MIME type
-    if !content_type.is_binary() {
+    if !content_type.is_form_data() {
         warn!("Unsupported content type: {content_type}");
-        return Err(respond_err(Status::BadRequest, &format!("Unsupported content type {}", content_type)));
+        return Err(ApiError::UnsupportedContentType(content_type.to_string()));
     }
 
     let form_options = MultipartFormDataOptions::with_multipart_form_data_fields(vec![
         MultipartFormDataField::text("username"),
         MultipartFormDataField::text("access_token"),
         MultipartFormDataField::text("mod_id"),
-        MultipartFormDataField::raw("file_data").size_limit(MAX_FILE_SIZE),
+        MultipartFormDataField::raw("file_data").size_limit(app_config().max_mod_file_size.as_u64()),
         MultipartFormDataField::text("description"),
+        MultipartFormDataField::text("language"),
+        MultipartFormDataField::text("tags"),
+        MultipartFormDataField::text("categories"),
     ]);
     let form_data: MultipartFormData = MultipartFormData::parse(content_type, data, form_options).await
-        .map_err(|e| format!("Could not parse form data: {e}")).map_err(err_400)?;
+        .map_err(|e| ApiError::MalformedRequest(format!("Could not parse form data: {e}")))?;
 
-    let username: &String = get_text_form_field(&form_data, "username").map_err(err_400)?;
-    let access_token: &String = get_text_form_field(&form_data, "access_token").map_err(err_400)?;
+    let username: &String = get_text_form_field(&form_data, "username")?;
+    let access_token: &String = get_text_form_field(&form_data, "access_token")?;
     ensure_account_authentication(username, access_token).await?;
+    rate_limit.check_account(username).await?;
     
-    let mod_id: &String = get_text_form_field(&form_data, "mod_id").map_err(err_400)?;
-    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|e| format!("Invalid Mod UUID: {e}")).map_err(err_400)?;
+    let mod_id: &String = get_text_form_field(&form_data, "mod_id")?;
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
     ensure_mod_authorization(mod_id, username).await?;
     
     let file_data: Option<&Vec<u8>> = get_bytes_form_field_opt(&form_data, "file_data");
     let description: Option<&String> = get_text_form_field_opt(&form_data, "description");
+    let language: Option<&String> = get_text_form_field_opt(&form_data, "language");
+    let tags: Option<&String> = get_text_form_field_opt(&form_data, "tags");
+    let categories: Option<&String> = get_text_form_field_opt(&form_data, "categories");
     
-    if file_data.is_none() && description.is_none() {
-        return Err(respond_err(Status::BadRequest, "Nothing to update"))
+    if file_data.is_none() && description.is_none() && language.is_none() && tags.is_none() && categories.is_none() {
+        return Err(ApiError::NothingToUpdate)
     }
     
-    let description: Option<String> = if let Some(desc) = description {
-        Some(sanitize_string(desc).ok_or_else(|| respond_err(Status::BadRequest, "Invalid description"))?)
-    } else { None };
+    let description: Option<String> = description.map(|description| validate_description(description)).transpose()?;
+    if let Some(language) = language && !is_language_code(language) {
+        return Err(ApiError::InvalidLanguage)
+    }
+    let tags: Option<Vec<String>> = match tags {
+        Some(tags) => Some(resolve_aliases(parse_tags(tags)?).await?),
+        None => None,
+    };
+    let categories: Option<Vec<ModCategory>> = categories.map(|categories| parse_categories(categories)).transpose()?;
+
+    // only a new file or description is a new version of the mod; tags, categories and the language are just metadata
+    let content_changed: bool = file_data.is_some() || description.is_some();
+
+    // rejected mods get resubmitted by updating them; new uploaders' new versions have to be reviewed again,
+    // but their metadata changes do not take an approved mod offline
+    let current_review_state: ReviewState = get_mod_review_state(mod_id).await?;
+    if current_review_state == ReviewState::Removed {
+        return Err(ApiError::ModRemoved)
+    }
+    let upload_review_state: ReviewState = review_state_for_upload(username).await?;
+    let needs_review: bool = current_review_state != ReviewState::Approved
+        || (content_changed && upload_review_state == ReviewState::PendingReview);
+    let columns_changed: bool = content_changed || language.is_some() || needs_review;
 
     let mut query = QueryBuilder::new("UPDATE mods SET ");
     let mut separated = query.separated(", ");
     if let Some(file_data) = file_data {
-        separated.push("file_data=").push_bind(file_data);
+        separated.push("file_data=").push_bind_unseparated(file_data);
     }
     if let Some(desc) = description {
-        separated.push("description=").push_bind(desc);
+        separated.push("description_html=").push_bind_unseparated(render_description(&desc));
+        separated.push("description=").push_bind_unseparated(desc);
+    }
+    if let Some(language) = language {
+        separated.push("language=").push_bind_unseparated(language);
+    }
+    if needs_review {
+        separated.push("review_state=").push_bind_unseparated(ReviewState::PendingReview.as_str());
+        separated.push("review_reason=NULL");
+    }
+    if content_changed {
+        separated.push("mod_version = mod_version + 1");
+        separated.push("updated_at = NOW()");
     }
-    separated.push("mod_version = mod_version + 1");
     
     query.push(" WHERE id=").push_bind(mod_id);
-    query.build().execute(pool()).await.map_err(|e| respond_err(
-        Status::InternalServerError,
-        &format!("Could not update mod: {e}"))
-    )?;
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for update: {e}")))?;
+    if columns_changed {
+        query.build().execute(&mut *transaction).await
+            .map_err(|e| ApiError::Internal(format!("Could not update mod: {e}")))?;
+    }
+    if let Some(tags) = &tags {
+        set_mod_tags(&mut transaction, mod_id, tags).await?;
+    }
+    if let Some(categories) = &categories {
+        set_mod_categories(&mut transaction, mod_id, categories).await?;
+    }
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit update of mod {mod_id}: {e}")))?;
+    if let Some(file_data) = file_data {
+        record_upload("update", file_data.len());
+    }
 
     respond_ok_empty()
 }
 
 
+#[utoipa::path(
+    tag = "mods",
+    request_body(content = DeleteModForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = 400, description = "`malformed_request` or `invalid_mod_id`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_mod_owner`", body = ErrorResponse),
+    ),
+)]
 #[delete("/mod", data = "<data>")]
-pub async fn api_delete_mod(content_type: &ContentType, data: Data<'_>) -> ApiResponse {
-    let err_400 = |e: String| respond_err(Status::BadRequest, &e);
+pub async fn api_delete_mod(rate_limit: RateLimit<'_, UploadRoutes>, content_type: &ContentType, data: Data<'_>) -> ApiResponse {
     info!("Handling `DELETE` mod");
     
     let form_options = MultipartFormDataOptions::with_multipart_form_data_fields(vec![
@@ -163,58 +406,341 @@ pub async fn api_delete_mod(content_type: &ContentType, data: Data<'_>) -> ApiRe
         MultipartFormDataField::text("mod_id"),
     ]);
     let form_data: MultipartFormData = MultipartFormData::parse(content_type, data, form_options).await
-        .map_err(|e| format!("Could not parse form data: {e}")).map_err(err_400)?;
+        .map_err(|e| ApiError::MalformedRequest(format!("Could not parse form data: {e}")))?;
 
-    let username: &String = get_text_form_field(&form_data, "username").map_err(err_400)?;
-    let access_token: &String = get_text_form_field(&form_data, "access_token").map_err(err_400)?;
+    let username: &String = get_text_form_field(&form_data, "username")?;
+    let access_token: &String = get_text_form_field(&form_data, "access_token")?;
     ensure_account_authentication(username, access_token).await?;
+    rate_limit.check_account(username).await?;
 
-    let mod_id: &String = get_text_form_field(&form_data, "mod_id").map_err(err_400)?;
-    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|e| format!("Invalid Mod UUID: {e}")).map_err(err_400)?;
+    let mod_id: &String = get_text_form_field(&form_data, "mod_id")?;
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
     ensure_mod_authorization(mod_id, username).await?;
+    delete_mod(mod_id).await?;
+
+    respond_ok_empty()
+}
+
+
+/// Approved mods, newest first, 50 per page.
+#[utoipa::path(
+    tag = "mods",
+    params(("page" = Option<u32>, Query, description = "Zero based page number")),
+    responses((status = 200, description = "Mods", body = ModList)),
+)]
+#[get("/mods?<page>")]
+pub async fn api_list_mods(_rate_limit: RateLimit<'_, ReadRoutes>, page: Option<u32>) -> ApiResponse {
+    let offset: i64 = i64::from(page.unwrap_or(0)) * MODS_PER_PAGE;
+    let mut query = select_mod_summaries();
+    query.push(" WHERE review_state = 'approved' ORDER BY created_at DESC LIMIT ").push_bind(MODS_PER_PAGE)
+        .push(" OFFSET ").push_bind(offset);
+    let mods: Vec<ModSummary> = fetch_mod_summaries(pool(), query)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not list mods: {e}")))?;
+
+    respond_ok_value(json!(ModList { mods }))
+}
+
+
+/// Full text search over titles and descriptions of approved mods, with filters; the 50 best matches and facet counts.
+#[utoipa::path(
+    tag = "mods",
+    params(
+        ("query" = String, Query, description = "Search terms; empty to only filter"),
+        ("mode" = Option<SearchMode>, Query, description = "How the terms are matched; `basic` by default"),
+        ("sort" = Option<SearchSort>, Query, description = "Order of the results; `relevance` by default"),
+        ("game" = Option<String>, Query, description = "`Undertale` or `Deltarune`"),
+        ("game_version" = Option<String>, Query, description = "`major.minor`; mods made for this version of the game"),
+        ("tags" = Option<String>, Query, description = "Comma separated; mods that have all of these tags"),
+        ("author" = Option<String>, Query, description = "Username of the author"),
+        ("updated_since" = Option<DateTime<Utc>>, Query, description = "Mods whose file or description changed at or after this time (RFC 3339)"),
+        ("language" = Option<String>, Query, description = "ISO 639-1 code of the language the terms are written in, for stemming them; `en` by default"),
+    ),
+    responses(
+        (status = 200, description = "Mods in the requested order", body = ModSearchResults),
+        (status = 400, description = "`invalid_search_parameter`", body = ErrorResponse),
+    ),
+)]
+#[get("/mods/search?<query>&<mode>&<sort>&<game>&<game_version>&<tags>&<author>&<updated_since>&<language>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn api_search_mods(
+    _rate_limit: RateLimit<'_, ReadRoutes>,
+    query: &str,
+    mode: Option<&str>,
+    sort: Option<&str>,
+    game: Option<&str>,
+    game_version: Option<&str>,
+    tags: Option<&str>,
+    author: Option<&str>,
+    updated_since: Option<&str>,
+    language: Option<&str>,
+) -> ApiResponse {
+    info!("Handling `GET mods/search` with query \"{query}\" ({})", mode.unwrap_or("basic"));
+    let mode: SearchMode = mode.map(SearchMode::from_str).transpose()
+        .map_err(ApiError::InvalidSearchParameter)?
+        .unwrap_or_default();
+    let sort: SearchSort = sort.map(SearchSort::from_str).transpose()
+        .map_err(ApiError::InvalidSearchParameter)?
+        .unwrap_or_default();
+
+    if let Some(game) = game && !is_known_game(game) {
+        return Err(ApiError::InvalidSearchParameter(format!("Unknown game \"{game}\"")))
+    }
+    let game_version: Option<(i32, i32)> = game_version
+        .map(|version| parse_game_version(version)
+            .ok_or_else(|| ApiError::InvalidSearchParameter("`game_version` has to be `major.minor`".to_string())))
+        .transpose()?;
+    let updated_since: Option<DateTime<Utc>> = updated_since
+        .map(|time| DateTime::parse_from_rfc3339(time)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|e| ApiError::InvalidSearchParameter(format!("`updated_since` is not an RFC 3339 time: {e}"))))
+        .transpose()?;
+    let language: &str = language.unwrap_or("en");
+    if !is_language_code(language) {
+        return Err(ApiError::InvalidSearchParameter("`language` has to be a two letter ISO 639-1 code".to_string()))
+    }
+    let tags: Vec<String> = tags.unwrap_or_default()
+        .split(',')
+        .filter(|tag| !tag.trim().is_empty())
+        .map(|tag| normalize_tag(tag).map_err(|e| ApiError::InvalidSearchParameter(e.message())))
+        .collect::<Result<_, _>>()?;
+    let filters = SearchFilters {
+        game_name: game.map(str::to_string),
+        game_version,
+        tags: resolve_aliases(tags).await?,
+        author: author.map(str::to_string),
+        updated_since,
+    };
+
+    let results: ModSearchResults = search(pool(), query, mode, language, sort, &filters).await?;
+    respond_ok_value(json!(results))
+}
+
+
+/// Completions for a search box: titles and authors that start with what has been typed so far.
+#[utoipa::path(
+    tag = "mods",
+    params(("q" = String, Query, description = "The text typed so far; case does not matter")),
+    responses(
+        (status = 200, description = "Up to 5 titles and 5 authors; both empty for empty text", body = ModSuggestions),
+        (status = 400, description = "`invalid_search_parameter`", body = ErrorResponse),
+    ),
+)]
+#[get("/mods/suggest?<q>")]
+pub async fn api_suggest_mods(_rate_limit: RateLimit<'_, ReadRoutes>, q: &str) -> ApiResponse {
+    info!("Handling `GET mods/suggest` with \"{q}\"");
+    let prefix: &str = q.trim_start();
+    if prefix.chars().count() > MAX_SUGGEST_PREFIX_LENGTH {
+        return Err(ApiError::InvalidSearchParameter(format!("`q` can not be longer than {MAX_SUGGEST_PREFIX_LENGTH} characters")))
+    }
+    if prefix.is_empty() {
+        return respond_ok_value(json!(ModSuggestions::default()))
+    }
+
+    let suggestions: ModSuggestions = suggest(pool(), prefix).await?;
+    respond_ok_value(json!(suggestions))
+}
+
+
+#[utoipa::path(
+    tag = "mods",
+    params(("mod_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The mod", body = ModSummary),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 404, description = "`mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/mod/<mod_id>")]
+pub async fn api_get_mod(_rate_limit: RateLimit<'_, ReadRoutes>, mod_id: &str) -> ApiResponse {
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    let mut query = select_mod_summaries();
+    query.push(" WHERE id = ").push_bind(mod_id).push(" AND review_state = 'approved'");
+    let mod_summary: ModSummary = fetch_mod_summaries(pool(), query)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch mod: {e}")))?
+        .pop()
+        .ok_or(ApiError::ModNotFound)?;
+
+    respond_ok_value(json!(mod_summary))
+}
+
+
+#[utoipa::path(
+    tag = "mods",
+    params(("mod_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The mod file", content_type = "application/octet-stream", body = ModFile),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 404, description = "`mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/mod/<mod_id>/download")]
+pub async fn api_download_mod(_rate_limit: RateLimit<'_, ReadRoutes>, mod_id: &str) -> Result<(ContentType, Vec<u8>), ApiError> {
+    info!("Handling `GET mod/{mod_id}/download`");
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    let file_data: Vec<u8> = sqlx::query_scalar!(
+        r#"
+        SELECT file_data
+        FROM mods
+        WHERE id = $1 AND review_state = 'approved'
+        "#,
+        mod_id,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch mod file: {e}")))?
+        .ok_or(ApiError::ModNotFound)?;
+
+    record_download(mod_id).await?;
+    Ok((ContentType::Binary, file_data))
+}
+
+
+/// Post request because the credentials are sent in the json body.
+///
+/// All mods of the account including unapproved ones, newest first.
+#[utoipa::path(
+    tag = "mods",
+    request_body = OwnModsRequest,
+    responses(
+        (status = 200, description = "Own mods with their review state", body = OwnModList),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+    ),
+)]
+#[post("/my_mods", data="<request_data>")]
+pub async fn api_get_own_mods(_rate_limit: RateLimit<'_, ReadRoutes>, request_data: Json<OwnModsRequest>) -> ApiResponse {
+    info!("Handling `POST my_mods` for user {}", request_data.username);
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+
+    let mods: Vec<OwnModStatus> = sqlx::query_as!(
+        OwnModStatus,
+        r#"
+        SELECT id, title, mod_version, review_state, review_reason, reviewed_at, created_at
+        FROM mods
+        WHERE author = $1
+        ORDER BY created_at DESC
+        "#,
+        request_data.username,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch mods of user: {e}")))?;
+
+    respond_ok_value(json!(OwnModList { mods }))
+}
+
+/// Rates an approved mod of another account with 1 to 5 stars; rating again replaces the earlier rating.
+/// The credentials are sent in the json body.
+#[utoipa::path(
+    tag = "mods",
+    params(("mod_id" = Uuid, Path)),
+    request_body = RateModRequest,
+    responses(
+        (status = 200, description = "The ratings of the mod, including this one", body = ModRatings),
+        (status = 400, description = "`invalid_mod_id` or `invalid_rating`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`own_mod_rating`", body = ErrorResponse),
+        (status = 404, description = "`mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[put("/mod/<mod_id>/rating", data = "<request_data>")]
+pub async fn api_rate_mod(rate_limit: RateLimit<'_, UploadRoutes>, mod_id: &str, request_data: Json<RateModRequest>) -> ApiResponse {
+    info!("Handling `PUT mod/{mod_id}/rating` for user {}", request_data.username);
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    if !(1..=5).contains(&request_data.rating) {
+        return Err(ApiError::InvalidRating)
+    }
+
+    // the mod row is locked before the rating is saved, so concurrent ratings of the same mod take turns;
+    // the totals are only recounted once the ratings before this one are committed and visible to the recount
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for rating: {e}")))?;
+    let author: String = sqlx::query_scalar!(
+        r#"
+        SELECT author
+        FROM mods
+        WHERE id = $1 AND review_state = 'approved'
+        FOR UPDATE
+        "#,
+        mod_id,
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch mod to rate: {e}")))?
+        .ok_or(ApiError::ModNotFound)?;
+    if author == request_data.username {
+        return Err(ApiError::OwnModRating)
+    }
 
     sqlx::query!(
         r#"
-        DELETE FROM mods
+        INSERT INTO mod_ratings (mod_id, username, rating)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (mod_id, username) DO UPDATE SET rating = EXCLUDED.rating, rated_at = NOW()
+        "#,
+        mod_id,
+        request_data.username,
+        request_data.rating,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not save rating: {e}")))?;
+    let ratings: ModRatings = sqlx::query_as!(
+        ModRatings,
+        r#"
+        UPDATE mods
+        SET rating_count = totals.count, rating_sum = totals.sum
+        FROM (
+            SELECT COUNT(*) AS count, COALESCE(SUM(rating), 0) AS sum
+            FROM mod_ratings
+            WHERE mod_id = $1
+        ) AS totals
         WHERE id = $1
+        RETURNING rating_sum::FLOAT8 / NULLIF(rating_count, 0) AS rating_average, rating_count
         "#,
         mod_id,
     )
-        .execute(pool())
+        .fetch_one(&mut *transaction)
         .await
-        .map_err(|e| respond_err(Status::InternalServerError, &format!("Could not delete mod: {e}")))?;
+        .map_err(|e| ApiError::Internal(format!("Could not update rating totals: {e}")))?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit rating: {e}")))?;
 
-    respond_ok_empty()
+    info!("User {} rated mod {mod_id} with {} stars", request_data.username, request_data.rating);
+    respond_ok_value(json!(ratings))
 }
 
-pub fn get_text_form_field<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Result<&'a String, String> {
+pub fn get_text_form_field<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Result<&'a String, ApiError> {
     form_data.texts.get(field_name)
-        .and_then(|i| i.get(0))
+        .and_then(|i| i.first())
         .map(|field| &field.text)
-        .ok_or_else(|| format!("Text field `{field_name}` missing from request form!"))
+        .ok_or_else(|| ApiError::MalformedRequest(format!("Text field `{field_name}` missing from request form!")))
 }
 
 pub fn get_text_form_field_opt<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Option<&'a String> {
     form_data.texts.get(field_name)
-        .and_then(|i| i.get(0))
+        .and_then(|i| i.first())
         .map(|field| &field.text)
 }
 
-pub fn get_bytes_form_field<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Result<&'a Vec<u8>, String> {
+pub fn get_bytes_form_field<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Result<&'a Vec<u8>, ApiError> {
     form_data.raw.get(field_name)
-        .and_then(|i| i.get(0))
+        .and_then(|i| i.first())
         .map(|field| &field.raw)
-        .ok_or_else(|| format!("Raw field `{field_name}` missing from request form!"))
+        .ok_or_else(|| ApiError::MalformedRequest(format!("Raw field `{field_name}` missing from request form!")))
 }
 
 pub fn get_bytes_form_field_opt<'a>(form_data: &'a MultipartFormData, field_name: &str) -> Option<&'a Vec<u8>> {
     form_data.raw.get(field_name)
-        .and_then(|i| i.get(0))
+        .and_then(|i| i.first())
         .map(|field| &field.raw)
 }
 
 
-async fn ensure_mod_authorization(mod_id: Uuid, username: &str) -> ApiResponse {
+pub async fn ensure_mod_authorization(mod_id: Uuid, username: &str) -> Result<(), ApiError> {
     let exists: bool = sqlx::query_scalar!(
         r#"
         SELECT EXISTS(
@@ -228,12 +754,132 @@ async fn ensure_mod_authorization(mod_id: Uuid, username: &str) -> ApiResponse {
     )
         .fetch_one(pool())
         .await
-        .map_err(|e| respond_err(Status::InternalServerError, &format!("Could not verify mod ownership: {e}")))?
-        .ok_or_else(|| respond_err(Status::InternalServerError, "The specified mod or user does not exist."))?;
+        .map_err(|e| ApiError::Internal(format!("Could not verify mod ownership: {e}")))?
+        .ok_or_else(|| ApiError::Internal("EXISTS query returned NULL while verifying mod ownership".to_string()))?;
 
     if !exists {
-        return Err(respond_err(Status::Forbidden, "Unauthorized; you do not have permission to modify this mod"))
+        return Err(ApiError::NotModOwner)
     }
-    respond_ok_empty()
+    Ok(())
+}
+
+
+/// Deletes the mod for good, no matter who it belongs to.
+pub async fn delete_mod(mod_id: Uuid) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM mods
+        WHERE id = $1
+        "#,
+        mod_id,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not delete mod: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::ModNotFound)
+    }
+    Ok(())
+}
+
+
+/// Takes the mod down without deleting it; the author sees the reason in `my_mods`.
+pub async fn remove_mod(mod_id: Uuid, reason: Option<&str>) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mods
+        SET review_state = 'removed', review_reason = $2, reviewed_by = NULL, reviewed_at = NOW()
+        WHERE id = $1
+        "#,
+        mod_id,
+        reason,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove mod {mod_id}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::ModNotFound)
+    }
+    Ok(())
+}
+
+
+/// Brings a removed mod back; it is approved afterwards, whatever its state was before.
+pub async fn restore_mod(mod_id: Uuid) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mods
+        SET review_state = 'approved', review_reason = NULL, reviewed_by = NULL, reviewed_at = NOW()
+        WHERE id = $1 AND review_state = 'removed'
+        "#,
+        mod_id,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not restore mod {mod_id}: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::ModNotFound)
+    }
+    Ok(())
+}
+
+
+/// Only appends an event; the `download_count_rollup` job adds them up, so downloads do not fight over the mod row.
+async fn record_download(mod_id: Uuid) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO mod_download_events (mod_id)
+        VALUES ($1)
+        "#,
+        mod_id,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not record download of mod {mod_id}: {e}")))?;
+    Ok(())
 }
 
+
+/// Moves recorded downloads into `mods.download_count`; returns how many mods got new downloads.
+pub async fn roll_up_download_counts() -> Result<u64, ApiError> {
+    let result = sqlx::query!(
+        r#"
+        WITH events AS (
+            DELETE FROM mod_download_events
+            RETURNING mod_id
+        ), counts AS (
+            SELECT mod_id, COUNT(*) AS downloads
+            FROM events
+            GROUP BY mod_id
+        )
+        UPDATE mods
+        SET download_count = download_count + counts.downloads
+        FROM counts
+        WHERE mods.id = counts.mod_id
+        "#,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not roll up download counts: {e}")))?;
+    Ok(result.rows_affected())
+}
+
+
+/// Deletes mods that were removed by an operator at least `days` ago; returns how many.
+pub async fn purge_removed_mods(days: i64) -> Result<u64, ApiError> {
+    let removed_before: DateTime<Utc> = Utc::now() - Duration::days(days);
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM mods
+        WHERE review_state = 'removed' AND reviewed_at < $1
+        "#,
+        removed_before,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not purge removed mods: {e}")))?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,92 @@
+use std::sync::LazyLock;
+use rocket::http::ContentType;
+use rocket::response::Redirect;
+use utoipa::OpenApi;
+
+
+/// The OpenAPI document of `/api/v1`, generated from the route definitions.
+///
+/// A copy is checked in at `docs/openapi.json` so changes to the api show up in review;
+/// `tests/openapi.rs` fails if it is out of date.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "AcornGM API",
+        description = "Mod hosting and accounts for AcornGM.\n\n\
+            Credentials (`username` and `access_token`) are sent in the request body. \
+            Every route can also respond with `429 rate_limited` and `500 internal_error`; \
+            all error codes are listed in `docs/error_codes.md`.",
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        crate::login::api_get_auth_providers,
+        crate::login::api_get_provider_auth,
+        crate::login::api_get_discord_auth,
+        crate::login::api_post_register,
+        crate::login::api_post_temp_login,
+        crate::login::api_get_access_token,
+        crate::login::api_get_identities,
+        crate::login::api_link_identity,
+        crate::login::api_unlink_identity,
+        crate::login::api_start_identity_move,
+        crate::login::api_confirm_identity_move,
+        crate::login::api_get_account,
+        crate::mods::api_upload_mod,
+        crate::mods::api_update_mod,
+        crate::mods::api_delete_mod,
+        crate::mods::api_list_mods,
+        crate::mods::api_search_mods,
+        crate::mods::api_suggest_mods,
+        crate::mods::api_get_mod,
+        crate::mods::api_download_mod,
+        crate::mods::api_get_own_mods,
+        crate::mods::api_rate_mod,
+        crate::tags::api_list_tags,
+        crate::tags::api_list_mods_with_tag,
+        crate::tags::api_list_mods_in_category,
+        crate::tags::api_set_canonical_tag,
+        crate::tags::api_remove_canonical_tag,
+        crate::screenshots::api_upload_screenshot,
+        crate::screenshots::api_list_screenshots,
+        crate::screenshots::api_get_screenshot,
+        crate::screenshots::api_get_screenshot_thumbnail,
+        crate::screenshots::api_arrange_screenshots,
+        crate::screenshots::api_delete_screenshot,
+        crate::review::api_get_pending_mods,
+        crate::review::api_download_pending_mod,
+        crate::review::api_approve_mod,
+        crate::review::api_reject_mod,
+    ),
+    tags(
+        (name = "auth", description = "Logging in with a login provider"),
+        (name = "identities", description = "Login provider identities linked to an account"),
+        (name = "accounts"),
+        (name = "mods"),
+        (name = "tags", description = "Categories and tags of mods; canonical tags are managed by moderators"),
+        (name = "screenshots", description = "Screenshot galleries of mods"),
+        (name = "review", description = "Pre-publication review; moderators only"),
+    ),
+)]
+struct ApiDoc;
+
+static SPEC: LazyLock<String> = LazyLock::new(|| ApiDoc::openapi()
+    .to_pretty_json()
+    .expect("Could not serialize OpenAPI document"));
+
+/// The pretty printed OpenAPI document.
+pub fn openapi_json() -> &'static str {
+    &SPEC
+}
+
+
+#[get("/openapi.json")]
+pub async fn api_get_openapi() -> (ContentType, &'static str) {
+    (ContentType::JSON, openapi_json())
+}
+
+
+/// rendered by `frontend/api_docs.html`
+#[get("/docs")]
+pub async fn redirect_api_docs() -> Redirect {
+    Redirect::to("/api_docs.html")
+}
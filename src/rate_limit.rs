@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use rocket::fairing::AdHoc;
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use serde::Deserialize;
+use crate::{app_config, pool};
+use crate::api_error::ApiError;
+use crate::config::RateLimitBackend;
+
+
+/// Token bucket parameters; a bucket starts full and holds at most `capacity` tokens.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Budget {
+    pub capacity: f64,
+    pub refill_per_second: f64,
+}
+
+/// Every rate limited route belongs to exactly one class. Each class has its own buckets,
+/// so exhausting the read budget does not lock anyone out of logging in.
+pub trait RouteClass: Send + Sync + 'static {
+    const NAME: &'static str;
+    fn budget() -> Budget;
+}
+
+pub struct AuthRoutes;
+pub struct UploadRoutes;
+pub struct ReadRoutes;
+
+impl RouteClass for AuthRoutes {
+    const NAME: &'static str = "auth";
+    fn budget() -> Budget {
+        app_config().rate_limit.auth
+    }
+}
+
+impl RouteClass for UploadRoutes {
+    const NAME: &'static str = "upload";
+    fn budget() -> Budget {
+        app_config().rate_limit.upload
+    }
+}
+
+impl RouteClass for ReadRoutes {
+    const NAME: &'static str = "read";
+    fn budget() -> Budget {
+        app_config().rate_limit.read
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+struct Decision {
+    allowed: bool,
+    limit: u64,
+    remaining: u64,
+    /// seconds until the bucket is full again
+    reset_after: u64,
+    /// seconds until the next request would be allowed
+    retry_after: u64,
+}
+
+impl Decision {
+    fn from_tokens(allowed: bool, tokens: f64, budget: Budget) -> Self {
+        let missing: f64 = (budget.capacity - tokens).max(0.0);
+        Decision {
+            allowed,
+            limit: budget.capacity as u64,
+            remaining: tokens.max(0.0).floor() as u64,
+            reset_after: (missing / budget.refill_per_second).ceil() as u64,
+            retry_after: ((1.0 - tokens).max(0.0) / budget.refill_per_second).ceil() as u64,
+        }
+    }
+
+    fn allow_unlimited() -> Self {
+        Decision { allowed: true, limit: 0, remaining: 0, reset_after: 0, retry_after: 0 }
+    }
+}
+
+
+struct MemoryBucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// the budget of the bucket's route class, so sweeps can tell whether it is full
+    budget: Budget,
+}
+
+impl MemoryBucket {
+    fn refilled(&self, now: Instant) -> f64 {
+        let elapsed: f64 = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        (self.tokens + elapsed * self.budget.refill_per_second).min(self.budget.capacity)
+    }
+}
+
+struct MemoryBuckets {
+    buckets: HashMap<String, MemoryBucket>,
+    last_sweep: Instant,
+}
+
+enum Backend {
+    /// buckets only live in this process; fine as long as there is one instance
+    Memory(Mutex<MemoryBuckets>),
+    /// buckets are shared by all instances through the `rate_limit_buckets` table
+    Postgres,
+}
+
+/// Full buckets are dropped once the map grows past this many keys...
+const MAX_MEMORY_BUCKETS: usize = 50_000;
+/// ...but at most this often, so a map of buckets that are all in use is not scanned on every request.
+const MEMORY_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+static BACKEND: LazyLock<Backend> = LazyLock::new(|| {
+    match app_config().rate_limit.backend {
+        RateLimitBackend::Memory => Backend::Memory(Mutex::new(MemoryBuckets { buckets: HashMap::new(), last_sweep: Instant::now() })),
+        RateLimitBackend::Postgres => Backend::Postgres,
+    }
+});
+
+
+async fn take_token(key: &str, budget: Budget) -> Decision {
+    match &*BACKEND {
+        Backend::Memory(buckets) => take_token_memory(buckets, key, budget),
+        Backend::Postgres => take_token_postgres(key, budget).await.unwrap_or_else(|e| {
+            // rather let requests through than take the whole api down with the database
+            error!("{e}");
+            Decision::allow_unlimited()
+        }),
+    }
+}
+
+fn take_token_memory(buckets: &Mutex<MemoryBuckets>, key: &str, budget: Budget) -> Decision {
+    let now = Instant::now();
+    let mut buckets = buckets.lock().unwrap_or_else(|e| e.into_inner());
+    if buckets.buckets.len() > MAX_MEMORY_BUCKETS && now.saturating_duration_since(buckets.last_sweep) >= MEMORY_SWEEP_INTERVAL {
+        buckets.buckets.retain(|_, bucket| bucket.refilled(now) < bucket.budget.capacity);
+        buckets.last_sweep = now;
+    }
+
+    let bucket = buckets.buckets.entry(key.to_string()).or_insert(MemoryBucket { tokens: budget.capacity, updated_at: now, budget });
+    let tokens: f64 = bucket.refilled(now);
+    let allowed: bool = tokens >= 1.0;
+    bucket.tokens = if allowed { tokens - 1.0 } else { tokens };
+    bucket.updated_at = now;
+
+    Decision::from_tokens(allowed, bucket.tokens, budget)
+}
+
+async fn take_token_postgres(key: &str, budget: Budget) -> Result<Decision, String> {
+    // the row lock taken by the upsert makes this atomic across instances;
+    // no row is returned if the bucket does not have a token left
+    let tokens_left: Option<f64> = sqlx::query_scalar!(
+        r#"
+        INSERT INTO rate_limit_buckets AS bucket (key, tokens, updated_at)
+        VALUES ($1, $2::float8 - 1, NOW())
+        ON CONFLICT (key) DO UPDATE SET
+            tokens = LEAST($2::float8, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::float8 * $3::float8) - 1,
+            updated_at = NOW()
+        WHERE LEAST($2::float8, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::float8 * $3::float8) >= 1
+        RETURNING tokens
+        "#,
+        key,
+        budget.capacity,
+        budget.refill_per_second,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| format!("Could not take rate limit token for {key}: {e}"))?;
+
+    if let Some(tokens) = tokens_left {
+        return Ok(Decision::from_tokens(true, tokens, budget))
+    }
+
+    let tokens: Option<f64> = sqlx::query_scalar!(
+        r#"
+        SELECT LEAST($2::float8, tokens + EXTRACT(EPOCH FROM NOW() - updated_at)::float8 * $3::float8)
+        FROM rate_limit_buckets
+        WHERE key = $1
+        "#,
+        key,
+        budget.capacity,
+        budget.refill_per_second,
+    )
+        .fetch_one(pool())
+        .await
+        .map_err(|e| format!("Could not read rate limit bucket for {key}: {e}"))?;
+
+    Ok(Decision::from_tokens(false, tokens.unwrap_or(0.0), budget))
+}
+
+
+/// Remembers the most restrictive decision made for a request so the
+/// `X-RateLimit-*` headers can be attached to whatever response comes out of it.
+#[derive(Default)]
+struct RequestDecision(Mutex<Option<Decision>>);
+
+impl RequestDecision {
+    fn record(&self, decision: Decision) {
+        let mut current = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let is_more_restrictive: bool = match *current {
+            None => true,
+            Some(current) => !decision.allowed || (current.allowed && decision.remaining < current.remaining),
+        };
+        if is_more_restrictive {
+            *current = Some(decision);
+        }
+    }
+
+    fn get(&self) -> Option<Decision> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+
+/// Request guard taking one token from the client IP's bucket of the route class.
+/// The IP is the peer's unless Rocket's `ip_header` is set, which only belongs behind a proxy (see `Rocket.toml`).
+/// Fails with 429 (handled by `api_catch_429`) when the bucket is empty.
+pub struct RateLimit<'r, C: RouteClass> {
+    request_decision: &'r RequestDecision,
+    _class: PhantomData<C>,
+}
+
+impl<C: RouteClass> RateLimit<'_, C> {
+    /// Per-account budget; handlers call this once they know which account the request is for.
+    pub async fn check_account(&self, username: &str) -> Result<(), ApiError> {
+        let decision: Decision = take_token(&format!("{}:account:{username}", C::NAME), C::budget()).await;
+        self.request_decision.record(decision);
+        if !decision.allowed {
+            return Err(ApiError::AccountRateLimited)
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, C: RouteClass> FromRequest<'r> for RateLimit<'r, C> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let request_decision: &'r RequestDecision = req.local_cache(RequestDecision::default);
+        let client_ip: String = req.client_ip().map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+
+        let decision: Decision = take_token(&format!("{}:ip:{client_ip}", C::NAME), C::budget()).await;
+        request_decision.record(decision);
+        if !decision.allowed {
+            warn!("Rate limited {client_ip} on {} routes", C::NAME);
+            return Outcome::Error((Status::TooManyRequests, ()))
+        }
+
+        Outcome::Success(RateLimit { request_decision, _class: PhantomData })
+    }
+}
+
+
+/// Attaches `X-RateLimit-*` headers (and `Retry-After` on 429) to rate limited responses.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_response("Rate limit headers", |req, res| Box::pin(async move {
+        let Some(decision) = req.local_cache(RequestDecision::default).get() else {
+            return
+        };
+        if decision.limit == 0 {
+            return  // limiter failed open; there is nothing meaningful to report
+        }
+
+        res.set_header(Header::new("X-RateLimit-Limit", decision.limit.to_string()));
+        res.set_header(Header::new("X-RateLimit-Remaining", decision.remaining.to_string()));
+        res.set_header(Header::new("X-RateLimit-Reset", decision.reset_after.to_string()));
+        if !decision.allowed {
+            res.set_header(Header::new("Retry-After", decision.retry_after.max(1).to_string()));
+        }
+    }))
+}
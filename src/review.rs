@@ -0,0 +1,284 @@
+use std::str::FromStr;
+use chrono::{Duration, Utc};
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use crate::{app_config, pool, respond_ok_empty, respond_ok_value, ApiResponse};
+use crate::api_error::ApiError;
+use crate::accounts::{ensure_account_authentication, get_account, get_account_role};
+use crate::rate_limit::{RateLimit, ReadRoutes, UploadRoutes};
+use crate::sanitize::{grapheme_length, sanitize_string};
+use acorngm_api_client::error::ErrorResponse;
+use acorngm_api_client::mods::ModFile;
+use acorngm_api_client::review::{ModeratorRequest, PendingMod, PendingModList, ReviewDecisionRequest};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewState {
+    PendingReview,
+    Approved,
+    Rejected,
+    /// taken down by an operator; only an operator can bring it back
+    Removed,
+}
+
+impl ReviewState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewState::PendingReview => "pending_review",
+            ReviewState::Approved => "approved",
+            ReviewState::Rejected => "rejected",
+            ReviewState::Removed => "removed",
+        }
+    }
+}
+
+impl FromStr for ReviewState {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "pending_review" => Ok(ReviewState::PendingReview),
+            "approved" => Ok(ReviewState::Approved),
+            "rejected" => Ok(ReviewState::Rejected),
+            "removed" => Ok(ReviewState::Removed),
+            other => Err(format!("Unknown review state \"{other}\"")),
+        }
+    }
+}
+
+
+/// Uploads by accounts below either threshold are held back for review by a moderator.
+/// Review is disabled entirely if neither threshold is configured.
+#[derive(Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReviewPolicy {
+    pub min_account_age_days: Option<i64>,
+    pub min_approved_mods: Option<i64>,
+}
+
+impl ReviewPolicy {
+    fn is_enabled(&self) -> bool {
+        self.min_account_age_days.is_some() || self.min_approved_mods.is_some()
+    }
+}
+
+const MAX_REJECTION_REASON_LENGTH: usize = 1024;
+
+
+/// decides whether a mod uploaded (or changed) by this user goes live right away
+pub async fn review_state_for_upload(username: &str) -> Result<ReviewState, ApiError> {
+    let policy: &ReviewPolicy = &app_config().review;
+    if !policy.is_enabled() {
+        return Ok(ReviewState::Approved)
+    }
+    if get_account_role(username).await?.is_moderator() {
+        return Ok(ReviewState::Approved)
+    }
+
+    if let Some(min_age_days) = policy.min_account_age_days {
+        let account = get_account(username).await?;
+        if Utc::now() - account.created_at < Duration::days(min_age_days) {
+            return Ok(ReviewState::PendingReview)
+        }
+    }
+
+    if let Some(min_approved_mods) = policy.min_approved_mods
+        && count_approved_mods(username).await? < min_approved_mods {
+        return Ok(ReviewState::PendingReview)
+    }
+
+    Ok(ReviewState::Approved)
+}
+
+async fn count_approved_mods(username: &str) -> Result<i64, ApiError> {
+    let count: Option<i64> = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM mods
+        WHERE author = $1 AND review_state = 'approved'
+        "#,
+        username,
+    )
+        .fetch_one(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not count approved mods of user {username}: {e}")))?;
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn get_mod_review_state(mod_id: Uuid) -> Result<ReviewState, ApiError> {
+    let review_state: String = sqlx::query_scalar!(
+        r#"
+        SELECT review_state
+        FROM mods
+        WHERE id = $1
+        "#,
+        mod_id,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not get review state of mod {mod_id}: {e}")))?
+        .ok_or(ApiError::ModNotFound)?;
+    ReviewState::from_str(&review_state).map_err(ApiError::Internal)
+}
+
+
+pub async fn ensure_moderator(username: &str, access_token: &str) -> Result<(), ApiError> {
+    ensure_account_authentication(username, access_token).await?;
+    if !get_account_role(username).await?.is_moderator() {
+        return Err(ApiError::NotModerator)
+    }
+    Ok(())
+}
+
+
+/// Mods waiting for review, oldest first.
+#[utoipa::path(
+    tag = "review",
+    request_body = ModeratorRequest,
+    responses(
+        (status = 200, description = "Pending mods", body = PendingModList),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+    ),
+)]
+#[post("/review/pending", data="<request_data>")]
+pub async fn api_get_pending_mods(_rate_limit: RateLimit<'_, ReadRoutes>, request_data: Json<ModeratorRequest>) -> ApiResponse {
+    info!("Handling `POST review/pending` for moderator {}", request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+
+    let pending_mods: Vec<PendingMod> = sqlx::query_as!(
+        PendingMod,
+        r#"
+        SELECT id, author, title, description, COALESCE(description_html, '') AS "description_html!", game_name, created_at
+        FROM mods
+        WHERE review_state = 'pending_review'
+        ORDER BY created_at ASC
+        "#,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch pending mods: {e}")))?;
+
+    respond_ok_value(json!(PendingModList { mods: pending_mods }))
+}
+
+
+/// Moderators need to look at the actual file before approving a mod.
+#[utoipa::path(
+    tag = "review",
+    request_body = ReviewDecisionRequest,
+    responses(
+        (status = 200, description = "The mod file", content_type = "application/octet-stream", body = ModFile),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+        (status = 404, description = "`mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[post("/review/download", data="<request_data>")]
+pub async fn api_download_pending_mod(_rate_limit: RateLimit<'_, ReadRoutes>, request_data: Json<ReviewDecisionRequest>) -> Result<(ContentType, Vec<u8>), ApiError> {
+    info!("Handling `POST review/download` for mod {} by moderator {}", request_data.mod_id, request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+    let mod_id: Uuid = parse_mod_id(&request_data.mod_id)?;
+
+    let file_data: Vec<u8> = sqlx::query_scalar!(
+        r#"
+        SELECT file_data
+        FROM mods
+        WHERE id = $1
+        "#,
+        mod_id,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch mod file: {e}")))?
+        .ok_or(ApiError::ModNotFound)?;
+
+    Ok((ContentType::Binary, file_data))
+}
+
+
+#[utoipa::path(
+    tag = "review",
+    request_body = ReviewDecisionRequest,
+    responses(
+        (status = 200, description = "Approved; the mod is public now"),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+        (status = 404, description = "`pending_mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[post("/review/approve", data="<request_data>")]
+pub async fn api_approve_mod(rate_limit: RateLimit<'_, UploadRoutes>, request_data: Json<ReviewDecisionRequest>) -> ApiResponse {
+    info!("Handling `POST review/approve` for mod {} by moderator {}", request_data.mod_id, request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    let mod_id: Uuid = parse_mod_id(&request_data.mod_id)?;
+
+    set_review_decision(mod_id, ReviewState::Approved, None, &request_data.username).await?;
+    info!("Mod {mod_id} was approved by {}", request_data.username);
+    respond_ok_empty()
+}
+
+
+/// The uploader sees the reason in `my_mods` and can resubmit by updating the mod.
+#[utoipa::path(
+    tag = "review",
+    request_body = ReviewDecisionRequest,
+    responses(
+        (status = 200, description = "Rejected"),
+        (status = 400, description = "`invalid_mod_id` or `invalid_rejection_reason`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+        (status = 404, description = "`pending_mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[post("/review/reject", data="<request_data>")]
+pub async fn api_reject_mod(rate_limit: RateLimit<'_, UploadRoutes>, request_data: Json<ReviewDecisionRequest>) -> ApiResponse {
+    info!("Handling `POST review/reject` for mod {} by moderator {}", request_data.mod_id, request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    let mod_id: Uuid = parse_mod_id(&request_data.mod_id)?;
+
+    // the uploader gets to see this, so it has to be given
+    let reason: String = request_data.reason.as_deref().and_then(sanitize_string)
+        .ok_or_else(|| ApiError::InvalidRejectionReason("A reason is required to reject a mod".to_string()))?;
+    if grapheme_length(&reason) > MAX_REJECTION_REASON_LENGTH {
+        return Err(ApiError::InvalidRejectionReason(format!("Reason must not be longer than {MAX_REJECTION_REASON_LENGTH} chars")))
+    }
+
+    set_review_decision(mod_id, ReviewState::Rejected, Some(&reason), &request_data.username).await?;
+    info!("Mod {mod_id} was rejected by {}: {reason}", request_data.username);
+    respond_ok_empty()
+}
+
+
+async fn set_review_decision(mod_id: Uuid, review_state: ReviewState, reason: Option<&str>, moderator: &str) -> Result<(), ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mods
+        SET review_state = $2, review_reason = $3, reviewed_by = $4, reviewed_at = NOW()
+        WHERE id = $1 AND review_state = 'pending_review'
+        "#,
+        mod_id,
+        review_state.as_str(),
+        reason,
+        moderator,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not update review state of mod: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::PendingModNotFound)
+    }
+    Ok(())
+}
+
+fn parse_mod_id(mod_id: &str) -> Result<Uuid, ApiError> {
+    Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)
+}
@@ -1,23 +1,87 @@
+//! Cleaning up the text users send before it is stored and shown to others.
+//!
+//! Everything goes through [`sanitize_string`]; the lengths of titles, descriptions and reasons are counted
+//! in grapheme clusters with [`grapheme_length`], so "é" or a flag emoji count as one character however
+//! they are encoded. Names other people have to tell apart are also checked for look-alike letters.
 
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{skeleton, MixedScript};
+use unicode_segmentation::UnicodeSegmentation;
 
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const ZERO_WIDTH_NON_JOINER: char = '\u{200C}';
+
+
+/// Line breaks and tabs are the only control characters that belong in text.
+fn is_stripped_control(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+/// Embeddings, overrides and isolates can make text display in a different order than it is stored in
+/// (and spill into whatever is shown after it). The plain left-to-right and right-to-left marks stay.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Characters that take no space, so two strings that look the same can differ in them.
+fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{180E}' | '\u{2061}'..='\u{2064}'
+        // Hangul fillers render as blanks outside of Hangul syllables
+        | '\u{115F}' | '\u{1160}' | '\u{3164}' | '\u{FFA0}'
+    )
+}
+
+/// Zero width (non-)joiners are needed in emoji sequences and some scripts,
+/// but next to ASCII or at the ends of the text they are only there to hide something.
+fn is_stray_joiner(previous: Option<char>, c: char, next: Option<char>) -> bool {
+    (c == ZERO_WIDTH_JOINER || c == ZERO_WIDTH_NON_JOINER)
+        && [previous, next].iter().any(|neighbour| neighbour.is_none_or(|neighbour| neighbour.is_ascii()))
+}
+
+
+/// Normalizes text to NFC and removes control, bidi override and zero width characters.
+/// Returns `None` if nothing but whitespace is left.
 pub fn sanitize_string(string: &str) -> Option<String> {
-    let string = string.trim();   // trim whitespace
-    
+    let string: String = string.replace("\r\n", "\n").replace('\r', "\n");
+    let visible: Vec<char> = string.chars()
+        .filter(|&c| !is_stripped_control(c) && !is_bidi_control(c) && !is_invisible(c))
+        .collect();
+    let cleaned: String = visible.iter().enumerate()
+        .filter(|&(i, &c)| !is_stray_joiner(i.checked_sub(1).map(|i| visible[i]), c, visible.get(i + 1).copied()))
+        .map(|(_, &c)| c)
+        .nfc()
+        .collect();
+
+    let string = cleaned.trim();   // trim whitespace
     if string.is_empty() {
         return None
     }
-    
-    // let banned_pattern: Regex = Regex::new(r"[\p{C}\\/]").unwrap(); // Control chars, backslashes
-    // if banned_pattern.is_match(string) {
-    //     return None
-    // }
-    
+
     // Normalize fancy quotes/dashes
     let normalized = string
-        .replace('“', "\"")
-        .replace('”', "\"")
+        .replace(['“', '”'], "\"")
         .replace('–', "-");
 
     Some(normalized)
 }
 
+
+/// The number of user-perceived characters.
+pub fn grapheme_length(string: &str) -> usize {
+    string.graphemes(true).count()
+}
+
+/// Finds a word that mixes letters of different scripts, like a Latin word with a Cyrillic "а" in it.
+/// Words written entirely in one script are fine, whatever the script; so are titles mixing such words.
+pub fn find_mixed_script_word(string: &str) -> Option<&str> {
+    string.unicode_words().find(|word| !word.is_single_script())
+}
+
+/// What a name looks like: names with the same skeleton are easily mistaken for each other,
+/// like `Alice` and `AIice` or `kris` and `Kris`. See Unicode TR 39 for the confusables.
+pub fn confusable_skeleton(string: &str) -> String {
+    let lowercase: String = skeleton(string).collect::<String>().to_lowercase();
+    skeleton(&lowercase).collect()
+}
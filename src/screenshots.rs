@@ -0,0 +1,513 @@
+use std::io::Cursor;
+use std::str::FromStr;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Limits};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::metadata::Orientation;
+use rocket::Data;
+use rocket::http::{ContentType, Header};
+use rocket::serde::json::Json;
+use rocket_multipart_form_data::{MultipartFormData, MultipartFormDataError, MultipartFormDataField, MultipartFormDataOptions};
+use serde_json::json;
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::{app_config, pool, respond_ok_empty, respond_ok_value, ApiResponse};
+use crate::api_error::ApiError;
+use crate::accounts::ensure_account_authentication;
+use crate::config::ScreenshotConfig;
+use crate::metrics::record_upload;
+use crate::mods::{ensure_mod_authorization, get_bytes_form_field, get_text_form_field, get_text_form_field_opt};
+use crate::rate_limit::{RateLimit, ReadRoutes, UploadRoutes};
+use crate::review::{get_mod_review_state, review_state_for_upload, ReviewState};
+use acorngm_api_client::error::ErrorResponse;
+use acorngm_api_client::screenshots::{
+    ArrangeScreenshotsRequest,
+    DeleteScreenshotRequest,
+    ScreenshotImage,
+    ScreenshotInfo,
+    ScreenshotList,
+    UploadScreenshotForm,
+};
+
+
+const JPEG_QUALITY: u8 = 90;
+
+
+/// A screenshot after it was decoded and encoded again; the image and the thumbnail share one format.
+struct ProcessedScreenshot {
+    content_type: &'static str,
+    image_data: Vec<u8>,
+    thumbnail_data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Screenshots never change once uploaded, so clients and proxies may keep them.
+#[derive(Responder)]
+pub struct ScreenshotFile {
+    data: Vec<u8>,
+    content_type: ContentType,
+    cache_control: Header<'static>,
+}
+
+
+/// Decodes the upload and encodes it again, which drops EXIF and all other metadata after the orientation was applied.
+/// JPEGs stay JPEGs; PNGs and WebPs are stored as PNG.
+fn process_screenshot(data: &[u8], config: ScreenshotConfig) -> Result<ProcessedScreenshot, ApiError> {
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()
+        .map_err(|e| ApiError::Internal(format!("Could not read screenshot: {e}")))?;
+    let format: ImageFormat = match reader.format() {
+        Some(format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) => format,
+        _ => return Err(ApiError::InvalidImage("Screenshots have to be PNG, JPEG or WebP images".to_string())),
+    };
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(config.max_dimension);
+    limits.max_image_height = Some(config.max_dimension);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|e| decode_error(e, config))?;
+    // broken EXIF data is not worth rejecting the image for
+    let orientation: Orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image: DynamicImage = DynamicImage::from_decoder(decoder).map_err(|e| decode_error(e, config))?;
+    image.apply_orientation(orientation);
+
+    // `thumbnail` would scale small images up
+    let thumbnail: DynamicImage = if image.width() > config.thumbnail_width || image.height() > config.thumbnail_height {
+        image.thumbnail(config.thumbnail_width, config.thumbnail_height)
+    } else {
+        image.clone()
+    };
+
+    let content_type: &'static str = if format == ImageFormat::Jpeg { "image/jpeg" } else { "image/png" };
+    Ok(ProcessedScreenshot {
+        content_type,
+        image_data: encode_image(&image, content_type)?,
+        thumbnail_data: encode_image(&thumbnail, content_type)?,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+fn decode_error(error: ImageError, config: ScreenshotConfig) -> ApiError {
+    match error {
+        ImageError::Limits(_) => ApiError::InvalidImage(format!("Screenshots may be at most {0}x{0} pixels", config.max_dimension)),
+        e => ApiError::InvalidImage(format!("Could not decode image: {e}")),
+    }
+}
+
+fn encode_image(image: &DynamicImage, content_type: &str) -> Result<Vec<u8>, ApiError> {
+    let mut data: Vec<u8> = Vec::new();
+    let result = if content_type == "image/jpeg" {
+        // JPEG has no alpha channel
+        image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))
+    } else {
+        image.write_with_encoder(PngEncoder::new(&mut data))
+    };
+    result.map_err(|e| ApiError::Internal(format!("Could not encode screenshot: {e}")))?;
+    Ok(data)
+}
+
+
+/// The gallery of a mod in order; the cover is the screenshot the author picked, otherwise the first one.
+async fn list_screenshots(connection: &mut PgConnection, mod_id: Uuid) -> Result<Vec<ScreenshotInfo>, ApiError> {
+    sqlx::query_as!(
+        ScreenshotInfo,
+        r#"
+        SELECT mod_screenshots.id, mod_screenshots.position, mod_screenshots.content_type, mod_screenshots.width, mod_screenshots.height,
+            mod_screenshots.id = COALESCE(
+                mods.cover_screenshot_id,
+                (SELECT first.id FROM mod_screenshots AS first WHERE first.mod_id = mods.id ORDER BY first.position LIMIT 1)
+            ) AS "cover!",
+            mod_screenshots.uploaded_at
+        FROM mod_screenshots
+        JOIN mods ON mods.id = mod_screenshots.mod_id
+        WHERE mod_screenshots.mod_id = $1
+        ORDER BY mod_screenshots.position
+        "#,
+        mod_id,
+    )
+        .fetch_all(connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not list screenshots of mod {mod_id}: {e}")))
+}
+
+/// Locks the mod, so screenshots of it are added, moved and removed one request at a time.
+async fn lock_mod(connection: &mut PgConnection, mod_id: Uuid) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        SELECT id
+        FROM mods
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        mod_id,
+    )
+        .fetch_optional(connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not lock mod {mod_id}: {e}")))?
+        .ok_or(ApiError::ModNotFound)?;
+    Ok(())
+}
+
+async fn ensure_mod_not_removed(mod_id: Uuid) -> Result<(), ApiError> {
+    if get_mod_review_state(mod_id).await? == ReviewState::Removed {
+        return Err(ApiError::ModRemoved)
+    }
+    Ok(())
+}
+
+
+/// Adds a screenshot to the end of the gallery of a mod; like updates, screenshots of new uploaders have to be reviewed.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("mod_id" = Uuid, Path)),
+    request_body(content = UploadScreenshotForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Added", body = ScreenshotInfo),
+        (status = 400, description = "`malformed_request`, `invalid_mod_id` or `invalid_image`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_mod_owner` or `mod_removed`", body = ErrorResponse),
+        (status = 409, description = "`too_many_screenshots`", body = ErrorResponse),
+        (status = 413, description = "`image_too_large`", body = ErrorResponse),
+        (status = 415, description = "`unsupported_content_type`", body = ErrorResponse),
+    ),
+)]
+#[put("/mod/<mod_id>/screenshots", data = "<data>")]
+pub async fn api_upload_screenshot(rate_limit: RateLimit<'_, UploadRoutes>, mod_id: &str, content_type: &ContentType, data: Data<'_>) -> ApiResponse {
+    info!("Handling `PUT mod/{mod_id}/screenshots`");
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+
+    // Check MIME type
+    if !content_type.is_form_data() {
+        warn!("Unsupported content type: {content_type}");
+        return Err(ApiError::UnsupportedContentType(content_type.to_string()));
+    }
+
+    let config: ScreenshotConfig = app_config().screenshots;
+    let form_options = MultipartFormDataOptions::with_multipart_form_data_fields(vec![
+        MultipartFormDataField::text("username"),
+        MultipartFormDataField::text("access_token"),
+        MultipartFormDataField::raw("image").size_limit(config.max_file_size.as_u64()),
+        MultipartFormDataField::text("cover"),
+    ]);
+    let form_data: MultipartFormData = MultipartFormData::parse(content_type, data, form_options).await
+        .map_err(|e| match e {
+            MultipartFormDataError::DataTooLargeError(field) if &*field == "image" => ApiError::ImageTooLarge,
+            e => ApiError::MalformedRequest(format!("Could not parse form data: {e}")),
+        })?;
+
+    let username: &String = get_text_form_field(&form_data, "username")?;
+    let access_token: &String = get_text_form_field(&form_data, "access_token")?;
+    ensure_account_authentication(username, access_token).await?;
+    rate_limit.check_account(username).await?;
+    ensure_mod_authorization(mod_id, username).await?;
+    ensure_mod_not_removed(mod_id).await?;
+
+    let image: Vec<u8> = get_bytes_form_field(&form_data, "image")?.clone();
+    let cover: bool = match get_text_form_field_opt(&form_data, "cover") {
+        Some(cover) => bool::from_str(cover).map_err(|_| ApiError::MalformedRequest("Field `cover` has to be `true` or `false`".to_string()))?,
+        None => false,
+    };
+
+    let upload_size: usize = image.len();
+    let screenshot: ProcessedScreenshot = rocket::tokio::task::spawn_blocking(move || process_screenshot(&image, config)).await
+        .map_err(|e| ApiError::Internal(format!("Screenshot processing panicked: {e}")))??;
+    let upload_review_state: ReviewState = review_state_for_upload(username).await?;
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for screenshot: {e}")))?;
+    lock_mod(&mut transaction, mod_id).await?;
+    let count: i64 = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM mod_screenshots
+        WHERE mod_id = $1
+        "#,
+        mod_id,
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not count screenshots of mod {mod_id}: {e}")))?;
+    if count >= config.max_per_mod {
+        return Err(ApiError::TooManyScreenshots)
+    }
+
+    let screenshot_id: Uuid = sqlx::query_scalar!(
+        r#"
+        INSERT INTO mod_screenshots (mod_id, position, content_type, image_data, thumbnail_data, width, height)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+        mod_id,
+        count as i32,
+        screenshot.content_type,
+        screenshot.image_data,
+        screenshot.thumbnail_data,
+        screenshot.width as i32,
+        screenshot.height as i32,
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not save screenshot of mod {mod_id}: {e}")))?;
+    if cover {
+        sqlx::query!(
+            r#"
+            UPDATE mods
+            SET cover_screenshot_id = $2
+            WHERE id = $1
+            "#,
+            mod_id,
+            screenshot_id,
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not set cover of mod {mod_id}: {e}")))?;
+    }
+    if upload_review_state == ReviewState::PendingReview {
+        sqlx::query!(
+            r#"
+            UPDATE mods
+            SET review_state = 'pending_review', review_reason = NULL
+            WHERE id = $1 AND review_state = 'approved'
+            "#,
+            mod_id,
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not hold back mod {mod_id} for review: {e}")))?;
+    }
+    let screenshot_info: ScreenshotInfo = list_screenshots(&mut transaction, mod_id).await?
+        .into_iter()
+        .find(|info| info.id == screenshot_id)
+        .ok_or_else(|| ApiError::Internal(format!("Screenshot {screenshot_id} vanished while uploading it")))?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit screenshot of mod {mod_id}: {e}")))?;
+
+    record_upload("screenshot", upload_size);
+    info!("User {username} added screenshot {screenshot_id} to mod {mod_id}");
+    respond_ok_value(json!(screenshot_info))
+}
+
+
+/// The gallery of an approved mod, in order.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("mod_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Screenshots", body = ScreenshotList),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 404, description = "`mod_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/mod/<mod_id>/screenshots")]
+pub async fn api_list_screenshots(_rate_limit: RateLimit<'_, ReadRoutes>, mod_id: &str) -> ApiResponse {
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    if get_mod_review_state(mod_id).await? != ReviewState::Approved {
+        return Err(ApiError::ModNotFound)
+    }
+
+    let mut connection = pool().acquire().await
+        .map_err(|e| ApiError::Internal(format!("Could not get database connection: {e}")))?;
+    let screenshots: Vec<ScreenshotInfo> = list_screenshots(&mut connection, mod_id).await?;
+    respond_ok_value(json!(ScreenshotList { screenshots }))
+}
+
+
+/// Also works before the mod is approved, so authors and moderators can see what they are about to publish.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("screenshot_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The image", content(
+            (ScreenshotImage = "image/png"),
+            (ScreenshotImage = "image/jpeg"),
+        )),
+        (status = 404, description = "`screenshot_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/screenshots/<screenshot_id>")]
+pub async fn api_get_screenshot(_rate_limit: RateLimit<'_, ReadRoutes>, screenshot_id: &str) -> Result<ScreenshotFile, ApiError> {
+    get_screenshot_file(screenshot_id, false).await
+}
+
+
+/// The screenshot scaled down to fit the thumbnail box of the server; small screenshots are not scaled up.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("screenshot_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The thumbnail", content(
+            (ScreenshotImage = "image/png"),
+            (ScreenshotImage = "image/jpeg"),
+        )),
+        (status = 404, description = "`screenshot_not_found`", body = ErrorResponse),
+    ),
+)]
+#[get("/screenshots/<screenshot_id>/thumbnail")]
+pub async fn api_get_screenshot_thumbnail(_rate_limit: RateLimit<'_, ReadRoutes>, screenshot_id: &str) -> Result<ScreenshotFile, ApiError> {
+    get_screenshot_file(screenshot_id, true).await
+}
+
+async fn get_screenshot_file(screenshot_id: &str, thumbnail: bool) -> Result<ScreenshotFile, ApiError> {
+    let screenshot_id: Uuid = Uuid::from_str(screenshot_id).map_err(|_| ApiError::ScreenshotNotFound)?;
+    let row = sqlx::query!(
+        r#"
+        SELECT mod_screenshots.content_type,
+            CASE WHEN $2 THEN mod_screenshots.thumbnail_data ELSE mod_screenshots.image_data END AS "data!"
+        FROM mod_screenshots
+        JOIN mods ON mods.id = mod_screenshots.mod_id
+        WHERE mod_screenshots.id = $1 AND mods.review_state <> 'removed'
+        "#,
+        screenshot_id,
+        thumbnail,
+    )
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not fetch screenshot {screenshot_id}: {e}")))?
+        .ok_or(ApiError::ScreenshotNotFound)?;
+
+    let content_type: ContentType = if row.content_type == "image/jpeg" { ContentType::JPEG } else { ContentType::PNG };
+    Ok(ScreenshotFile {
+        data: row.data,
+        content_type,
+        cache_control: Header::new("Cache-Control", "public, max-age=604800, immutable"),
+    })
+}
+
+
+/// Reorders the gallery and/or picks its cover.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("mod_id" = Uuid, Path)),
+    request_body = ArrangeScreenshotsRequest,
+    responses(
+        (status = 200, description = "The gallery in its new order", body = ScreenshotList),
+        (status = 400, description = "`invalid_mod_id`, `invalid_screenshot_order` or `nothing_to_update`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_mod_owner` or `mod_removed`", body = ErrorResponse),
+        (status = 404, description = "`screenshot_not_found`", body = ErrorResponse),
+    ),
+)]
+#[patch("/mod/<mod_id>/screenshots", data = "<request_data>")]
+pub async fn api_arrange_screenshots(rate_limit: RateLimit<'_, UploadRoutes>, mod_id: &str, request_data: Json<ArrangeScreenshotsRequest>) -> ApiResponse {
+    info!("Handling `PATCH mod/{mod_id}/screenshots` for user {}", request_data.username);
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    ensure_mod_authorization(mod_id, &request_data.username).await?;
+    ensure_mod_not_removed(mod_id).await?;
+    if request_data.order.is_none() && request_data.cover.is_none() {
+        return Err(ApiError::NothingToUpdate)
+    }
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for arranging screenshots: {e}")))?;
+    lock_mod(&mut transaction, mod_id).await?;
+    let current: Vec<ScreenshotInfo> = list_screenshots(&mut transaction, mod_id).await?;
+
+    if let Some(order) = &request_data.order {
+        let mut current_ids: Vec<Uuid> = current.iter().map(|info| info.id).collect();
+        let mut new_ids: Vec<Uuid> = order.clone();
+        current_ids.sort();
+        new_ids.sort();
+        if current_ids != new_ids {
+            return Err(ApiError::InvalidScreenshotOrder)
+        }
+        // the unique position constraint is deferred, so positions may collide until the commit
+        sqlx::query!(
+            r#"
+            UPDATE mod_screenshots
+            SET position = (new_order.position - 1)::INT
+            FROM UNNEST($2::UUID[]) WITH ORDINALITY AS new_order(id, position)
+            WHERE mod_screenshots.id = new_order.id AND mod_screenshots.mod_id = $1
+            "#,
+            mod_id,
+            order,
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not reorder screenshots of mod {mod_id}: {e}")))?;
+    }
+    if let Some(cover) = request_data.cover {
+        if !current.iter().any(|info| info.id == cover) {
+            return Err(ApiError::ScreenshotNotFound)
+        }
+        sqlx::query!(
+            r#"
+            UPDATE mods
+            SET cover_screenshot_id = $2
+            WHERE id = $1
+            "#,
+            mod_id,
+            cover,
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not set cover of mod {mod_id}: {e}")))?;
+    }
+
+    let screenshots: Vec<ScreenshotInfo> = list_screenshots(&mut transaction, mod_id).await?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit arranging screenshots of mod {mod_id}: {e}")))?;
+
+    respond_ok_value(json!(ScreenshotList { screenshots }))
+}
+
+
+/// Removes a screenshot; the ones after it move up. Removing the cover makes the first screenshot the cover again.
+#[utoipa::path(
+    tag = "screenshots",
+    params(("mod_id" = Uuid, Path), ("screenshot_id" = Uuid, Path)),
+    request_body = DeleteScreenshotRequest,
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = 400, description = "`invalid_mod_id`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_mod_owner` or `mod_removed`", body = ErrorResponse),
+        (status = 404, description = "`screenshot_not_found`", body = ErrorResponse),
+    ),
+)]
+#[delete("/mod/<mod_id>/screenshots/<screenshot_id>", data = "<request_data>")]
+pub async fn api_delete_screenshot(rate_limit: RateLimit<'_, UploadRoutes>, mod_id: &str, screenshot_id: &str, request_data: Json<DeleteScreenshotRequest>) -> ApiResponse {
+    info!("Handling `DELETE mod/{mod_id}/screenshots/{screenshot_id}` for user {}", request_data.username);
+    let mod_id: Uuid = Uuid::from_str(mod_id).map_err(|_| ApiError::InvalidModId)?;
+    let screenshot_id: Uuid = Uuid::from_str(screenshot_id).map_err(|_| ApiError::ScreenshotNotFound)?;
+    ensure_account_authentication(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    ensure_mod_authorization(mod_id, &request_data.username).await?;
+    ensure_mod_not_removed(mod_id).await?;
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for deleting screenshot: {e}")))?;
+    lock_mod(&mut transaction, mod_id).await?;
+    let position: i32 = sqlx::query_scalar!(
+        r#"
+        DELETE FROM mod_screenshots
+        WHERE id = $1 AND mod_id = $2
+        RETURNING position
+        "#,
+        screenshot_id,
+        mod_id,
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not delete screenshot {screenshot_id}: {e}")))?
+        .ok_or(ApiError::ScreenshotNotFound)?;
+    sqlx::query!(
+        r#"
+        UPDATE mod_screenshots
+        SET position = position - 1
+        WHERE mod_id = $1 AND position > $2
+        "#,
+        mod_id,
+        position,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not move up screenshots of mod {mod_id}: {e}")))?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit deleting screenshot {screenshot_id}: {e}")))?;
+
+    info!("User {} deleted screenshot {screenshot_id} of mod {mod_id}", request_data.username);
+    respond_ok_empty()
+}
@@ -1,49 +1,328 @@
-use sqlx::PgPool;
-use uuid::Uuid;
-
-// PostgreSQL query for basic search
-pub async fn basic_search(pool: &PgPool, raw_query: &str) -> Result<Vec<Uuid>, String> {
-    // Normalize the query: remove punctuation, handle whitespace
-    let normalized_query = raw_query
-        .replace(|c: char| !c.is_alphanumeric() && !c.is_whitespace(), " ")
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" & ");  // AND operator for tsquery
-
-    let records = sqlx::query!(
-        r#"
-        SELECT 
-            id,
-            title,
-            description,
-            -- Combined relevance score with:
-            -- 1. Standard full-text search ranking
-            ts_rank_cd(
-                setweight(to_tsvector('english', title), 'A') || 
-                setweight(to_tsvector('english', description), 'B'),
-                to_tsquery($1)
-            ) * 0.7 + 
-            -- 2. Bonus for exact phrase matches (ordered terms)
-            ts_rank_cd(
-                setweight(to_tsvector('english', title), 'A') || 
-                setweight(to_tsvector('english', description), 'B'),
-                phraseto_tsquery('english', $2)
-            ) * 0.3 AS relevance
-        FROM mods
-        WHERE 
-            to_tsvector('english', title) @@ to_tsquery($1) OR
-            to_tsvector('english', description) @@ to_tsquery($1)
-        ORDER BY relevance DESC
-        LIMIT 50
-        "#,
-        normalized_query,
-        raw_query,
-    )
-        .fetch_all(pool)
-        .await
-        .map_err(|e|format!("Could not search mods (basic mode): {e}"))?;
-
-    Ok(records.iter().map(|i| i.id).collect())
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use crate::app_config;
+use crate::api_error::ApiError;
+use crate::config::SearchRanking;
+use crate::mods::{fetch_mod_summaries, MOD_SUMMARY_COLUMNS};
+use acorngm_api_client::mods::{AuthorSuggestion, FacetCount, ModSearchResults, ModSuggestions, ModSummary, SearchFacets, SearchMode, SearchSort, TitleSuggestion};
+
+/// How many values each facet lists at most.
+const FACET_LIMIT: i64 = 20;
+/// Until a mod has a few ratings, its average is pulled towards an average mod's, so one 5 star rating does not top the charts.
+const RATING_PRIOR_MEAN: f64 = 3.0;
+const RATING_PRIOR_COUNT: f64 = 5.0;
+/// How many titles and how many authors `GET mods/suggest` returns at most.
+const SUGGESTION_LIMIT: i64 = 5;
+/// How many matches of a prefix, in alphabetical order, are ranked for `GET mods/suggest`.
+const SUGGESTION_CANDIDATES: i64 = 200;
+/// The largest character; everything that starts with a prefix sorts before the prefix followed by it.
+const PREFIX_RANGE_END: &str = "\u{10FFFF}";
+
+/// Everything besides the text; all of them have to match.
+#[derive(Debug, Default)]
+pub struct SearchFilters {
+    pub game_name: Option<String>,
+    /// `(game_version_minor, game_version_major)`, see [`crate::mods::parse_game_version`]
+    pub game_version: Option<(i32, i32)>,
+    /// normalized and with aliases resolved, see [`crate::tags::resolve_aliases`]
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub updated_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Game,
+    GameVersion,
+    Author,
+    Tag,
+}
+
+/// The search terms in the forms the queries need them.
+struct TextMatch {
+    mode: SearchMode,
+    /// ISO 639-1 code of the terms; picks the stemmer (see `mod_search_config` in migration `0012_mod_languages.sql`)
+    language: String,
+    /// terms ANDed together for `to_tsquery`; with `:*` in prefix mode
+    ts_query: String,
+    /// for the exact phrase bonus
+    raw_query: String,
+    /// the terms separated by spaces, for trigram similarity
+    similarity_query: String,
+}
+
+impl TextMatch {
+    /// `None` if the query has no terms, which matches every mod
+    fn new(raw_query: &str, mode: SearchMode, language: &str) -> Option<TextMatch> {
+        // Normalize the query: remove punctuation, handle whitespace
+        let terms: Vec<String> = raw_query
+            .replace(|c: char| !c.is_alphanumeric() && !c.is_whitespace(), " ")
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if terms.is_empty() {
+            return None
+        }
+
+        let ts_query: String = terms.iter()
+            .map(|term| if mode == SearchMode::Prefix { format!("{term}:*") } else { term.clone() })
+            .collect::<Vec<_>>()
+            .join(" & ");  // AND operator for tsquery
+        Some(TextMatch {
+            mode,
+            language: language.to_string(),
+            ts_query,
+            raw_query: raw_query.to_string(),
+            similarity_query: terms.join(" "),
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct FacetRow {
+    value: String,
+    count: i64,
+}
+
+
+/// Search approved mods: the first 50 matches in `sort` order, and the facet counts of all matches.
+/// `language` is the one the query is written in; mods in other languages still match words exactly as they are written.
+pub async fn search(
+    pool: &PgPool,
+    raw_query: &str,
+    mode: SearchMode,
+    language: &str,
+    sort: SearchSort,
+    filters: &SearchFilters,
+) -> Result<ModSearchResults, ApiError> {
+    let text: Option<TextMatch> = TextMatch::new(raw_query, mode, language);
+    let text: Option<&TextMatch> = text.as_ref();
+
+    let (mods, games, game_versions, authors, tags) = rocket::tokio::try_join!(
+        find_mods(pool, text, sort, filters),
+        count_facet(pool, text, filters, Facet::Game),
+        count_facet(pool, text, filters, Facet::GameVersion),
+        count_facet(pool, text, filters, Facet::Author),
+        count_facet(pool, text, filters, Facet::Tag),
+    )?;
+    Ok(ModSearchResults { mods, facets: SearchFacets { games, game_versions, authors, tags } })
 }
 
+async fn find_mods(pool: &PgPool, text: Option<&TextMatch>, sort: SearchSort, filters: &SearchFilters) -> Result<Vec<ModSummary>, ApiError> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {MOD_SUMMARY_COLUMNS}, "));
+    push_score(&mut query, text, &app_config().search_ranking);
+    query.push(" AS score FROM mods");
+    push_conditions(&mut query, text, filters, None);
+    query.push(" ORDER BY ");
+    match sort {
+        SearchSort::Relevance => query.push("score DESC, updated_at DESC"),
+        SearchSort::Downloads => query.push("download_count DESC, score DESC"),
+        SearchSort::Rating => {
+            push_rating_score(&mut query);
+            query.push(" DESC, rating_count DESC, score DESC")
+        }
+        SearchSort::Newest => query.push("created_at DESC"),
+        SearchSort::Updated => query.push("updated_at DESC"),
+    };
+    query.push(" LIMIT 50");
+
+    fetch_mod_summaries(pool, query).await
+        .map_err(|e| ApiError::Internal(format!("Could not search mods ({} mode): {e}", text.map_or("filter", |text| text.mode.as_str()))))
+}
+
+async fn count_facet(pool: &PgPool, text: Option<&TextMatch>, filters: &SearchFilters, facet: Facet) -> Result<Vec<FacetCount>, ApiError> {
+    let mut query = QueryBuilder::<Postgres>::new("SELECT ");
+    query.push(match facet {
+        Facet::Game => "game_name AS value",
+        Facet::GameVersion => "game_version_minor || '.' || game_version_major AS value",
+        Facet::Author => "author AS value",
+        Facet::Tag => "mod_tags.tag AS value",
+    });
+    query.push(", COUNT(*) AS count FROM mods");
+    if facet == Facet::Tag {
+        query.push(" JOIN mod_tags ON mod_tags.mod_id = mods.id");
+    }
+    push_conditions(&mut query, text, filters, Some(facet));
+    query.push(" GROUP BY value ORDER BY count DESC, value LIMIT ").push_bind(FACET_LIMIT);
+
+    let rows: Vec<FacetRow> = query.build_query_as().fetch_all(pool).await
+        .map_err(|e| ApiError::Internal(format!("Could not count search facet {facet:?}: {e}")))?;
+    Ok(rows.into_iter().map(|row| FacetCount { value: row.value, count: row.count }).collect())
+}
 
+/// The text score blended with popularity and activity, weighted by the `search_ranking` config.
+/// Without search terms the text score is 0 for every mod, so filtered listings are ordered by the rest.
+fn push_score(query: &mut QueryBuilder<'_, Postgres>, text: Option<&TextMatch>, ranking: &SearchRanking) {
+    query.push("(");
+    push_relevance(query, text);
+    query.push(") * ").push_bind(ranking.text_weight);
+
+    // log scale: the first hundred downloads count as much as the next ten thousand
+    query.push(" + LEAST(ln(1 + mods.download_count::FLOAT8) / ln(1 + ")
+        .push_bind(ranking.downloads_for_full_score)
+        .push("), 1) * ")
+        .push_bind(ranking.downloads_weight);
+
+    query.push(" + (");
+    push_rating_score(query);
+    query.push(" - 1) / 4 * ").push_bind(ranking.rating_weight);
+
+    // mods that were never updated get nothing here, their upload counts as freshness
+    query.push(" + CASE WHEN mods.updated_at > mods.created_at THEN exp(-ln(2) * EXTRACT(EPOCH FROM NOW() - mods.updated_at)::FLOAT8 / 86400 / ")
+        .push_bind(ranking.update_half_life_days)
+        .push(") ELSE 0 END * ")
+        .push_bind(ranking.recent_update_weight);
+
+    query.push(" + exp(-ln(2) * EXTRACT(EPOCH FROM NOW() - mods.created_at)::FLOAT8 / 86400 / ")
+        .push_bind(ranking.freshness_half_life_days)
+        .push(") * ")
+        .push_bind(ranking.freshness_weight);
+}
+
+/// Average rating in stars (1-5), pulled towards [`RATING_PRIOR_MEAN`] while there are few ratings.
+fn push_rating_score(query: &mut QueryBuilder<'_, Postgres>) {
+    query.push("((mods.rating_sum + ")
+        .push_bind(RATING_PRIOR_MEAN * RATING_PRIOR_COUNT)
+        .push(") / (mods.rating_count + ")
+        .push_bind(RATING_PRIOR_COUNT)
+        .push("))");
+}
+
+/// Combined relevance score with:
+/// 1. Standard full-text search ranking
+/// 2. Bonus for exact phrase matches (ordered terms)
+/// 3. In fuzzy mode, how similar the best matching part of the title (or, less important, the description) is
+fn push_relevance(query: &mut QueryBuilder<'_, Postgres>, text: Option<&TextMatch>) {
+    let Some(text) = text else {
+        query.push("0::REAL");
+        return
+    };
+    query.push("ts_rank_cd(mods.search_vector, ");
+    push_ts_query(query, text);
+    query.push(") * 0.7");
+    query.push(" + ts_rank_cd(mods.search_vector, phraseto_tsquery(mod_search_config(")
+        .push_bind(text.language.clone())
+        .push("), ")
+        .push_bind(text.raw_query.clone())
+        .push(")) * 0.3");
+    if text.mode == SearchMode::Fuzzy {
+        query.push(" + GREATEST(word_similarity(")
+            .push_bind(text.similarity_query.clone())
+            .push(", mods.title), word_similarity(")
+            .push_bind(text.similarity_query.clone())
+            .push(", mods.description) * 0.5) * 0.5");
+    }
+}
+
+/// The terms stemmed for their language, or as they are written, for mods in other languages.
+fn push_ts_query(query: &mut QueryBuilder<'_, Postgres>, text: &TextMatch) {
+    query.push("(to_tsquery(mod_search_config(")
+        .push_bind(text.language.clone())
+        .push("), ")
+        .push_bind(text.ts_query.clone())
+        .push(") || to_tsquery('simple', ")
+        .push_bind(text.ts_query.clone())
+        .push("))");
+}
+
+/// The `WHERE` clause: approved mods that match the text and all filters, except the one of the `ignored` facet.
+fn push_conditions(query: &mut QueryBuilder<'_, Postgres>, text: Option<&TextMatch>, filters: &SearchFilters, ignored: Option<Facet>) {
+    query.push(" WHERE mods.review_state = 'approved'");
+
+    if let Some(text) = text {
+        // the stored, weighted title and description (see migration `0011_search_vectors.sql`), with a GIN index
+        query.push(" AND (mods.search_vector @@ ");
+        push_ts_query(query, text);
+        // the trigram indexes make these fast
+        if text.mode == SearchMode::Fuzzy {
+            query.push(" OR ").push_bind(text.similarity_query.clone()).push(" <% mods.title");
+            query.push(" OR ").push_bind(text.similarity_query.clone()).push(" <% mods.description");
+        }
+        query.push(")");
+    }
+
+    if let Some(game_name) = &filters.game_name && ignored != Some(Facet::Game) {
+        query.push(" AND mods.game_name = ").push_bind(game_name.clone());
+    }
+    if let Some((minor, major)) = filters.game_version && ignored != Some(Facet::GameVersion) {
+        query.push(" AND mods.game_version_minor = ").push_bind(minor);
+        query.push(" AND mods.game_version_major = ").push_bind(major);
+    }
+    if let Some(author) = &filters.author && ignored != Some(Facet::Author) {
+        query.push(" AND mods.author = ").push_bind(author.clone());
+    }
+    if !filters.tags.is_empty() && ignored != Some(Facet::Tag) {
+        query.push(" AND mods.id IN (SELECT mod_id FROM mod_tags WHERE tag = ANY(")
+            .push_bind(filters.tags.clone())
+            .push(") GROUP BY mod_id HAVING COUNT(*) = ")
+            .push_bind(filters.tags.len() as i64)
+            .push(")");
+    }
+    if let Some(updated_since) = filters.updated_since {
+        query.push(" AND mods.updated_at >= ").push_bind(updated_since);
+    }
+}
+
+
+/// Titles and authors that start with `prefix` (ignoring case), for completing a search as it is typed.
+/// Both walk a prefix index in order (see migration `0010_suggest_prefixes.sql`) and only rank the first
+/// [`SUGGESTION_CANDIDATES`] matches, so short prefixes that match half the catalog are as fast as long ones.
+pub async fn suggest(pool: &PgPool, prefix: &str) -> Result<ModSuggestions, ApiError> {
+    let titles = async {
+        sqlx::query_as!(
+            TitleSuggestion,
+            r#"
+            SELECT id AS "id!", title AS "title!"
+            FROM (
+                SELECT id, title, download_count
+                FROM mods
+                WHERE review_state = 'approved'
+                    AND lower(title) COLLATE "C" >= lower($1) COLLATE "C"
+                    AND lower(title) COLLATE "C" < (lower($1) || $2) COLLATE "C"
+                ORDER BY lower(title) COLLATE "C"
+                LIMIT $3
+            ) AS candidates
+            ORDER BY download_count DESC, title
+            LIMIT $4
+            "#,
+            prefix,
+            PREFIX_RANGE_END,
+            SUGGESTION_CANDIDATES,
+            SUGGESTION_LIMIT,
+        )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not suggest titles: {e}")))
+    };
+    let authors = async {
+        sqlx::query_as!(
+            AuthorSuggestion,
+            r#"
+            SELECT username AS "username!", mod_count AS "mod_count!"
+            FROM (
+                SELECT
+                    username,
+                    (SELECT COUNT(*) FROM mods WHERE mods.author = accounts.username AND mods.review_state = 'approved') AS mod_count
+                FROM accounts
+                WHERE lower(username) COLLATE "C" >= lower($1) COLLATE "C"
+                    AND lower(username) COLLATE "C" < (lower($1) || $2) COLLATE "C"
+                ORDER BY lower(username) COLLATE "C"
+                LIMIT $3
+            ) AS candidates
+            WHERE mod_count > 0
+            ORDER BY mod_count DESC, username
+            LIMIT $4
+            "#,
+            prefix,
+            PREFIX_RANGE_END,
+            SUGGESTION_CANDIDATES,
+            SUGGESTION_LIMIT,
+        )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Could not suggest authors: {e}")))
+    };
+
+    let (titles, authors) = rocket::tokio::try_join!(titles, authors)?;
+    Ok(ModSuggestions { titles, authors })
+}
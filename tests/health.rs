@@ -0,0 +1,34 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use crate::common::{expect_json, TestApp};
+
+
+#[rocket::async_test]
+async fn readiness_checks_database_storage_and_migrations() {
+    let app: TestApp = TestApp::spawn().await;
+
+    let response = app.client.get("/healthz").dispatch().await;
+    let health: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(health["status"], "ok");
+
+    let response = app.client.get("/readyz").dispatch().await;
+    let readiness: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(readiness["status"], "ok");
+    for component in ["database", "storage", "migrations"] {
+        assert_eq!(readiness["components"][component]["status"], "ok", "{readiness}");
+        assert!(readiness["components"][component]["durationMs"].is_u64());
+    }
+
+    // a migration of this binary that the database has not seen yet
+    let mut database = app.database().await;
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)")
+        .execute(&mut database).await.unwrap();
+    let response = app.client.get("/readyz").dispatch().await;
+    let readiness: Value = expect_json(response, Status::ServiceUnavailable).await;
+    assert_eq!(readiness["status"], "unavailable");
+    assert_eq!(readiness["components"]["database"]["status"], "ok");
+    assert_eq!(readiness["components"]["migrations"]["status"], "unavailable");
+    assert!(readiness["components"]["migrations"]["error"].as_str().unwrap().starts_with("Pending migrations: "), "{readiness}");
+}
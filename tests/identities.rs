@@ -0,0 +1,101 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::mock_discord::MockDiscordUser;
+use crate::common::{expect_error, expect_json, expect_status, TestApp};
+
+
+#[rocket::async_test]
+async fn accounts_link_identities_of_several_providers() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("linker", "300000000000000001").await;
+
+    let response = app.client.get("/api/v1/auth/providers").dispatch().await;
+    let providers: Value = expect_json(response, Status::Ok).await;
+    let names: Vec<&str> = providers["providers"].as_array().unwrap().iter().filter_map(|provider| provider["name"].as_str()).collect();
+    assert_eq!(names, ["discord", "mockoidc"]);
+
+    let response = app.client.get("/api/v1/auth/nonexistent?code=whatever").dispatch().await;
+    expect_error(response, Status::NotFound, "unknown_identity_provider").await;
+
+    // log in with an oidc identity that has no account yet, then link it instead of registering
+    let oidc_user = MockDiscordUser::new("oidc-subject-1", "linker_elsewhere");
+    let code: String = app.discord.authorize(&oidc_user);
+    let response = app.client.get(format!("/api/v1/auth/mockoidc?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], true);
+    assert_eq!(auth["providerUserId"], "oidc-subject-1");
+    assert_eq!(auth["providerUsername"], "linker_elsewhere");
+
+    let link_request = json!({
+        "username": user.username,
+        "access_token": user.access_token,
+        "provider": "mockoidc",
+        "provider_user_id": "oidc-subject-1",
+        "provider_access_token": auth["providerAccessToken"],
+    });
+    let response = app.client.post("/api/v1/identities/link").json(&link_request).dispatch().await;
+    expect_status(response, Status::Ok).await;
+    let response = app.client.post("/api/v1/identities/link").json(&link_request).dispatch().await;
+    expect_error(response, Status::Conflict, "identity_already_linked").await;
+
+    let response = app.client.post("/api/v1/identities")
+        .json(&json!({"username": user.username, "access_token": user.access_token}))
+        .dispatch().await;
+    let identities: Value = expect_json(response, Status::Ok).await;
+    let linked: Vec<(&str, &str)> = identities["identities"].as_array().unwrap().iter()
+        .map(|identity| (identity["provider"].as_str().unwrap(), identity["providerUserId"].as_str().unwrap()))
+        .collect();
+    assert_eq!(linked, [("discord", "300000000000000001"), ("mockoidc", "oidc-subject-1")]);
+
+    // the linked identity now logs into the same account
+    let code: String = app.discord.authorize(&oidc_user);
+    let response = app.client.get(format!("/api/v1/auth/mockoidc?code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], false);
+    assert_eq!(auth["username"], "linker");
+
+    // nobody else can register with it
+    let response = app.client.post("/api/v1/register")
+        .json(&json!({
+            "username": "identity_thief",
+            "provider": "mockoidc",
+            "provider_user_id": "oidc-subject-1",
+            "provider_access_token": app.discord.access_token(&oidc_user),
+        }))
+        .dispatch().await;
+    expect_error(response, Status::Conflict, "identity_already_linked").await;
+
+    // unlinking
+    let unlink = |provider: &str, provider_user_id: &str| json!({
+        "username": user.username,
+        "access_token": user.access_token,
+        "provider": provider,
+        "provider_user_id": provider_user_id,
+    });
+    let response = app.client.post("/api/v1/identities/unlink").json(&unlink("mockoidc", "someone-else")).dispatch().await;
+    expect_error(response, Status::NotFound, "identity_not_linked").await;
+    let response = app.client.post("/api/v1/identities/unlink").json(&unlink("discord", "300000000000000001")).dispatch().await;
+    expect_status(response, Status::Ok).await;
+    let response = app.client.post("/api/v1/identities/unlink").json(&unlink("mockoidc", "oidc-subject-1")).dispatch().await;
+    expect_error(response, Status::Conflict, "last_identity").await;
+
+    // the unlinked discord account is free to register again
+    let code: String = app.discord.authorize(&user.discord);
+    let response = app.client.get(format!("/api/v1/discord_auth?discord_code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], true);
+
+    let response = app.client.get("/goto_auth/mockoidc?temp_login_token=abc").dispatch().await;
+    let page: String = response.into_string().await.unwrap_or_default();
+    assert!(page.contains("/oauth2/authorize?client_id="), "{page}");
+    assert!(page.contains("redirect_uri=http%3A%2F%2Flocalhost%3A24187%2Fauth_page.html%3Fprovider%3Dmockoidc"), "{page}");
+
+    // the token ends up in a script; it must not be able to end the string or the element
+    let response = app.client.get("/goto_auth/mockoidc?temp_login_token=x%27);alert(1)//%3C/script%3E%3Cb%3E").dispatch().await;
+    let page: String = response.into_string().await.unwrap_or_default();
+    assert!(page.contains(r#"localStorage.setItem("tempLoginToken", "x');alert(1)//\u003c/script\u003e\u003cb\u003e");"#), "{page}");
+    assert!(!page.contains("</script><b>") && page.matches("</script>").count() == 1, "{page}");
+}
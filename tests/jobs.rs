@@ -0,0 +1,56 @@
+mod common;
+
+use clap::Parser;
+use rocket::http::Status;
+use sqlx::PgConnection;
+use acorngm_backend::admin::{run, AdminCli};
+use crate::common::{expect_status, TestApp};
+
+
+async fn run_job(name: &str) -> Result<(), String> {
+    let cli = AdminCli::try_parse_from(["acorngm-admin", "run-job", name]).expect("Invalid admin command");
+    run(cli.command).await
+}
+
+async fn count(database: &mut PgConnection, query: &str) -> i64 {
+    sqlx::query_scalar(query).fetch_one(database).await.unwrap_or_else(|e| panic!("{query}: {e}"))
+}
+
+
+#[rocket::async_test]
+async fn housekeeping_jobs_clean_up_and_take_turns() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("housekeeper", "600000000000000001").await;
+    let mut database = app.database().await;
+
+    // downloads are counted once the rollup ran
+    let mod_id: &str = &app.upload_mod(&user, "Popular Mod").description("Everyone downloads this").id().await;
+    for _ in 0..3 {
+        let response = app.client.get(format!("/api/v1/mod/{mod_id}/download")).dispatch().await;
+        expect_status(response, Status::Ok).await;
+    }
+    assert_eq!(count(&mut database, "SELECT download_count FROM mods").await, 0);
+    run_job("download_count_rollup").await.unwrap();
+    assert_eq!(count(&mut database, "SELECT download_count FROM mods").await, 3);
+    assert_eq!(count(&mut database, "SELECT COUNT(*) FROM mod_download_events").await, 0);
+
+    // expired temp login tokens go, valid ones stay
+    sqlx::query("INSERT INTO temp_login_tokens (token, username, expires_at) VALUES ('old', 'housekeeper', NOW() - INTERVAL '1 minute'), ('new', 'housekeeper', NOW() + INTERVAL '5 minutes')")
+        .execute(&mut database).await.unwrap();
+    run_job("expired_login_codes").await.unwrap();
+    assert_eq!(count(&mut database, "SELECT COUNT(*) FROM temp_login_tokens WHERE token IN ('old', 'new')").await, 1);
+
+    // a job that is running on another instance is skipped, not run twice
+    sqlx::query("UPDATE scheduled_jobs SET locked_until = NOW() + INTERVAL '1 minute' WHERE name = 'expired_login_codes'")
+        .execute(&mut database).await.unwrap();
+    sqlx::query("UPDATE temp_login_tokens SET expires_at = NOW() - INTERVAL '1 minute' WHERE token = 'new'")
+        .execute(&mut database).await.unwrap();
+    run_job("expired_login_codes").await.unwrap();
+    assert_eq!(count(&mut database, "SELECT COUNT(*) FROM temp_login_tokens WHERE token = 'new'").await, 1);
+
+    // without a configured max age, access tokens are kept
+    run_job("expired_access_tokens").await.unwrap();
+    assert_eq!(count(&mut database, "SELECT COUNT(*) FROM access_tokens").await, 1);
+
+    assert!(run_job("orphaned_blobs").await.is_err());
+}
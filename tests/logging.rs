@@ -0,0 +1,72 @@
+mod common;
+
+use log::{Level, Record};
+use rocket::http::{Header, Status};
+use rocket::serde::json::Value;
+use acorngm_backend::logging::{LogFormat, Logger, Redacted};
+use crate::common::TestApp;
+
+
+#[rocket::async_test]
+async fn requests_get_ids_and_logs_keep_secrets_out() {
+    let app: TestApp = TestApp::spawn().await;
+
+    // ids from a proxy are passed on, unusable ones replaced
+    let response = app.client.get("/healthz").header(Header::new("X-Request-Id", "proxy-1234.abc")).dispatch().await;
+    assert_eq!(response.headers().get_one("X-Request-Id"), Some("proxy-1234.abc"));
+    let response = app.client.get("/healthz").header(Header::new("X-Request-Id", "evil\" id")).dispatch().await;
+    let generated: &str = response.headers().get_one("X-Request-Id").unwrap();
+    assert_eq!(generated.len(), 32);
+    let response = app.client.get("/api/v1/nothing_here").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+    assert!(response.headers().get_one("X-Request-Id").is_some());
+
+    let fields: [(&str, u16); 1] = [("status", 404)];
+    let line: String = Logger::new(LogFormat::Json).format(
+        &Record::builder()
+            .level(Level::Info)
+            .target("acorngm_backend::access")
+            .args(format_args!("GET /api/v1/nothing_here {:?}", Redacted("a-long-temp-login-token")))
+            .key_values(&fields)
+            .build(),
+    );
+    let line: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["target"], "acorngm_backend::access");
+    assert_eq!(line["message"], "GET /api/v1/nothing_here \"a-lo…\"");
+    assert_eq!(line["status"], 404);
+    assert_eq!(Redacted("short").to_string(), "…");
+}
+
+/// Secrets are cut down by the logger itself, whoever wrote the log line.
+#[test]
+fn secrets_are_redacted_in_every_log_line() {
+    let format = |message: &str, fields: &[(&str, &str)]| -> String {
+        Logger::new(LogFormat::Text).format(
+            &Record::builder()
+                .level(Level::Error)
+                .target("acorngm_backend::login")
+                .args(format_args!("{message}"))
+                .key_values(&fields)
+                .build(),
+        )
+    };
+
+    let line: String = format("Handling `POST temp_login` with username bob and temp login token \"0123456789abcdef\"", &[]);
+    assert!(line.ends_with("with username bob and temp login token \"0123…\""), "{line}");
+    let line: String = format("Handling `GET discord_auth` with oauth code \"supersecretcode\"", &[]);
+    assert!(line.ends_with("with oauth code \"supe…\""), "{line}");
+    let line: String = format(&format!("Handling `GET discord_auth` with oauth code {:?}", Redacted("supersecretcode")), &[]);
+    assert!(line.ends_with("with oauth code \"supe…\""), "{line}");
+    let line: String = format("Redirecting to https://example.com/goto_move/discord?move_code=supersecretcode&state=1", &[]);
+    assert!(line.ends_with("?move_code=supe…&state=1"), "{line}");
+    // status and error codes are not secrets
+    let line: String = format("Responding with error code: invalid_tag, code=400", &[]);
+    assert!(line.ends_with("with error code: invalid_tag, code=400"), "{line}");
+    let line: String = format(r#"GitHub: got {"access_token":"gho_abcdefghijkl","refresh_token": "ghr_abcdefghijkl","token_type":"bearer"}"#, &[]);
+    assert!(line.contains(r#"{"access_token":"gho_…","refresh_token": "ghr_…","token_type":"bearer"}"#), "{line}");
+    let line: String = format("sent Authorization: Bearer abcdefghijkl to the provider", &[("discordAccessToken", "abcdefghijkl")]);
+    assert!(line.contains("Bearer abcd… to the provider discordAccessToken=\"abcd…\""), "{line}");
+    let line: String = format("Handling `GET mods/search` with status code 200", &[("providerAccessToken", "short")]);
+    assert!(line.ends_with("with status code 200 providerAccessToken=\"…\""), "{line}");
+}
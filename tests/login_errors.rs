@@ -0,0 +1,69 @@
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+use crate::common::mock_discord::MockDiscordUser;
+use crate::common::{expect_error, TestApp};
+
+
+#[rocket::async_test]
+async fn login_rejects_bad_discord_credentials() {
+    let app: TestApp = TestApp::spawn().await;
+
+    let response = app.client.get("/api/v1/discord_auth?discord_code=never-issued").dispatch().await;
+    expect_error(response, Status::Unauthorized, "invalid_authorization_code").await;
+
+    // codes can only be exchanged once
+    let discord_user = MockDiscordUser::new("200000000000000001", "someone");
+    let code: String = app.discord.authorize(&discord_user);
+    let response = app.client.get(format!("/api/v1/discord_auth?discord_code={code}")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = app.client.get(format!("/api/v1/discord_auth?discord_code={code}")).dispatch().await;
+    expect_error(response, Status::Unauthorized, "invalid_authorization_code").await;
+
+    let response = app.client.post("/api/v1/register")
+        .json(&json!({
+            "username": "someone",
+            "discord_user_id": "200000000000000001",
+            "discord_access_token": "not-a-token",
+        }))
+        .dispatch().await;
+    expect_error(response, Status::Unauthorized, "invalid_provider_token").await;
+
+    // the access token has to belong to the claimed discord account
+    let response = app.client.post("/api/v1/register")
+        .json(&json!({
+            "username": "someone",
+            "discord_user_id": "200000000000000002",
+            "discord_access_token": app.discord.access_token(&discord_user),
+        }))
+        .dispatch().await;
+    expect_error(response, Status::Unauthorized, "identity_mismatch").await;
+
+    let response = app.client.post("/api/v1/register")
+        .json(&json!({
+            "username": "x",
+            "discord_user_id": "200000000000000001",
+            "discord_access_token": app.discord.access_token(&discord_user),
+        }))
+        .dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_username").await;
+
+    // a capital i looks like a lowercase L
+    app.register("alice", "200000000000000003").await;
+    let impostor = MockDiscordUser::new("200000000000000004", "impostor");
+    let response = app.client.post("/api/v1/register")
+        .json(&json!({
+            "username": "AIice",
+            "discord_user_id": "200000000000000004",
+            "discord_access_token": app.discord.access_token(&impostor),
+        }))
+        .dispatch().await;
+    expect_error(response, Status::Conflict, "confusable_username").await;
+
+    let response = app.client.post("/api/v1/access_token").body("unknown-temp-login-token").dispatch().await;
+    expect_error(response, Status::NotFound, "temp_login_token_not_found").await;
+
+    let response = app.client.get("/api/v1/does_not_exist").dispatch().await;
+    expect_error(response, Status::NotFound, "unknown_url").await;
+}
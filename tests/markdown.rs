@@ -0,0 +1,45 @@
+//! The Markdown renderer does not need a running app; the hostile input is generated from a fixed seed,
+//! so a failure can be reproduced. `fuzz/` has a coverage guided fuzzer for the same checks.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use acorngm_backend::markdown::{find_unsafe_html, render_description, sanitize_html};
+
+
+/// Pieces that are likely to confuse a Markdown parser or an HTML sanitizer when put together in any order.
+const FRAGMENTS: [&str; 51] = [
+    "# ", "## ", "> ", "- ", "1. ", "* ", "**", "_", "~~", "`", "```\n", "    ", "\n", "\n\n", "| a | b |\n|---|---|\n",
+    "[link](", ")", "](", "![image](", "<", ">", "</", "/>", "\"", "'", "=", "&", "&#", "&#x6A;", "&lt;", ";",
+    "<script>alert(1)</script>", "<img src=x onerror=alert(1)>", "<a href=\"javascript:alert(1)\">", "<svg onload=alert(1)>",
+    "<iframe src=//evil.example>", "<style>*{display:none}</style>", "<!--", "-->", "<![CDATA[", "<p onclick=alert(1)>",
+    "javascript:alert(1)", "JaVaScRiPt:alert(1)", "java\tscript:", "data:text/html,<script>", "https://example.com/",
+    "mailto:someone@example.com", "\u{202E}\u{200B}\u{0000}\u{1B}", "\r\n", "&#13;", "<pre>",
+];
+
+fn assert_safe(input: &str, html: &str) {
+    if let Some(problem) = find_unsafe_html(html) {
+        panic!("{problem} in {html:?} from {input:?}");
+    }
+}
+
+
+#[test]
+fn descriptions_are_rendered_to_safe_html() {
+    assert_eq!(
+        render_description("# Hard Mode\n\n**Harder** fights, see [the wiki](https://example.com/wiki).\n\n- one\n- two"),
+        "<h1>Hard Mode</h1>\n<p><strong>Harder</strong> fights, see <a href=\"https://example.com/wiki\" rel=\"nofollow noopener noreferrer\">the wiki</a>.</p>\n\
+        <ul>\n<li>one</li>\n<li>two</li>\n</ul>\n",
+    );
+    assert_eq!(render_description("[click](javascript:alert(1)) ![x](https://example.com/x.png)"), "<p><a rel=\"nofollow noopener noreferrer\">click</a> </p>\n");
+    assert_eq!(render_description("<b onclick=\"steal()\">bold</b> <script>alert(1)</script>"), "<p>bold </p>\n");
+    assert_eq!(render_description("[home](/api/v1/mods)"), "<p><a rel=\"nofollow noopener noreferrer\">home</a></p>\n");
+
+    let mut rng = StdRng::seed_from_u64(0x4ac0_7a11);
+    for _ in 0..5_000 {
+        let input: String = (0..rng.random_range(1..40))
+            .map(|_| FRAGMENTS[rng.random_range(0..FRAGMENTS.len())])
+            .collect();
+        assert_safe(&input, &render_description(&input));
+        assert_safe(&input, &sanitize_html(&input));
+    }
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use rocket::http::Status;
+use acorngm_backend::metrics::render;
+use crate::common::{expect_error, TestApp};
+
+
+#[rocket::async_test]
+async fn metrics_count_requests_uploads_and_logins() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("measured", "700000000000000001").await;
+
+    app.upload_mod(&user, "Measured Mod").file_data(b"twelve bytes").description("Counted").id().await;
+    let response = app.client.get("/api/v1/account/nobody").dispatch().await;
+    expect_error(response, Status::NotFound, "account_not_found").await;
+
+    let metrics: String = render().await.unwrap();
+    // routes are labelled by their template, not by the requested path
+    assert!(metrics.contains(r#"acorngm_http_requests_total{method="GET",route="/api/v1/account/<username>",status="404"} 1"#), "{metrics}");
+    assert!(metrics.contains(r#"acorngm_upload_bytes_total{kind="upload"} 12"#), "{metrics}");
+    assert!(metrics.contains(r#"acorngm_identity_provider_request_duration_seconds_count{operation="exchange_code",provider="discord"} 1"#), "{metrics}");
+    assert!(metrics.contains("acorngm_accounts 1"), "{metrics}");
+    assert!(metrics.contains(r#"acorngm_mods{review_state="approved"} 1"#), "{metrics}");
+    assert!(metrics.contains("# TYPE acorngm_job_runs_total counter"), "{metrics}");
+    assert!(metrics.contains(r#"acorngm_job_runs_total{job="download_count_rollup"} 0"#), "{metrics}");
+}
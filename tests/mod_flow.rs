@@ -0,0 +1,118 @@
+mod common;
+
+use rocket::http::{Method, Status};
+use rocket::serde::json::Value;
+use serde_json::json;
+use acorngm_backend::markdown::render_missing_descriptions;
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp};
+
+
+/// register → temp_login → access_token → upload → update → delete
+#[rocket::async_test]
+async fn full_mod_lifecycle() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("flow_tester", "100000000000000001").await;
+
+    // logging in again with the same discord account finds the existing account
+    let code: String = app.discord.authorize(&user.discord);
+    let response = app.client.get(format!("/api/v1/discord_auth?discord_code={code}")).dispatch().await;
+    let auth: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(auth["register"], false);
+    assert_eq!(auth["username"], "flow_tester");
+
+    // titles are counted in graphemes; look-alike letters from another script are not allowed within a word
+    for title in ["🇨🇦🇨🇦🇨🇦🇨🇦", "Int\u{435}gration Test Mod", "Integration\nTest Mod"] {
+        expect_error(app.upload_mod(&user, title).send().await, Status::BadRequest, "invalid_title").await;
+    }
+
+    // upload; invisible and reordering characters are dropped
+    let response = app.upload_mod(&user, "Integration\u{200B} Test Mod\u{202E}")
+        .file_data(b"first version")
+        .description("Uploaded by the integration tests")
+        .game("Undertale", "1.08")
+        .send().await;
+    let uploaded: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(uploaded["reviewState"], "approved");
+    let mod_id: String = uploaded["modId"].as_str().expect("No mod id in response").to_string();
+
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(summary["title"], "Integration Test Mod");
+    assert_eq!(summary["author"], "flow_tester");
+    assert_eq!(summary["modVersion"], 1);
+
+    let response = app.client.get("/api/v1/mods/search?query=integration").dispatch().await;
+    let search: Value = expect_json(response, Status::Ok).await;
+    assert!(search["mods"].as_array().unwrap().iter().any(|found| found["id"] == mod_id.as_str()));
+
+    // update
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", &mod_id)
+        .bytes("file_data", b"second version")
+        .text("description", "Updated by the **integration** tests<script>alert(1)</script>");
+    let response = app.send_multipart(Method::Patch, "/api/v1/mod", form).await;
+    expect_status(response, Status::Ok).await;
+
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(summary["description"], "Updated by the **integration** tests<script>alert(1)</script>");
+    assert_eq!(summary["descriptionHtml"], "<p>Updated by the <strong>integration</strong> tests</p>\n");
+    assert_eq!(summary["modVersion"], 2);
+
+    // changing only tags, categories or the language is not a new version
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", &mod_id)
+        .text("tags", "hard-mode")
+        .text("language", "de");
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    let retagged: Value = expect_json(response, Status::Ok).await;
+    assert_eq!((&retagged["tags"], &retagged["language"]), (&json!(["hard-mode"]), &json!("de")));
+    assert_eq!((&retagged["modVersion"], &retagged["updatedAt"]), (&summary["modVersion"], &summary["updatedAt"]));
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", &mod_id)
+        .text("tags", "boss-fight");
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    let retagged: Value = expect_json(response, Status::Ok).await;
+    assert_eq!((&retagged["tags"], &retagged["modVersion"]), (&json!(["boss-fight"]), &summary["modVersion"]));
+
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}/download")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_bytes().await.unwrap_or_default(), b"second version");
+
+    // descriptions saved before they were rendered on save have no html until the server renders them
+    let mut database = app.database().await;
+    sqlx::query("UPDATE mods SET description_html = NULL").execute(&mut database).await.unwrap();
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["descriptionHtml"], "");
+    assert_eq!(render_missing_descriptions().await.unwrap(), 1);
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["descriptionHtml"], "<p>Updated by the <strong>integration</strong> tests</p>\n");
+
+    // someone else can not touch the mod
+    let other = app.register("other_tester", "100000000000000002").await;
+    let form = Multipart::new()
+        .text("username", &other.username)
+        .text("access_token", &other.access_token)
+        .text("mod_id", &mod_id);
+    let response = app.send_multipart(Method::Delete, "/api/v1/mod", form).await;
+    expect_error(response, Status::Forbidden, "not_mod_owner").await;
+
+    // delete
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", &mod_id);
+    let response = app.send_multipart(Method::Delete, "/api/v1/mod", form).await;
+    expect_status(response, Status::Ok).await;
+
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    expect_error(response, Status::NotFound, "mod_not_found").await;
+}
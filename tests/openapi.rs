@@ -0,0 +1,18 @@
+//! `docs/openapi.json` is checked in so api changes can be diffed in review.
+//! Regenerate it with `UPDATE_OPENAPI=1 cargo test --test openapi`.
+
+use acorngm_backend::openapi::openapi_json;
+
+
+#[test]
+fn checked_in_openapi_document_is_up_to_date() {
+    let path: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/openapi.json");
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(path, openapi_json()).expect("Could not write docs/openapi.json");
+        return
+    }
+
+    let checked_in: String = std::fs::read_to_string(path).unwrap_or_default();
+    assert!(checked_in == openapi_json(),
+        "docs/openapi.json is out of date; regenerate it with `UPDATE_OPENAPI=1 cargo test --test openapi`");
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use rocket::futures::future::join_all;
+use rocket::http::{ContentType, Method, Status};
+use rocket::local::asynchronous::LocalResponse;
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp, TestUser};
+
+
+async fn rate<'a>(app: &'a TestApp, user: &TestUser, mod_id: &str, rating: i16) -> LocalResponse<'a> {
+    app.client.put(format!("/api/v1/mod/{mod_id}/rating"))
+        .header(ContentType::JSON)
+        .body(json!({ "username": user.username, "access_token": user.access_token, "rating": rating }).to_string())
+        .dispatch().await
+}
+
+#[rocket::async_test]
+async fn ratings_and_popularity_order_search_results() {
+    let app: TestApp = TestApp::spawn().await;
+    let author = app.register("remixer", "800000000000000031").await;
+    let fan = app.register("listener", "800000000000000032").await;
+    let popular: String = app.upload_mod(&author, "Popular Remix").description("A remix of the soundtrack").id().await;
+    let loved: String = app.upload_mod(&author, "Loved Remix").description("A remix of the soundtrack").id().await;
+    let updated: String = app.upload_mod(&author, "Updated Remix").description("A remix of the soundtrack").id().await;
+
+    let mut database = app.database().await;
+    sqlx::query("UPDATE mods SET download_count = 5000 WHERE id = $1::UUID").bind(&popular)
+        .execute(&mut database).await.unwrap();
+    sqlx::query("UPDATE mods SET created_at = NOW() - (title = 'Loved Remix')::INT * INTERVAL '1 day', updated_at = NOW() - INTERVAL '2 days'")
+        .execute(&mut database).await.unwrap();
+
+    // rating again replaces the earlier rating
+    let response = rate(&app, &fan, &loved, 5).await;
+    assert_eq!(expect_json(response, Status::Ok).await, json!({ "ratingAverage": 5.0, "ratingCount": 1 }));
+    let response = rate(&app, &fan, &loved, 4).await;
+    assert_eq!(expect_json(response, Status::Ok).await, json!({ "ratingAverage": 4.0, "ratingCount": 1 }));
+    expect_error(rate(&app, &author, &loved, 5).await, Status::Forbidden, "own_mod_rating").await;
+    expect_error(rate(&app, &fan, &loved, 6).await, Status::BadRequest, "invalid_rating").await;
+
+    let form = Multipart::new()
+        .text("username", &author.username)
+        .text("access_token", &author.access_token)
+        .text("mod_id", &updated)
+        .text("description", "A remix of the whole soundtrack");
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+
+    assert_eq!(app.search_titles("query=remix&sort=downloads").await[0], "Popular Remix");
+    assert_eq!(app.search_titles("query=remix&sort=rating").await[0], "Loved Remix");
+    assert_eq!(app.search_titles("query=remix&sort=updated").await[0], "Updated Remix");
+    assert_eq!(app.search_titles("query=remix&sort=newest").await[2], "Loved Remix");
+    // the same text score everywhere, so thousands of downloads beat one rating and a recent update
+    assert_eq!(app.search_titles("query=remix").await[0], "Popular Remix");
+    assert_eq!(app.search_titles("query=&sort=relevance").await[0], "Popular Remix");
+
+    let response = app.client.get(format!("/api/v1/mod/{loved}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!((summary["ratingAverage"].as_f64(), summary["ratingCount"].as_i64()), (Some(4.0), Some(1)));
+
+    let response = app.client.get("/api/v1/mods/search?query=remix&sort=random").dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_search_parameter").await;
+
+    // ratings that arrive at the same time all end up in the totals
+    let mut raters: Vec<TestUser> = Vec::new();
+    for i in 0..10 {
+        raters.push(app.register(&format!("crowd{i}"), &format!("80000000000000010{i}")).await);
+    }
+    let ratings = raters.iter().enumerate().map(|(i, rater)| rate(&app, rater, &popular, i as i16 % 5 + 1));
+    for response in join_all(ratings).await {
+        expect_status(response, Status::Ok).await;
+    }
+    let response = app.client.get(format!("/api/v1/mod/{popular}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!((summary["ratingAverage"].as_f64(), summary["ratingCount"].as_i64()), (Some(3.0), Some(10)));
+}
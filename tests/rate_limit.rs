@@ -0,0 +1,81 @@
+mod common;
+
+use std::net::SocketAddr;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::LocalResponse;
+use serde_json::json;
+use uuid::Uuid;
+use crate::common::{expect_error, expect_json, expect_status, Budget, TestApp, TestUser};
+
+
+fn header<'a>(response: &'a LocalResponse<'_>, name: &str) -> Option<&'a str> {
+    response.headers().get_one(name)
+}
+
+fn peer(ip: &str) -> SocketAddr {
+    format!("{ip}:50000").parse().unwrap()
+}
+
+async fn upload<'a>(app: &'a TestApp, user: &TestUser, ip: &str) -> LocalResponse<'a> {
+    app.upload_mod(user, "Rate Limited Mod").remote(peer(ip)).send().await
+}
+
+
+/// Budgets are small and barely refill, so the buckets run dry within the test.
+#[rocket::async_test]
+async fn buckets_run_dry_per_ip_account_and_class() {
+    let app: TestApp = TestApp::spawn_with(|figment| figment
+        .merge(("rate_limit.read", Budget { capacity: 3.0, refill_per_second: 0.01 }))
+        .merge(("rate_limit.upload", Budget { capacity: 2.0, refill_per_second: 0.01 }))
+        .merge(("rate_limit.auth", Budget { capacity: 10.0, refill_per_second: 0.01 }))
+    ).await;
+    let alice: TestUser = app.register("alice", "900000000000000001").await;
+    let bob: TestUser = app.register("bob", "900000000000000002").await;
+
+    for remaining in ["2", "1", "0"] {
+        let response = app.client.get("/api/v1/mods").remote(peer("10.0.0.1")).dispatch().await;
+        assert_eq!(header(&response, "X-RateLimit-Limit"), Some("3"));
+        assert_eq!(header(&response, "X-RateLimit-Remaining"), Some(remaining));
+        assert_eq!(header(&response, "Retry-After"), None);
+        expect_json(response, Status::Ok).await;
+    }
+    let response = app.client.get("/api/v1/mods").remote(peer("10.0.0.1")).dispatch().await;
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), Some("0"));
+    let reset: u64 = header(&response, "X-RateLimit-Reset").unwrap().parse().unwrap();
+    assert!((299..=300).contains(&reset), "{reset}");
+    let retry_after: u64 = header(&response, "Retry-After").unwrap().parse().unwrap();
+    assert!((99..=100).contains(&retry_after), "{retry_after}");
+    expect_error(response, Status::TooManyRequests, "rate_limited").await;
+    // without a proxy configured in `ip_header`, clients can not claim another ip
+    let response = app.client.get("/api/v1/mods").remote(peer("10.0.0.1")).header(Header::new("X-Real-IP", "10.9.9.9")).dispatch().await;
+    expect_error(response, Status::TooManyRequests, "rate_limited").await;
+
+    // other ips have their own buckets...
+    let response = app.client.get("/api/v1/mods").remote(peer("10.0.0.2")).dispatch().await;
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), Some("2"));
+    expect_json(response, Status::Ok).await;
+    // ...and so do the other route classes
+    let response = app.client.get("/api/v1/discord_auth?discord_code=never-issued").remote(peer("10.0.0.1")).dispatch().await;
+    assert_eq!(header(&response, "X-RateLimit-Limit"), Some("10"));
+    expect_error(response, Status::Unauthorized, "invalid_authorization_code").await;
+    let response = upload(&app, &alice, "10.0.0.1").await;
+    assert_eq!(header(&response, "X-RateLimit-Limit"), Some("2"));
+    expect_json(response, Status::Ok).await;
+
+    // an account can not get around its budget by changing ips
+    expect_json(upload(&app, &alice, "10.0.1.1").await, Status::Ok).await;
+    let response = upload(&app, &alice, "10.0.1.2").await;
+    assert!(header(&response, "Retry-After").is_some());
+    expect_error(response, Status::TooManyRequests, "account_rate_limited").await;
+    expect_json(upload(&app, &bob, "10.0.1.2").await, Status::Ok).await;
+    expect_error(upload(&app, &bob, "10.0.1.2").await, Status::TooManyRequests, "rate_limited").await;
+
+    // nobody can use up the budget of an account they have not logged into
+    for _ in 0..10 {
+        let request = json!({"temp_login_token": Uuid::new_v4().to_string(), "username": "alice"});
+        expect_status(app.client.post("/api/v1/temp_login").remote(peer("10.6.6.6")).json(&request).dispatch().await, Status::Ok).await;
+    }
+    let request = json!({"temp_login_token": Uuid::new_v4().to_string(), "username": "alice"});
+    expect_error(app.client.post("/api/v1/temp_login").remote(peer("10.6.6.6")).json(&request).dispatch().await, Status::TooManyRequests, "rate_limited").await;
+    expect_status(app.client.post("/api/v1/temp_login").remote(peer("10.0.2.1")).json(&request).dispatch().await, Status::Ok).await;
+}
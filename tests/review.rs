@@ -0,0 +1,100 @@
+mod common;
+
+use rocket::http::{Method, Status};
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp, TestUser};
+
+
+async fn own_mod(app: &TestApp, user: &TestUser) -> Value {
+    let request = json!({"username": user.username, "access_token": user.access_token});
+    let own_mods: Value = expect_json(app.client.post("/api/v1/my_mods").json(&request).dispatch().await, Status::Ok).await;
+    own_mods["mods"][0].clone()
+}
+
+async fn pending_ids(app: &TestApp, moderator: &TestUser) -> Vec<String> {
+    let request = json!({"username": moderator.username, "access_token": moderator.access_token});
+    let pending: Value = expect_json(app.client.post("/api/v1/review/pending").json(&request).dispatch().await, Status::Ok).await;
+    pending["mods"].as_array().unwrap().iter().map(|pending| pending["id"].as_str().unwrap().to_string()).collect()
+}
+
+async fn update_description(app: &TestApp, user: &TestUser, mod_id: &str, description: &str) {
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", mod_id)
+        .text("description", description);
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+}
+
+
+/// With a minimum account age, everything new accounts upload or change waits for a moderator.
+#[rocket::async_test]
+async fn new_accounts_are_reviewed_until_approved() {
+    let app: TestApp = TestApp::spawn_with(|figment| figment.merge(("review.min_account_age_days", 7))).await;
+    let newcomer: TestUser = app.register("newcomer", "600000000000000001").await;
+    let moderator: TestUser = app.register("gatekeeper", "600000000000000002").await;
+    let mut database = app.database().await;
+    sqlx::query("UPDATE accounts SET role = 'moderator' WHERE username = 'gatekeeper'")
+        .execute(&mut database).await.unwrap();
+
+    let uploaded: Value = expect_json(app.upload_mod(&newcomer, "Fresh Account Mod").send().await, Status::Ok).await;
+    assert_eq!(uploaded["reviewState"], "pending_review");
+    let mod_id: &str = uploaded["modId"].as_str().unwrap();
+    expect_error(app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await, Status::NotFound, "mod_not_found").await;
+    let response = app.client.get("/api/v1/mods/search?query=fresh").dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["mods"], json!([]));
+    // moderators do not wait for themselves
+    let moderated: Value = expect_json(app.upload_mod(&moderator, "Moderator Mod").send().await, Status::Ok).await;
+    assert_eq!(moderated["reviewState"], "approved");
+
+    let request = json!({"username": newcomer.username, "access_token": newcomer.access_token});
+    expect_error(app.client.post("/api/v1/review/pending").json(&request).dispatch().await, Status::Forbidden, "not_moderator").await;
+    let decision = |reason: Option<&str>| json!({
+        "username": moderator.username,
+        "access_token": moderator.access_token,
+        "mod_id": mod_id,
+        "reason": reason,
+    });
+    assert_eq!(pending_ids(&app, &moderator).await, [mod_id]);
+    let response = app.client.post("/api/v1/review/download").json(&decision(None)).dispatch().await;
+    assert_eq!(response.into_bytes().await.unwrap(), b"mod");
+
+    // rejections need a reason, which the uploader gets to see
+    let response = app.client.post("/api/v1/review/reject").json(&decision(Some(" \u{200B} "))).dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_rejection_reason").await;
+    let response = app.client.post("/api/v1/review/reject").json(&decision(Some("Please describe what the mod changes"))).dispatch().await;
+    expect_status(response, Status::Ok).await;
+    let rejected: Value = own_mod(&app, &newcomer).await;
+    assert_eq!((&rejected["reviewState"], &rejected["reviewReason"]), (&json!("rejected"), &json!("Please describe what the mod changes")));
+    expect_error(app.client.post("/api/v1/review/approve").json(&decision(None)).dispatch().await, Status::NotFound, "pending_mod_not_found").await;
+    assert!(pending_ids(&app, &moderator).await.is_empty());
+
+    // updating a rejected mod resubmits it
+    update_description(&app, &newcomer, mod_id, "Makes every fight a bit harder").await;
+    let resubmitted: Value = own_mod(&app, &newcomer).await;
+    assert_eq!((&resubmitted["reviewState"], &resubmitted["reviewReason"]), (&json!("pending_review"), &Value::Null));
+    expect_status(app.client.post("/api/v1/review/approve").json(&decision(None)).dispatch().await, Status::Ok).await;
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["description"], "Makes every fight a bit harder");
+
+    // retagging an approved mod keeps it public
+    let form = Multipart::new()
+        .text("username", &newcomer.username)
+        .text("access_token", &newcomer.access_token)
+        .text("mod_id", mod_id)
+        .text("tags", "hard-mode")
+        .text("language", "de");
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+    assert_eq!(own_mod(&app, &newcomer).await["reviewState"], "approved");
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["tags"], json!(["hard-mode"]));
+
+    // new versions of new accounts are reviewed again, even of approved mods
+    update_description(&app, &newcomer, mod_id, "Makes every fight a lot harder").await;
+    assert_eq!(own_mod(&app, &newcomer).await["reviewState"], "pending_review");
+    expect_error(app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await, Status::NotFound, "mod_not_found").await;
+    assert_eq!(pending_ids(&app, &moderator).await, [mod_id]);
+    expect_status(app.client.post("/api/v1/review/approve").json(&decision(None)).dispatch().await, Status::Ok).await;
+    assert_eq!(own_mod(&app, &newcomer).await["reviewState"], "approved");
+}
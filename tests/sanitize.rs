@@ -0,0 +1,46 @@
+//! The text normalization does not need a running app.
+
+use acorngm_backend::sanitize::{confusable_skeleton, find_mixed_script_word, grapheme_length, sanitize_string};
+
+
+#[test]
+fn text_is_normalized_and_cleaned() {
+    // decomposed é becomes the precomposed one
+    assert_eq!(sanitize_string("Cafe\u{301} Mod").as_deref(), Some("Caf\u{E9} Mod"));
+    assert_eq!(sanitize_string("  line\r\nbreak\rhere\t ").as_deref(), Some("line\nbreak\nhere"));
+    assert_eq!(sanitize_string("null\u{0}bell\u{7}escape\u{1B}[31m").as_deref(), Some("nullbellescape[31m"));
+    assert_eq!(sanitize_string("Hard\u{202E}edoM\u{202C} \u{2067}x\u{2069}").as_deref(), Some("HardedoM x"));
+    assert_eq!(sanitize_string("Ha\u{200B}rd\u{FEFF} Mo\u{2060}de\u{200D}").as_deref(), Some("Hard Mode"));
+    assert_eq!(sanitize_string("“Fancy” – quotes").as_deref(), Some("\"Fancy\" - quotes"));
+    assert_eq!(sanitize_string(" \u{200B}\u{3164}\n"), None);
+
+    // joiners inside emoji sequences and scripts that need them stay
+    let family: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    assert_eq!(sanitize_string(family).as_deref(), Some(family));
+    let persian: &str = "\u{645}\u{6CC}\u{200C}\u{62E}\u{648}\u{627}\u{647}\u{645}";
+    assert_eq!(sanitize_string(persian).as_deref(), Some(persian));
+    // left-to-right and right-to-left marks are needed to mix directions
+    assert_eq!(sanitize_string("abc\u{200F}def").as_deref(), Some("abc\u{200F}def"));
+}
+
+#[test]
+fn lengths_are_counted_in_graphemes() {
+    assert_eq!(grapheme_length("Mod"), 3);
+    assert_eq!(grapheme_length("Cafe\u{301}"), 4);
+    assert_eq!(grapheme_length("\u{1F1E8}\u{1F1E6}\u{1F1E8}\u{1F1E6}"), 2);
+    assert_eq!(grapheme_length("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 1);
+}
+
+#[test]
+fn look_alikes_are_detected() {
+    // the "а" is Cyrillic
+    assert_eq!(find_mixed_script_word("Hard Mode H\u{430}rd"), Some("H\u{430}rd"));
+    assert_eq!(find_mixed_script_word("Hard Mode \u{0422}\u{0440}\u{0443}\u{0434}\u{043D}\u{043E}"), None);
+    assert_eq!(find_mixed_script_word("デルタルーン改造 2.0"), None);
+
+    assert_eq!(confusable_skeleton("AIice"), confusable_skeleton("alice"));
+    assert_eq!(confusable_skeleton("Kris"), confusable_skeleton("kris"));
+    assert_eq!(confusable_skeleton("rnoss"), confusable_skeleton("moss"));
+    assert_eq!(confusable_skeleton("g0ner"), confusable_skeleton("goner"));
+    assert_ne!(confusable_skeleton("alice"), confusable_skeleton("alicia"));
+}
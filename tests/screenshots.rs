@@ -0,0 +1,143 @@
+mod common;
+
+use std::io::Cursor;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use rocket::http::{ContentType, Method, Status};
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp, TestUser};
+
+
+fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+    let image = DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| image::Rgb([x as u8, y as u8, 128])));
+    let mut data: Vec<u8> = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), format).unwrap();
+    data
+}
+
+/// A JPEG with an EXIF block that says "rotate 90° clockwise" and carries some private text.
+fn jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+    let jpeg: Vec<u8> = encode(width, height, ImageFormat::Jpeg);
+    let mut tiff: Vec<u8> = b"MM\0\x2a\0\0\0\x08".to_vec();
+    tiff.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]);
+    tiff.extend_from_slice(b"GPS 51.5N 0.1W");
+    let mut app1: Vec<u8> = b"Exif\0\0".to_vec();
+    app1.extend_from_slice(&tiff);
+    let mut data: Vec<u8> = jpeg[..2].to_vec();
+    data.extend_from_slice(&[0xFF, 0xE1]);
+    data.extend_from_slice(&((app1.len() + 2) as u16).to_be_bytes());
+    data.extend_from_slice(&app1);
+    data.extend_from_slice(&jpeg[2..]);
+    data
+}
+
+async fn upload(app: &TestApp, user: &TestUser, mod_id: &str, image: &[u8], cover: bool) -> Value {
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .bytes("image", image)
+        .text("cover", &cover.to_string());
+    let response = app.send_multipart(Method::Put, &format!("/api/v1/mod/{mod_id}/screenshots"), form).await;
+    expect_json(response, Status::Ok).await
+}
+
+async fn gallery(app: &TestApp, mod_id: &str) -> Vec<(String, bool)> {
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}/screenshots")).dispatch().await;
+    let list: Value = expect_json(response, Status::Ok).await;
+    list["screenshots"].as_array().unwrap().iter()
+        .map(|info| (info["id"].as_str().unwrap().to_string(), info["cover"].as_bool().unwrap()))
+        .collect()
+}
+
+
+#[rocket::async_test]
+async fn screenshots_are_uploaded_cleaned_and_arranged() {
+    let app: TestApp = TestApp::spawn().await;
+    let author = app.register("photographer", "800000000000000051").await;
+    let stranger = app.register("passerby", "800000000000000052").await;
+
+    let mod_id: &str = &app.upload_mod(&author, "Scenic Ruins").description("Prettier ruins").id().await;
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["coverScreenshotId"], Value::Null);
+    let response = app.client.get("/api/v1/mod/00000000-0000-4000-8000-000000000000/screenshots").dispatch().await;
+    expect_error(response, Status::NotFound, "mod_not_found").await;
+
+    // EXIF is applied to the pixels and then dropped, the thumbnail fits the 480x270 box
+    let first: Value = upload(&app, &author, mod_id, &jpeg_with_exif(1200, 600), false).await;
+    assert_eq!((&first["contentType"], &first["width"], &first["height"]), (&json!("image/jpeg"), &json!(600), &json!(1200)));
+    assert_eq!((&first["position"], &first["cover"]), (&json!(0), &json!(true)));
+    let first_id: &str = first["id"].as_str().unwrap();
+    let response = app.client.get(format!("/api/v1/screenshots/{first_id}")).dispatch().await;
+    assert_eq!(response.content_type(), Some(ContentType::JPEG));
+    assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=604800, immutable"));
+    let stored: Vec<u8> = response.into_bytes().await.unwrap();
+    assert!(!stored.windows(4).any(|window| window == b"Exif" || window == b"GPS "));
+    let response = app.client.get(format!("/api/v1/screenshots/{first_id}/thumbnail")).dispatch().await;
+    let thumbnail = image::load_from_memory(&response.into_bytes().await.unwrap()).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (135, 270));
+
+    // WebP becomes PNG; small images are not scaled up
+    let second: Value = upload(&app, &author, mod_id, &encode(64, 48, ImageFormat::WebP), true).await;
+    assert_eq!((&second["contentType"], &second["position"], &second["cover"]), (&json!("image/png"), &json!(1), &json!(true)));
+    let second_id: &str = second["id"].as_str().unwrap();
+    let response = app.client.get(format!("/api/v1/screenshots/{second_id}/thumbnail")).dispatch().await;
+    assert_eq!(response.content_type(), Some(ContentType::PNG));
+    let thumbnail = image::load_from_memory(&response.into_bytes().await.unwrap()).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (64, 48));
+    let response = app.client.get(format!("/api/v1/mod/{mod_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["coverScreenshotId"], json!(second_id));
+
+    for (image, status, code) in [
+        (b"not an image".to_vec(), Status::BadRequest, "invalid_image"),
+        (b"GIF89a\x01\x00\x01\x00\x00\x00\x00;".to_vec(), Status::BadRequest, "invalid_image"),
+        (encode(8193, 1, ImageFormat::Png), Status::BadRequest, "invalid_image"),
+        (vec![0; 9 * 1024 * 1024], Status::PayloadTooLarge, "image_too_large"),
+    ] {
+        let form = Multipart::new()
+            .text("username", &author.username)
+            .text("access_token", &author.access_token)
+            .bytes("image", &image);
+        expect_error(app.send_multipart(Method::Put, &format!("/api/v1/mod/{mod_id}/screenshots"), form).await, status, code).await;
+    }
+    let form = Multipart::new()
+        .text("username", &stranger.username)
+        .text("access_token", &stranger.access_token)
+        .bytes("image", &encode(8, 8, ImageFormat::Png));
+    expect_error(app.send_multipart(Method::Put, &format!("/api/v1/mod/{mod_id}/screenshots"), form).await, Status::Forbidden, "not_mod_owner").await;
+
+    let mut ids: Vec<String> = vec![first_id.to_string(), second_id.to_string()];
+    for _ in 2..8 {
+        ids.push(upload(&app, &author, mod_id, &encode(8, 8, ImageFormat::Png), false).await["id"].as_str().unwrap().to_string());
+    }
+    let form = Multipart::new()
+        .text("username", &author.username)
+        .text("access_token", &author.access_token)
+        .bytes("image", &encode(8, 8, ImageFormat::Png));
+    expect_error(app.send_multipart(Method::Put, &format!("/api/v1/mod/{mod_id}/screenshots"), form).await, Status::Conflict, "too_many_screenshots").await;
+
+    // the order has to be a permutation of the gallery
+    let uri: String = format!("/api/v1/mod/{mod_id}/screenshots");
+    let request = json!({"username": author.username, "access_token": author.access_token, "order": &ids[1..]});
+    expect_error(app.client.patch(&uri).json(&request).dispatch().await, Status::BadRequest, "invalid_screenshot_order").await;
+    let request = json!({"username": author.username, "access_token": author.access_token});
+    expect_error(app.client.patch(&uri).json(&request).dispatch().await, Status::BadRequest, "nothing_to_update").await;
+    ids.reverse();
+    let request = json!({"username": author.username, "access_token": author.access_token, "order": ids, "cover": ids[0]});
+    let response = app.client.patch(&uri).json(&request).dispatch().await;
+    let arranged: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(arranged["screenshots"][0]["id"], json!(ids[0]));
+    assert_eq!(arranged["screenshots"][7]["position"], json!(7));
+    let expected: Vec<(String, bool)> = ids.iter().enumerate().map(|(i, id)| (id.clone(), i == 0)).collect();
+    assert_eq!(gallery(&app, mod_id).await, expected);
+
+    // deleting the cover falls back to the first screenshot; later ones move up
+    let request = json!({"username": author.username, "access_token": author.access_token});
+    expect_status(app.client.delete(format!("{uri}/{}", ids[0])).json(&request).dispatch().await, Status::Ok).await;
+    expect_error(app.client.delete(format!("{uri}/{}", ids[0])).json(&request).dispatch().await, Status::NotFound, "screenshot_not_found").await;
+    let expected: Vec<(String, bool)> = ids[1..].iter().enumerate().map(|(i, id)| (id.clone(), i == 0)).collect();
+    assert_eq!(gallery(&app, mod_id).await, expected);
+    let response = app.client.get("/api/v1/mods/search?query=scenic").dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["mods"][0]["coverScreenshotId"], json!(ids[1]));
+    let request = json!({"username": author.username, "access_token": author.access_token, "cover": ids[0]});
+    expect_error(app.client.patch(&uri).json(&request).dispatch().await, Status::NotFound, "screenshot_not_found").await;
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use rocket::http::Status;
+use crate::common::{expect_error, TestApp};
+
+
+#[rocket::async_test]
+async fn search_modes_match_typos_and_partial_words() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("searcher", "800000000000000001").await;
+    app.upload_mod(&user, "Deltarune Soundtrack Remix").description("Every song of chapter one, remixed").id().await;
+    app.upload_mod(&user, "Spamton Shop Extended").description("More items in the basement").id().await;
+
+    assert_eq!(app.search_titles("query=soundtrack").await, ["Deltarune Soundtrack Remix"]);
+    // a typo or a partial word finds nothing in basic mode
+    assert!(app.search_titles("query=soundtrac").await.is_empty());
+    assert!(app.search_titles("query=sound").await.is_empty());
+
+    assert_eq!(app.search_titles("query=soundtrac&mode=fuzzy").await, ["Deltarune Soundtrack Remix"]);
+    assert_eq!(app.search_titles("query=spamton%20shopp&mode=fuzzy").await, ["Spamton Shop Extended"]);
+    assert_eq!(app.search_titles("query=sound%20rem&mode=prefix").await, ["Deltarune Soundtrack Remix"]);
+    assert!(app.search_titles("query=zzzz&mode=fuzzy").await.is_empty());
+
+    let response = app.client.get("/api/v1/mods/search?query=sound&mode=psychic").dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_search_parameter").await;
+}
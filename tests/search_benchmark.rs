@@ -0,0 +1,129 @@
+//! Full text search before and after the stored search vectors of migration `0011_search_vectors.sql`,
+//! and `GET mods/suggest` against its 20ms budget, on a seeded catalog of 100k mods. Takes a while, so it only runs when asked for:
+//! `cargo test --release --test search_benchmark -- --ignored --nocapture`
+
+mod common;
+
+use std::time::{Duration, Instant};
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use sqlx::PgConnection;
+use crate::common::{expect_json, TestApp};
+
+
+const CATALOG_SIZE: i32 = 100_000;
+const RUNS: usize = 5;
+const QUERIES: [&str; 3] = ["spamton", "boss & rush", "music & remix & chapter"];
+/// From prefixes of thousands of titles or hundreds of authors to one that only matches a single author.
+const SUGGEST_PREFIXES: [&str; 6] = ["s", "spam", "ralsei%20music", "author", "author4", "author499"];
+const SUGGEST_BUDGET: Duration = Duration::from_millis(20);
+
+/// What the search matched and ranked with before, computing the vectors of every row per query.
+const PER_ROW_VECTORS: &str = r#"
+    SELECT id,
+        ts_rank_cd(setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', description), 'B'), to_tsquery('english', $1)) AS relevance
+    FROM mods
+    WHERE review_state = 'approved' AND (
+        to_tsvector('english', title) @@ to_tsquery('english', $1) OR
+        to_tsvector('english', description) @@ to_tsquery('english', $1)
+    )
+    ORDER BY relevance DESC
+    LIMIT 50
+"#;
+
+const STORED_VECTORS: &str = r#"
+    SELECT id, ts_rank_cd(search_vector, to_tsquery('english', $1)) AS relevance
+    FROM mods
+    WHERE review_state = 'approved' AND search_vector @@ to_tsquery('english', $1)
+    ORDER BY relevance DESC
+    LIMIT 50
+"#;
+
+
+async fn seed_catalog(database: &mut PgConnection) {
+    sqlx::query("INSERT INTO accounts (username, created_at) SELECT 'author' || i, NOW() FROM generate_series(1, 500) AS i")
+        .execute(&mut *database).await.unwrap();
+    // a few common words, so some queries match thousands of mods, plus unique noise
+    sqlx::query(
+        r#"
+        INSERT INTO mods (id, author, file_data, title, description, game_name, game_version_major, game_version_minor)
+        SELECT
+            gen_random_uuid(),
+            'author' || (1 + i % 500),
+            '\x00',
+            (ARRAY['Spamton', 'Sans', 'Susie', 'Ralsei', 'Noelle', 'Berdly', 'Queen', 'Jevil'])[1 + i % 8]
+                || ' ' || (ARRAY['Boss Rush', 'Music Remix', 'Hard Mode', 'Shop', 'Skin', 'Translation'])[1 + i % 6]
+                || ' ' || left(md5(i::TEXT), 8),
+            'Adds ' || (ARRAY['new dialogue', 'a remix of the chapter music', 'harder bosses', 'items'])[1 + i % 4]
+                || ' to chapter ' || (1 + i % 4) || '. ' || md5(i::TEXT),
+            'Deltarune', 0, 1
+        FROM generate_series(1, $1) AS i
+        "#,
+    )
+        .bind(CATALOG_SIZE)
+        .execute(&mut *database).await.unwrap();
+    // what autovacuum does to a live table; without the visibility map the author counts of `mods/suggest`
+    // can not be answered from the index alone
+    sqlx::query("VACUUM ANALYZE mods").execute(&mut *database).await.unwrap();
+}
+
+/// Median of a few runs, after one to warm the caches.
+async fn time_query(database: &mut PgConnection, sql: &str, ts_query: &str) -> (Duration, usize) {
+    let mut durations: Vec<Duration> = Vec::new();
+    let mut matches: usize = 0;
+    for _ in 0..=RUNS {
+        let started = Instant::now();
+        matches = sqlx::query(sql).bind(ts_query).fetch_all(&mut *database).await.unwrap().len();
+        durations.push(started.elapsed());
+    }
+    durations.remove(0);
+    durations.sort();
+    (durations[RUNS / 2], matches)
+}
+
+/// Median of a few whole requests, after one to warm the caches.
+async fn time_suggest(app: &TestApp, prefix: &str) -> (Duration, usize) {
+    let mut durations: Vec<Duration> = Vec::new();
+    let mut suggestions: usize = 0;
+    for _ in 0..=RUNS {
+        let started = Instant::now();
+        let response = app.client.get(format!("/api/v1/mods/suggest?q={prefix}")).dispatch().await;
+        let body: Value = expect_json(response, Status::Ok).await;
+        durations.push(started.elapsed());
+        suggestions = body["titles"].as_array().unwrap().len() + body["authors"].as_array().unwrap().len();
+    }
+    durations.remove(0);
+    durations.sort();
+    (durations[RUNS / 2], suggestions)
+}
+
+
+#[rocket::async_test]
+#[ignore = "seeds 100k mods; run with --ignored"]
+async fn stored_search_vectors_are_faster_than_per_row_vectors() {
+    let app: TestApp = TestApp::spawn().await;
+    let mut database = app.database().await;
+    seed_catalog(&mut database).await;
+
+    println!("{CATALOG_SIZE} mods, median of {RUNS} runs");
+    for ts_query in QUERIES {
+        let (before, before_matches) = time_query(&mut database, PER_ROW_VECTORS, ts_query).await;
+        let (after, after_matches) = time_query(&mut database, STORED_VECTORS, ts_query).await;
+        println!("{ts_query:<24} per row: {before:>10.2?}  stored: {after:>10.2?}  ({:.0}x)", before.as_secs_f64() / after.as_secs_f64());
+        assert_eq!(before_matches, after_matches);
+        assert!(after < before, "{ts_query}: stored vectors took {after:?}, per row vectors {before:?}");
+    }
+
+    // the whole request, including the facet counts
+    let started = Instant::now();
+    let response = app.client.get("/api/v1/mods/search?query=boss%20rush").dispatch().await;
+    let results: Value = expect_json(response, Status::Ok).await;
+    println!("GET mods/search?query=boss rush: {:.2?} ({} mods)", started.elapsed(), results["mods"].as_array().unwrap().len());
+
+    for prefix in SUGGEST_PREFIXES {
+        let (duration, suggestions) = time_suggest(&app, prefix).await;
+        println!("GET mods/suggest?q={prefix:<16} {duration:>10.2?}  ({suggestions} suggestions)");
+        assert!(suggestions > 0, "{prefix}: no suggestions");
+        assert!(duration < SUGGEST_BUDGET, "{prefix}: suggestions took {duration:?}");
+    }
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, TestApp};
+
+
+async fn search(app: &TestApp, query: &str) -> Value {
+    let response = app.client.get(format!("/api/v1/mods/search?{query}")).dispatch().await;
+    expect_json(response, Status::Ok).await
+}
+
+
+#[rocket::async_test]
+async fn search_filters_narrow_results_and_count_facets() {
+    let app: TestApp = TestApp::spawn().await;
+    let alice = app.register("alice", "800000000000000002").await;
+    let bob = app.register("bob", "800000000000000003").await;
+    app.upload_mod(&alice, "Hard Mode").description("Enemies hit twice as hard").game("Undertale", "1.19").id().await;
+    app.upload_mod(&alice, "Hard Mode Plus").description("Even harder enemies").id().await;
+    app.upload_mod(&bob, "Easy Mode").description("Enemies barely hit").game("Undertale", "1.19").id().await;
+
+    let mut database = app.database().await;
+    sqlx::query("INSERT INTO mod_tags (mod_id, tag) SELECT id, tag FROM mods, UNNEST($1::TEXT[]) AS tag WHERE title = $2")
+        .bind(vec!["difficulty", "gameplay"]).bind("Hard Mode")
+        .execute(&mut database).await.unwrap();
+    sqlx::query("INSERT INTO mod_tags (mod_id, tag) SELECT id, 'difficulty' FROM mods WHERE title <> 'Hard Mode'")
+        .execute(&mut database).await.unwrap();
+
+    assert_eq!(app.search_titles("query=enemies&game=Undertale&author=alice").await, ["Hard Mode"]);
+    assert_eq!(app.search_titles("query=&game_version=1.0").await, ["Hard Mode Plus"]);
+    assert_eq!(app.search_titles("query=&tags=difficulty,gameplay").await, ["Hard Mode"]);
+    assert!(app.search_titles("query=&updated_since=2999-01-01T00:00:00Z").await.is_empty());
+    assert_eq!(app.search_titles("query=&updated_since=2000-01-01T00:00:00Z").await.len(), 3);
+
+    // the game counts ignore the game filter, all other counts are narrowed by it
+    let body: Value = search(&app, "query=enemies&game=Undertale").await;
+    assert_eq!(body["mods"].as_array().unwrap().len(), 2);
+    let facets: &Value = &body["facets"];
+    assert_eq!(facets["games"], json!([{ "value": "Undertale", "count": 2 }, { "value": "Deltarune", "count": 1 }]));
+    assert_eq!(facets["gameVersions"], json!([{ "value": "1.19", "count": 2 }]));
+    assert_eq!(facets["authors"], json!([{ "value": "alice", "count": 1 }, { "value": "bob", "count": 1 }]));
+    assert_eq!(facets["tags"], json!([{ "value": "difficulty", "count": 2 }, { "value": "gameplay", "count": 1 }]));
+
+    for query in ["game=Minecraft", "game_version=latest", "updated_since=yesterday"] {
+        let response = app.client.get(format!("/api/v1/mods/search?query=&{query}")).dispatch().await;
+        expect_error(response, Status::BadRequest, "invalid_search_parameter").await;
+    }
+}
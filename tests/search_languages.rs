@@ -0,0 +1,42 @@
+mod common;
+
+use rocket::http::{Method, Status};
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp};
+
+
+#[rocket::async_test]
+async fn search_stems_per_language_and_falls_back_to_exact_words() {
+    let app: TestApp = TestApp::spawn().await;
+    let user = app.register("polyglot", "800000000000000021").await;
+    let spanish_id: String = app.upload_mod(&user, "Canciones remezcladas").description("Toda la banda sonora").language("es").id().await;
+    app.upload_mod(&user, "Remixed songs").description("The whole soundtrack").id().await;
+    let japanese_id: String = app.upload_mod(&user, "Hado modo tsuika").description("Teki ga tsuyoku naru").language("ja").id().await;
+
+    let response = app.client.get(format!("/api/v1/mod/{spanish_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["language"], "es");
+
+    // Spanish stemming matches another form of the word; English stemming does not
+    assert_eq!(app.search_titles("query=remezclado&language=es").await, ["Canciones remezcladas"]);
+    assert!(app.search_titles("query=remezclado").await.is_empty());
+    // words as they are written match in any language
+    assert_eq!(app.search_titles("query=canciones").await, ["Canciones remezcladas"]);
+    assert_eq!(app.search_titles("query=remixed&language=es").await, ["Remixed songs"]);
+    // no stemmer for Japanese, only exact words
+    assert_eq!(app.search_titles("query=tsuika&language=ja").await, ["Hado modo tsuika"]);
+
+    // the language can be corrected later
+    let form = Multipart::new()
+        .text("username", &user.username)
+        .text("access_token", &user.access_token)
+        .text("mod_id", &japanese_id)
+        .text("language", "en");
+    let response = app.send_multipart(Method::Patch, "/api/v1/mod", form).await;
+    expect_status(response, Status::Ok).await;
+    let response = app.client.get(format!("/api/v1/mod/{japanese_id}")).dispatch().await;
+    assert_eq!(expect_json(response, Status::Ok).await["language"], "en");
+
+    let response = app.upload_mod(&user, "Spanish mod").description("Hola").language("spanish").send().await;
+    expect_error(response, Status::BadRequest, "invalid_language").await;
+    let response = app.client.get("/api/v1/mods/search?query=hola&language=ES").dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_search_parameter").await;
+}
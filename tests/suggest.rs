@@ -0,0 +1,47 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, TestApp};
+
+
+async fn suggest(app: &TestApp, q: &str) -> Value {
+    let response = app.client.get(format!("/api/v1/mods/suggest?q={q}")).dispatch().await;
+    expect_json(response, Status::Ok).await
+}
+
+fn titles(suggestions: &Value) -> Vec<&str> {
+    suggestions["titles"].as_array().unwrap().iter().map(|title| title["title"].as_str().unwrap()).collect()
+}
+
+
+#[rocket::async_test]
+async fn suggestions_complete_titles_and_authors_by_prefix() {
+    let app: TestApp = TestApp::spawn().await;
+    let spam = app.register("spamfan", "800000000000000011").await;
+    let kris = app.register("kris", "800000000000000012").await;
+    app.upload_mod(&spam, "Spamton Shop Extended").id().await;
+    app.upload_mod(&spam, "Spamton NEO Boss Rush").id().await;
+    app.upload_mod(&kris, "Sans Fight Remastered").id().await;
+
+    let mut database = app.database().await;
+    sqlx::query("UPDATE mods SET download_count = 100 WHERE title = 'Spamton NEO Boss Rush'")
+        .execute(&mut database).await.unwrap();
+
+    // most downloaded first, case does not matter
+    let suggestions: Value = suggest(&app, "spamton").await;
+    assert_eq!(titles(&suggestions), ["Spamton NEO Boss Rush", "Spamton Shop Extended"]);
+    assert_eq!(suggestions["authors"], json!([]));
+    assert_eq!(titles(&suggest(&app, "SPAMTON%20s").await), ["Spamton Shop Extended"]);
+
+    // a prefix can match both titles and authors; a word in the middle of a title does not count
+    let suggestions: Value = suggest(&app, "s").await;
+    assert_eq!(titles(&suggestions).len(), 3);
+    assert_eq!(suggestions["authors"], json!([{ "username": "spamfan", "modCount": 2 }]));
+    assert!(titles(&suggest(&app, "shop").await).is_empty());
+
+    assert_eq!(suggest(&app, "").await, json!({ "titles": [], "authors": [] }));
+    let response = app.client.get(format!("/api/v1/mods/suggest?q={}", "a".repeat(101))).dispatch().await;
+    expect_error(response, Status::BadRequest, "invalid_search_parameter").await;
+}
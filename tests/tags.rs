@@ -0,0 +1,82 @@
+mod common;
+
+use rocket::http::{Method, Status};
+use rocket::serde::json::Value;
+use serde_json::json;
+use crate::common::{expect_error, expect_json, expect_status, Multipart, TestApp};
+
+
+async fn listed_titles(app: &TestApp, uri: &str) -> Vec<String> {
+    let response = app.client.get(uri).dispatch().await;
+    let body: Value = expect_json(response, Status::Ok).await;
+    let mut titles: Vec<String> = body["mods"].as_array().unwrap().iter().map(|summary| summary["title"].as_str().unwrap().to_string()).collect();
+    titles.sort();
+    titles
+}
+
+
+#[rocket::async_test]
+async fn mods_are_tagged_categorized_and_browsed() {
+    let app: TestApp = TestApp::spawn().await;
+    let author = app.register("tagger", "800000000000000041").await;
+    let moderator = app.register("curator", "800000000000000042").await;
+    let mut database = app.database().await;
+    sqlx::query("UPDATE accounts SET role = 'moderator' WHERE username = 'curator'")
+        .execute(&mut database).await.unwrap();
+
+    // tags are normalized, categories are a fixed set
+    let hard: String = app.upload_mod(&author, "Hard Mode Plus").tags(" Boss_Fight ,Hard  Mode,hard-mode").categories("gameplay,content").id().await;
+    let response = app.client.get(format!("/api/v1/mod/{hard}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(summary["tags"], json!(["boss-fight", "hard-mode"]));
+    assert_eq!(summary["categories"], json!(["content", "gameplay"]));
+    app.upload_mod(&author, "Bossfight Remix").tags("bossfight").categories("cosmetic").id().await;
+
+    for (tags, categories, code) in [
+        ("a", "", "invalid_tag"),
+        ("boss<script>", "", "invalid_tag"),
+        ("a1,a2,a3,a4,a5,a6,a7,a8,a9,a10,a11", "", "invalid_tag"),
+        ("", "cheats", "invalid_category"),
+    ] {
+        let response = app.upload_mod(&author, "Invalid Tags").tags(tags).categories(categories).send().await;
+        expect_error(response, Status::BadRequest, code).await;
+    }
+
+    // only moderators pick canonical tags; existing mods are retagged
+    let request = json!({"username": author.username, "access_token": author.access_token, "aliases": ["bossfight"]});
+    expect_error(app.client.put("/api/v1/tags/boss-fight").json(&request).dispatch().await, Status::Forbidden, "not_moderator").await;
+    let request = json!({"username": moderator.username, "access_token": moderator.access_token, "aliases": ["Bossfight", "boss fights"]});
+    let response = app.client.put("/api/v1/tags/boss-fight").json(&request).dispatch().await;
+    assert_eq!(
+        expect_json(response, Status::Ok).await,
+        json!({"tag": "boss-fight", "canonical": true, "aliases": ["boss-fights", "bossfight"], "modCount": 2}),
+    );
+    let request = json!({"username": moderator.username, "access_token": moderator.access_token, "aliases": ["boss-fight"]});
+    expect_error(app.client.put("/api/v1/tags/bossfight").json(&request).dispatch().await, Status::BadRequest, "invalid_tag").await;
+
+    // aliases given later resolve to the canonical tag
+    let form = Multipart::new()
+        .text("username", &author.username)
+        .text("access_token", &author.access_token)
+        .text("mod_id", &hard)
+        .text("tags", "boss-fights")
+        .text("categories", "");
+    expect_status(app.send_multipart(Method::Patch, "/api/v1/mod", form).await, Status::Ok).await;
+    let response = app.client.get(format!("/api/v1/mod/{hard}")).dispatch().await;
+    let summary: Value = expect_json(response, Status::Ok).await;
+    assert_eq!((&summary["tags"], &summary["categories"]), (&json!(["boss-fight"]), &json!([])));
+
+    assert_eq!(listed_titles(&app, "/api/v1/tags/bossfight/mods").await, ["Bossfight Remix", "Hard Mode Plus"]);
+    assert_eq!(listed_titles(&app, "/api/v1/categories/cosmetic/mods").await, ["Bossfight Remix"]);
+    assert_eq!(listed_titles(&app, "/api/v1/mods/search?query=&tags=Bossfight").await, ["Bossfight Remix", "Hard Mode Plus"]);
+    expect_error(app.client.get("/api/v1/categories/cheats/mods").dispatch().await, Status::BadRequest, "invalid_category").await;
+
+    let response = app.client.get("/api/v1/tags").dispatch().await;
+    let tags: Value = expect_json(response, Status::Ok).await;
+    assert_eq!(tags["tags"][0], json!({"tag": "boss-fight", "canonical": true, "aliases": ["boss-fights", "bossfight"], "modCount": 2}));
+
+    let request = json!({"username": moderator.username, "access_token": moderator.access_token});
+    expect_status(app.client.delete("/api/v1/tags/boss-fight").json(&request).dispatch().await, Status::Ok).await;
+    expect_error(app.client.delete("/api/v1/tags/boss-fight").json(&request).dispatch().await, Status::NotFound, "canonical_tag_not_found").await;
+    assert_eq!(listed_titles(&app, "/api/v1/tags/bossfight/mods").await, Vec::<String>::new());
+}